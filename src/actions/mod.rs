@@ -1,5 +1,7 @@
 use log::{error, warn};
 
+use crate::networking::openvpn::Signal;
+
 pub mod runner;
 use runner::{ActionRunner, RealActionRunner};
 
@@ -8,6 +10,13 @@ pub enum Action {
     Reboot,
     RestartUnit(String),
     Command(String), // fallback: executes an external command
+    /// Send `signal` to OpenVPN over its management interface at `management`.
+    OpenVpnSignal {
+        management: String,
+        signal: Signal,
+    },
+    /// Activate a NetworkManager connection profile (id or UUID).
+    NmActivate(String),
 }
 
 /// Parse an action type and argument into an `Action` enum.
@@ -16,12 +25,24 @@ pub enum Action {
 /// - "reboot" -> `Action::Reboot`
 /// - "restart-unit" -> `Action::RestartUnit(arg)`
 /// - "command" -> `Action::Command(arg)`
+/// - "openvpn-sigusr1" / "openvpn-sighup" -> `Action::OpenVpnSignal` with the
+///   management interface address (`host:port` or socket path) as `arg`
+/// - "nm-activate" -> `Action::NmActivate(arg)`
 ///   Any other value will be logged as a warning and treated as a `Command` fallback.
 pub fn parse_action(action_type: &str, arg: &str) -> Action {
     match action_type {
         "reboot" => Action::Reboot,
         "restart-unit" => Action::RestartUnit(arg.to_string()),
         "command" => Action::Command(arg.to_string()),
+        "openvpn-sigusr1" => Action::OpenVpnSignal {
+            management: arg.to_string(),
+            signal: Signal::Usr1,
+        },
+        "openvpn-sighup" => Action::OpenVpnSignal {
+            management: arg.to_string(),
+            signal: Signal::Hup,
+        },
+        "nm-activate" => Action::NmActivate(arg.to_string()),
         other => {
             warn!(
                 "Unknown action type '{}', falling back to command with given arg",
@@ -58,6 +79,17 @@ mod tests {
             parse_action("command", "echo hi"),
             Action::Command("echo hi".to_string())
         );
+        assert_eq!(
+            parse_action("openvpn-sighup", "127.0.0.1:7505"),
+            Action::OpenVpnSignal {
+                management: "127.0.0.1:7505".to_string(),
+                signal: Signal::Hup
+            }
+        );
+        assert_eq!(
+            parse_action("nm-activate", "work-vpn"),
+            Action::NmActivate("work-vpn".to_string())
+        );
     }
 
     #[test]
@@ -77,5 +109,14 @@ mod tests {
         assert!(runner
             .execute(&Action::Command("echo hi".into()), true)
             .is_ok());
+        assert!(runner
+            .execute(
+                &Action::OpenVpnSignal {
+                    management: "/run/openvpn/client.sock".into(),
+                    signal: Signal::Usr1
+                },
+                true
+            )
+            .is_ok());
     }
 }
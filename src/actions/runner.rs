@@ -4,6 +4,8 @@ use std::process::Command;
 use zbus::blocking::Connection;
 
 use super::Action;
+use crate::networking::network_manager;
+use crate::networking::openvpn::{self, ManagementAddr, Signal};
 
 /// Trait allowing injection of action execution implementations for testing.
 ///
@@ -89,6 +91,41 @@ impl RealActionRunner {
             }
         }
     }
+
+    fn do_openvpn_signal(&self, management: &str, signal: Signal, dry_run: bool) -> Result<()> {
+        if dry_run {
+            info!(
+                "[dry-run] would send {} to OpenVPN via management interface {}",
+                signal, management
+            );
+            return Ok(());
+        }
+
+        let addr: ManagementAddr = management.parse().map_err(anyhow::Error::msg)?;
+        let mut mgmt = openvpn::Management::connect(&addr, openvpn::MANAGEMENT_TIMEOUT)?;
+        mgmt.signal(signal)
+            .with_context(|| format!("failed to send {} via {}", signal, addr))?;
+        info!("Sent {} to OpenVPN via {}", signal, addr);
+        Ok(())
+    }
+
+    fn do_nm_activate(&self, name: &str, dry_run: bool) -> Result<()> {
+        if dry_run {
+            info!(
+                "[dry-run] would activate NetworkManager connection '{}'",
+                name
+            );
+            return Ok(());
+        }
+
+        let conn = crate::networking::system_bus()?;
+        let active = network_manager::activate(&conn, name)?;
+        info!(
+            "Activated NetworkManager connection '{}' ({})",
+            name, active
+        );
+        Ok(())
+    }
 }
 
 impl Default for RealActionRunner {
@@ -103,6 +140,10 @@ impl ActionRunner for RealActionRunner {
             Action::Reboot => self.do_reboot(dry_run),
             Action::RestartUnit(unit) => self.do_restart_unit(unit, dry_run),
             Action::Command(cmd) => self.do_command(cmd, dry_run),
+            Action::OpenVpnSignal { management, signal } => {
+                self.do_openvpn_signal(management, *signal, dry_run)
+            }
+            Action::NmActivate(name) => self.do_nm_activate(name, dry_run),
         }
     }
 }
@@ -0,0 +1,139 @@
+use std::fmt;
+use std::net::IpAddr;
+
+use crate::config::ChangeMode;
+use crate::ip_api::IpInfo;
+
+/// A detected change of the public exit, for logging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitChange {
+    pub old_ip: Option<IpAddr>,
+    pub new_ip: Option<IpAddr>,
+    pub old_asn: Option<u32>,
+    pub new_asn: Option<u32>,
+}
+
+impl fmt::Display for ExitChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ip = |ip: Option<IpAddr>| ip.map_or("unknown".to_string(), |ip| ip.to_string());
+        let asn = |asn: Option<u32>| asn.map_or("unknown".to_string(), |a| format!("AS{}", a));
+        write!(
+            f,
+            "IP {} -> {}, {} -> {}",
+            ip(self.old_ip),
+            ip(self.new_ip),
+            asn(self.old_asn),
+            asn(self.new_asn)
+        )
+    }
+}
+
+/// Remembers the last seen public IP and ASN between checks.
+///
+/// Long-running callers keep one tracker for the life of the process; the
+/// first successful lookup only sets the baseline.
+#[derive(Debug, Default, Clone)]
+pub struct ChangeTracker {
+    last_ip: Option<IpAddr>,
+    last_asn: Option<u32>,
+}
+
+impl ChangeTracker {
+    pub fn last_ip(&self) -> Option<IpAddr> {
+        self.last_ip
+    }
+
+    pub fn last_asn(&self) -> Option<u32> {
+        self.last_asn
+    }
+
+    /// Record `info` and report whether the exit changed under `mode`.
+    /// Fields the lookup did not report are neither compared nor forgotten.
+    pub fn observe(&mut self, info: &IpInfo, mode: ChangeMode) -> Option<ExitChange> {
+        let change = ExitChange {
+            old_ip: self.last_ip,
+            new_ip: info.ip.or(self.last_ip),
+            old_asn: self.last_asn,
+            new_asn: info.asn.or(self.last_asn),
+        };
+        self.last_ip = change.new_ip;
+        self.last_asn = change.new_asn;
+
+        let changed = match mode {
+            ChangeMode::Off => false,
+            ChangeMode::Ip => {
+                differs(change.old_ip, change.new_ip) || differs(change.old_asn, change.new_asn)
+            }
+            ChangeMode::Asn => differs(change.old_asn, change.new_asn),
+        };
+        changed.then_some(change)
+    }
+}
+
+/// A field changed only if we knew its old value and it now differs.
+fn differs<T: PartialEq>(old: Option<T>, new: Option<T>) -> bool {
+    old.is_some() && old != new
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(ip: &str, asn: Option<u32>) -> IpInfo {
+        IpInfo {
+            ip: Some(ip.parse().unwrap()),
+            asn,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn first_lookup_sets_baseline() {
+        let mut t = ChangeTracker::default();
+        assert_eq!(
+            t.observe(&info("203.0.113.7", Some(64500)), ChangeMode::Ip),
+            None
+        );
+        assert_eq!(t.last_ip(), Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(t.last_asn(), Some(64500));
+    }
+
+    #[test]
+    fn ip_mode_reports_new_address() {
+        let mut t = ChangeTracker::default();
+        t.observe(&info("203.0.113.7", Some(64500)), ChangeMode::Ip);
+        assert_eq!(
+            t.observe(&info("203.0.113.7", Some(64500)), ChangeMode::Ip),
+            None
+        );
+        let change = t
+            .observe(&info("203.0.113.8", Some(64500)), ChangeMode::Ip)
+            .expect("change");
+        assert_eq!(
+            change.to_string(),
+            "IP 203.0.113.7 -> 203.0.113.8, AS64500 -> AS64500"
+        );
+    }
+
+    #[test]
+    fn asn_mode_ignores_address_hops() {
+        let mut t = ChangeTracker::default();
+        t.observe(&info("203.0.113.7", Some(64500)), ChangeMode::Asn);
+        assert!(t
+            .observe(&info("203.0.113.8", Some(64500)), ChangeMode::Asn)
+            .is_none());
+        assert!(t
+            .observe(&info("198.51.100.1", Some(64501)), ChangeMode::Asn)
+            .is_some());
+    }
+
+    #[test]
+    fn missing_fields_are_not_a_change() {
+        let mut t = ChangeTracker::default();
+        t.observe(&info("203.0.113.7", Some(64500)), ChangeMode::Ip);
+        assert!(t
+            .observe(&info("203.0.113.7", None), ChangeMode::Ip)
+            .is_none());
+        assert_eq!(t.last_asn(), Some(64500));
+    }
+}
@@ -1,9 +1,71 @@
 use anyhow::Result;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::{Duration, SystemTime};
 
+use super::change::ChangeTracker;
 use crate::actions;
-use crate::config::EffectiveConfig;
-use crate::networking;
+use crate::config::{self, ChangeMode, EffectiveConfig};
+use crate::ip_api::{IpInfo, IpLookupError};
+use crate::matching::MatchRules;
+use crate::networking::{self, InterfaceState, NetworkingError};
+
+/// What a single `perform_check` run concluded.
+#[derive(Debug)]
+pub enum CheckOutcome {
+    /// The lookup succeeded and no VPN-lost condition matched.
+    VpnActive,
+    /// A VPN-lost condition matched and the action was run.
+    VpnLost,
+    /// The configured VPN systemd unit is failed, inactive or missing (holds
+    /// the reason); the unit action was run and nothing was looked up.
+    ServiceDown(String),
+    /// The configured tunnel interface is missing or down (holds the
+    /// reason); the tunnel action was run and nothing was looked up.
+    TunnelDown(String),
+    /// Traffic to a connectivity endpoint is routed around the tunnel
+    /// (holds the route actually used); the tunnel action was run.
+    RouteBypass(String),
+    /// No connectivity endpoint was reachable; nothing was looked up.
+    Offline,
+    /// The connectivity check itself failed (e.g. DNS resolution).
+    ConnectivityError(NetworkingError),
+    /// The lookup failed or was skipped, so the VPN state is unknown. Holds
+    /// the typed error when the failure came from the lookup layer.
+    LookupFailed(Option<IpLookupError>),
+}
+
+impl CheckOutcome {
+    /// Process exit code for this outcome when errors are fatal
+    /// (`run_once` / `exit_on_error`), or None when there is nothing to report.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            CheckOutcome::VpnActive
+            | CheckOutcome::VpnLost
+            | CheckOutcome::ServiceDown(_)
+            | CheckOutcome::TunnelDown(_)
+            | CheckOutcome::RouteBypass(_) => None,
+            CheckOutcome::Offline => Some(config::EXIT_CONNECTIVITY_FAILURE),
+            CheckOutcome::ConnectivityError(NetworkingError::DnsResolve(_)) => {
+                Some(config::EXIT_CONNECTIVITY_DNS)
+            }
+            CheckOutcome::ConnectivityError(_) => Some(config::EXIT_CONNECTIVITY_FAILURE),
+            CheckOutcome::LookupFailed(Some(IpLookupError::RateLimited { .. })) => {
+                Some(config::EXIT_LOOKUP_RATE_LIMITED)
+            }
+            CheckOutcome::LookupFailed(_) => Some(config::EXIT_ISP_FAILURE),
+        }
+    }
+
+    /// True for failures expected to clear up on their own (rate limits),
+    /// which should not stop a long-running service even with `exit_on_error`.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            CheckOutcome::LookupFailed(Some(IpLookupError::RateLimited { .. }))
+        )
+    }
+}
 
 /// Perform a single connectivity+ISP check using injected dependencies.
 ///
@@ -12,27 +74,196 @@ use crate::networking;
 /// `get_isp_fn` and `run_action_fn`.
 ///
 /// Behavior summary:
+/// - If `eff.vpn_unit` is set and systemd reports that unit failed, inactive
+///   or not found, the unit action (default: the VPN-lost action) runs and
+///   `CheckOutcome::ServiceDown` is returned before any other stage. D-Bus
+///   errors are logged and the check goes on.
+/// - If `eff.tunnel_interface` is set and that interface is missing or down,
+///   the tunnel action (default: the VPN-lost action) runs and
+///   `CheckOutcome::TunnelDown` is returned before anything touches the
+///   network. With `eff.route_check`, a route to a connectivity endpoint
+///   that does not use the interface returns `CheckOutcome::RouteBypass`
+///   the same way. An interface that cannot be read is logged and the
+///   check goes on.
+/// - If `eff.wireguard_interface` is set and a peer's latest handshake is
+///   older than `eff.wireguard_max_handshake_age_secs` (or the interface has
+///   no peers), the VPN-lost action runs and `CheckOutcome::VpnLost` is
+///   returned. Peers that cannot be read are logged and the check goes on.
+/// - If `eff.openvpn_management` is set and OpenVPN reports RECONNECTING or
+///   EXITING, or has been connecting for longer than
+///   `openvpn::CONNECTING_GRACE`, the VPN-lost action runs and
+///   `CheckOutcome::VpnLost` is returned. A connection that is still coming
+///   up or an unreachable management interface is logged and the check
+///   goes on.
+/// - If `eff.nm_connection` is set and that NetworkManager profile is not an
+///   activated or activating connection, the VPN-lost action runs and
+///   `CheckOutcome::VpnLost` is returned. D-Bus errors are logged and the
+///   check goes on.
 /// - If connectivity checks indicate the internet is down, either returns Ok(())
 ///   or exits the process with an appropriate code when `eff.run_once` or
 ///   `eff.exit_on_error` is set. Exiting is left to the application layer to
 ///   preserve the test seam and scripting semantics.
-/// - If connectivity is up, we attempt to determine the ISP via `get_isp_fn`.
-///   On success, compare with `eff.isp_to_check` and run the configured
-///   action when they match (VPN likely lost). The action is executed by
-///   calling the provided `run_action_fn` with the parsed `Action` and
-///   `eff.dry_run` flag.
-/// - Networking and ISP resolution errors are handled according to
-///   `eff.run_once`/`exit_on_error` flags: either surfaced (Ok/Err) or cause
-///   a process exit. Tests use injections to avoid exiting the process.
-pub fn perform_check<FGet, FRun>(
+/// - If connectivity is up, we attempt to look up the public IP record via
+///   `get_info_fn`. It may return an `IpInfo` or anything convertible into
+///   one (a bare ISP `String` works too). On success, evaluate the
+///   `MatchRules` built from `eff` (ISP, org, ASN, country, CIDR) and run the
+///   configured action when any of them match (VPN likely lost). The action
+///   is executed by calling the provided `run_action_fn` with the parsed
+///   `Action` and `eff.dry_run` flag.
+/// - Networking and lookup failures are logged and returned as a
+///   `CheckOutcome` (lookup errors keep their `IpLookupError` kind). The
+///   caller decides whether to exit based on `eff.run_once`/`exit_on_error`;
+///   this function never exits so tests can inject failures.
+///
+/// A single call has no previous exit to compare against, so public IP
+/// change detection needs `perform_check_with_tracker`.
+pub fn perform_check<FGet, FRun, R>(
+    eff: &EffectiveConfig,
+    get_info_fn: FGet,
+    run_action_fn: FRun,
+) -> Result<CheckOutcome>
+where
+    FGet: Fn() -> Result<R>,
+    R: Into<IpInfo>,
+    FRun: Fn(&actions::Action, bool),
+{
+    perform_check_with_tracker(
+        eff,
+        &mut ChangeTracker::default(),
+        get_info_fn,
+        run_action_fn,
+    )
+}
+
+/// `perform_check` that also compares each successful lookup with the one
+/// remembered in `tracker`. When `eff.change_detection` is enabled and the
+/// public IP or ASN changed, the change is logged and the configured change
+/// action (if any) is run before the VPN-lost conditions are evaluated.
+pub fn perform_check_with_tracker<FGet, FRun, R>(
     eff: &EffectiveConfig,
-    get_isp_fn: FGet,
+    tracker: &mut ChangeTracker,
+    get_info_fn: FGet,
     run_action_fn: FRun,
-) -> Result<()>
+) -> Result<CheckOutcome>
 where
-    FGet: Fn() -> Result<String>,
+    FGet: Fn() -> Result<R>,
+    R: Into<IpInfo>,
     FRun: Fn(&actions::Action, bool),
 {
+    let change_mode = ChangeMode::parse(&eff.change_detection).unwrap_or(ChangeMode::Off);
+    let rules = MatchRules::from_effective(eff)
+        .map_err(|e| anyhow::anyhow!("invalid VPN-lost conditions: {}", e))?;
+
+    // A stopped VPN service explains everything else; say so plainly.
+    if let Some(unit) = &eff.vpn_unit {
+        let state =
+            networking::system_bus().and_then(|conn| networking::systemd::unit_state(&conn, unit));
+        match state {
+            Ok(state) if state.is_down() => {
+                let reason = state.to_string();
+                warn!("VPN service down: {}", reason);
+                let action = match &eff.vpn_unit_action_type {
+                    Some(action_type) => {
+                        actions::parse_action(action_type, &eff.vpn_unit_action_arg)
+                    }
+                    None => actions::parse_action(&eff.action_type, &eff.action_arg),
+                };
+                run_action_fn(&action, eff.dry_run);
+                return Ok(CheckOutcome::ServiceDown(reason));
+            }
+            Ok(state) => debug!("VPN service {}", state),
+            Err(e) => error!("Cannot query systemd for {}: {:#}", unit, e),
+        }
+    }
+
+    // Cheap local stage: a missing or downed tunnel means the VPN is gone,
+    // whatever the internet says.
+    if let Some(name) = &eff.tunnel_interface {
+        let tunnel_action = || match &eff.tunnel_action_type {
+            Some(action_type) => actions::parse_action(action_type, &eff.tunnel_action_arg),
+            None => actions::parse_action(&eff.action_type, &eff.action_arg),
+        };
+        if let Some(reason) = tunnel_down_reason(name, networking::read_interface(name)) {
+            warn!("Tunnel down: {}", reason);
+            run_action_fn(&tunnel_action(), eff.dry_run);
+            return Ok(CheckOutcome::TunnelDown(reason));
+        }
+        if eff.route_check {
+            if let Some(reason) = route_bypass_reason(name, &eff.connectivity_endpoints) {
+                warn!("Tunnel bypassed: {}", reason);
+                run_action_fn(&tunnel_action(), eff.dry_run);
+                return Ok(CheckOutcome::RouteBypass(reason));
+            }
+        }
+    }
+
+    // A WireGuard peer without a recent handshake means the tunnel is dead.
+    if let Some(name) = &eff.wireguard_interface {
+        match networking::wireguard::read_peers(name) {
+            Ok(peers) => {
+                let max_age = Duration::from_secs(eff.wireguard_max_handshake_age_secs);
+                if let Some(reason) =
+                    networking::wireguard::stale_reason(name, &peers, SystemTime::now(), max_age)
+                {
+                    warn!("VPN Lost: {}", reason);
+                    let action = actions::parse_action(&eff.action_type, &eff.action_arg);
+                    run_action_fn(&action, eff.dry_run);
+                    return Ok(CheckOutcome::VpnLost);
+                }
+                debug!(
+                    "WireGuard interface {}: {} peer(s) fresh",
+                    name,
+                    peers.len()
+                );
+            }
+            Err(e) => error!("Cannot read WireGuard peers of {}: {:#}", name, e),
+        }
+    }
+
+    // OpenVPN reports its own state over the management interface.
+    if let Some(addr) = &eff.openvpn_management {
+        let timeout = Duration::from_secs(eff.connectivity_timeout_secs);
+        let status = addr
+            .parse::<networking::openvpn::ManagementAddr>()
+            .map_err(anyhow::Error::msg)
+            .and_then(|addr| networking::openvpn::query(&addr, timeout));
+        match status {
+            Ok((state, _)) if state.is_lost(SystemTime::now()) => {
+                warn!("VPN Lost: OpenVPN state {}", state);
+                let action = actions::parse_action(&eff.action_type, &eff.action_arg);
+                run_action_fn(&action, eff.dry_run);
+                return Ok(CheckOutcome::VpnLost);
+            }
+            Ok((state, _)) if !state.is_connected() => {
+                info!("OpenVPN is connecting: state {}", state)
+            }
+            Ok((state, Some(bytes))) => debug!("OpenVPN state {} ({})", state, bytes),
+            Ok((state, None)) => debug!("OpenVPN state {}", state),
+            Err(e) => error!(
+                "Cannot query OpenVPN management interface {}: {:#}",
+                addr, e
+            ),
+        }
+    }
+
+    // A VPN managed by NetworkManager must be among its active connections.
+    if let Some(name) = &eff.nm_connection {
+        let active = networking::system_bus()
+            .and_then(|conn| networking::network_manager::active_connections(&conn));
+        match active {
+            Ok(active) => {
+                if let Some(reason) = networking::network_manager::vpn_down_reason(name, &active) {
+                    warn!("VPN Lost: {}", reason);
+                    let action = actions::parse_action(&eff.action_type, &eff.action_arg);
+                    run_action_fn(&action, eff.dry_run);
+                    return Ok(CheckOutcome::VpnLost);
+                }
+                debug!("NetworkManager connection '{}' is up", name);
+            }
+            Err(e) => error!("Cannot query NetworkManager: {:#}", e),
+        }
+    }
+
     // Convert endpoints into a slice of &str for the networking API.
     let endpoints_ref: Vec<&str> = eff
         .connectivity_endpoints
@@ -40,43 +271,153 @@ where
         .map(|s| s.as_str())
         .collect();
 
-    match networking::is_online_with_retries(
+    let outcome = match networking::is_online_with_bind(
         &endpoints_ref,
         eff.connectivity_timeout_secs,
         &eff.connectivity_ports,
         eff.connectivity_retries,
+        &networking::SocketBind::from_effective(eff),
     ) {
         Ok(true) => {
             // Connectivity appears fine, determine ISP.
-            match get_isp_fn() {
-                Ok(isp) => {
-                    if isp == eff.isp_to_check {
-                        // ISP matches the one we're watching for -> VPN likely lost.
-                        warn!("VPN Lost (ISP: {})", isp);
+            match get_info_fn().map(Into::into) {
+                Ok(IpInfo {
+                    isp: None,
+                    ip: None,
+                    asn: None,
+                    ..
+                }) => {
+                    error!("Failed to determine ISP: lookup returned no usable data");
+                    CheckOutcome::LookupFailed(None)
+                }
+                Ok(info) => {
+                    if let Some(change) = tracker.observe(&info, change_mode) {
+                        warn!("Public exit changed: {}", change);
+                        if let Some(action_type) = &eff.change_action_type {
+                            let action = actions::parse_action(action_type, &eff.change_action_arg);
+                            run_action_fn(&action, eff.dry_run);
+                        }
+                    }
+                    // With the dual-stack check on, this lookup was forced
+                    // over IPv4; say so, as the IPv6 result is logged apart.
+                    let family = if eff.ipv6_leak_check {
+                        " over IPv4"
+                    } else {
+                        ""
+                    };
+                    if let Some(reason) = rules.vpn_lost_reason(&info) {
+                        // Exit looks like the connection we're watching for -> VPN likely lost.
+                        warn!("VPN Lost{}: {} ({})", family, reason, info);
                         let action = actions::parse_action(&eff.action_type, &eff.action_arg);
                         run_action_fn(&action, eff.dry_run);
+                        CheckOutcome::VpnLost
                     } else {
-                        info!("VPN active (ISP: {})", isp);
+                        info!("VPN active{} ({})", family, info);
+                        CheckOutcome::VpnActive
                     }
                 }
                 Err(e) => {
-                    error!("Failed to determine ISP: {}", e);
-                    // For single-run invocations or when caller requested exit-on-error
-                    // the application may want to translate this into a process-exit.
-                    // We do not exit here to keep this function testable; callers
-                    // (e.g., `run`) can decide to exit based on eff flags.
+                    let kind = e.downcast_ref::<IpLookupError>().cloned();
+                    match &kind {
+                        Some(IpLookupError::RateLimited { .. }) => {
+                            warn!("VPN state unknown: lookup skipped, {}", e);
+                        }
+                        _ => error!("Failed to determine ISP: {}", e),
+                    }
+                    CheckOutcome::LookupFailed(kind)
                 }
             }
         }
         Ok(false) => {
             error!("Internet appears to be down (connectivity checks failed)");
-            // Caller decides whether to exit when run_once/exit_on_error is set.
+            CheckOutcome::Offline
         }
         Err(e) => {
             error!("Connectivity check failed: {}", e);
-            // Caller decides whether to exit when run_once/exit_on_error is set.
+            CheckOutcome::ConnectivityError(e)
         }
+    };
+
+    Ok(outcome)
+}
+
+/// Why the tunnel interface `name` is unusable given its `state`, or None
+/// when it is up or could not be read (logged).
+fn tunnel_down_reason(name: &str, state: Result<Option<InterfaceState>>) -> Option<String> {
+    match state {
+        Ok(Some(state)) if state.is_up() => {
+            debug!(
+                "Tunnel interface {} is up (operstate {}, addresses {:?})",
+                name, state.operstate, state.addresses
+            );
+            None
+        }
+        Ok(Some(state)) => Some(format!(
+            "interface {} is down (operstate {}, flags {:#x})",
+            name, state.operstate, state.flags
+        )),
+        Ok(None) => Some(format!("interface {} does not exist", name)),
+        Err(e) => {
+            error!("Cannot read tunnel interface {}: {:#}", name, e);
+            None
+        }
+    }
+}
+
+/// Describe the first connectivity endpoint whose route does not use the
+/// tunnel interface `name`. Endpoints that do not resolve or have no route
+/// are left to the connectivity stage.
+fn route_bypass_reason(name: &str, endpoints: &[String]) -> Option<String> {
+    for endpoint in endpoints {
+        let Some(ip) = endpoint_ip(endpoint) else {
+            debug!("Route check: cannot resolve {}, skipping", endpoint);
+            continue;
+        };
+        match networking::route_to(ip) {
+            Ok(route) if route.interface == name => {
+                debug!("Route to {} ({}) uses {}", endpoint, ip, route);
+            }
+            Ok(route) => {
+                return Some(format!(
+                    "route to {} ({}) uses {} instead of {}",
+                    endpoint, ip, route, name
+                ))
+            }
+            Err(e) => debug!("Route check: {:#}", e),
+        }
+    }
+    None
+}
+
+/// First address of a connectivity endpoint (`ip`, `host` or `host:port`).
+fn endpoint_ip(endpoint: &str) -> Option<IpAddr> {
+    if let Ok(ip) = endpoint.parse::<IpAddr>() {
+        return Some(ip);
     }
+    if let Ok(addr) = endpoint.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    let host_port = if endpoint.contains(':') {
+        endpoint.to_string()
+    } else {
+        format!("{}:0", endpoint)
+    };
+    host_port.to_socket_addrs().ok()?.next().map(|a| a.ip())
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreadable_tunnel_is_not_down() {
+        assert_eq!(
+            tunnel_down_reason("wg0", Err(anyhow::anyhow!("permission denied"))),
+            None
+        );
+        assert_eq!(
+            tunnel_down_reason("wg0", Ok(None)).as_deref(),
+            Some("interface wg0 does not exist")
+        );
+    }
 }
@@ -0,0 +1,229 @@
+use anyhow::Result;
+use log::{info, warn};
+use std::net::IpAddr;
+
+use crate::actions;
+use crate::config::EffectiveConfig;
+use crate::ip_api::IpInfo;
+use crate::matching::{Cidr, MatchRules};
+
+/// What the IPv6 side of a dual-stack check found.
+#[derive(Debug)]
+pub enum Ipv6Status {
+    /// The IPv6-only lookup failed, so there is no IPv6 egress to leak
+    /// through (or the provider cannot be reached over IPv6). Holds the error.
+    Unavailable(String),
+    /// IPv6 egress exists and no VPN-lost condition matched it.
+    Tunnelled(IpInfo),
+    /// IPv6 egress goes via the connection we are watching for; the IPv6
+    /// leak action was run.
+    Leak(IpInfo),
+}
+
+/// Look up the public address over IPv6 with `get_v6_info_fn` and judge it
+/// with the same `MatchRules` as the main (IPv4) check.
+///
+/// A match means IPv6 traffic bypasses the tunnel even if IPv4 does not, so
+/// it is reported as its own condition and runs `ipv6_leak_action_type`
+/// (if configured) instead of the VPN-lost action.
+pub fn check_ipv6_leak<FGet, FRun, R>(
+    eff: &EffectiveConfig,
+    get_v6_info_fn: FGet,
+    run_action_fn: FRun,
+) -> Result<Ipv6Status>
+where
+    FGet: Fn() -> Result<R>,
+    R: Into<IpInfo>,
+    FRun: Fn(&actions::Action, bool),
+{
+    let rules = MatchRules::from_effective(eff)
+        .map_err(|e| anyhow::anyhow!("invalid VPN-lost conditions: {}", e))?;
+
+    let info = match get_v6_info_fn().map(Into::into) {
+        Ok(info) => info,
+        Err(e) => {
+            info!("No IPv6 egress: {}", e);
+            return Ok(Ipv6Status::Unavailable(e.to_string()));
+        }
+    };
+
+    match rules.vpn_lost_reason(&info) {
+        Some(reason) => {
+            warn!("IPv6 leak: {} ({})", reason, info);
+            if let Some(action_type) = &eff.ipv6_leak_action_type {
+                let action = actions::parse_action(action_type, &eff.ipv6_leak_action_arg);
+                run_action_fn(&action, eff.dry_run);
+            }
+            Ok(Ipv6Status::Leak(info))
+        }
+        None => {
+            info!("IPv6 egress tunnelled ({})", info);
+            Ok(Ipv6Status::Tunnelled(info))
+        }
+    }
+}
+
+/// What the DNS leak check found.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DnsStatus {
+    /// Every active resolver is in `allowed_dns`.
+    Clean(Vec<IpAddr>),
+    /// These resolvers are not in `allowed_dns`; the VPN-lost action was run.
+    Leak(Vec<IpAddr>),
+}
+
+/// Compare the active resolvers returned by `get_resolvers_fn` with
+/// `allowed_dns` (addresses or CIDR networks).
+///
+/// Queries to an unlisted resolver (typically the ISP's) bypass the tunnel,
+/// so a mismatch is reported as a DNS leak and handled like a lost VPN:
+/// the configured `action_type`/`action_arg` is run via `run_action_fn`.
+pub fn check_dns_leak<FGet, FRun>(
+    eff: &EffectiveConfig,
+    get_resolvers_fn: FGet,
+    run_action_fn: FRun,
+) -> Result<DnsStatus>
+where
+    FGet: Fn() -> Result<Vec<IpAddr>>,
+    FRun: Fn(&actions::Action, bool),
+{
+    let allowed = eff
+        .allowed_dns
+        .iter()
+        .map(|s| s.parse::<Cidr>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("invalid allowed_dns entry: {}", e))?;
+
+    let resolvers = get_resolvers_fn()?;
+    let leaked: Vec<IpAddr> = resolvers
+        .iter()
+        .filter(|ip| !allowed.iter().any(|net| net.contains(ip)))
+        .copied()
+        .collect();
+    if leaked.is_empty() {
+        info!("DNS resolvers allowed ({})", join(&resolvers));
+        return Ok(DnsStatus::Clean(resolvers));
+    }
+
+    warn!("DNS leak: resolvers not in allowed_dns ({})", join(&leaked));
+    let action = actions::parse_action(&eff.action_type, &eff.action_arg);
+    run_action_fn(&action, eff.dry_run);
+    Ok(DnsStatus::Leak(leaked))
+}
+
+fn join(ips: &[IpAddr]) -> String {
+    if ips.is_empty() {
+        return "none configured".to_string();
+    }
+    ips.iter()
+        .map(IpAddr::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn eff() -> EffectiveConfig {
+        EffectiveConfig {
+            isp_to_check: "Home ISP".to_string(),
+            ipv6_leak_check: true,
+            ipv6_leak_action_type: Some("command".to_string()),
+            ipv6_leak_action_arg: "/usr/local/bin/block-v6".to_string(),
+            dry_run: true,
+            ..Default::default()
+        }
+    }
+
+    fn v6_info(isp: &str) -> IpInfo {
+        IpInfo {
+            ip: Some("2001:db8::7".parse().unwrap()),
+            isp: Some(isp.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn home_isp_over_ipv6_is_a_leak() {
+        let ran = Cell::new(None);
+        let status = check_ipv6_leak(
+            &eff(),
+            || Ok(v6_info("Home ISP")),
+            |a, _| ran.set(Some(a.clone())),
+        )
+        .unwrap();
+        assert!(matches!(status, Ipv6Status::Leak(_)));
+        assert_eq!(
+            ran.take(),
+            Some(actions::Action::Command(
+                "/usr/local/bin/block-v6".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn vpn_exit_over_ipv6_is_tunnelled() {
+        let status = check_ipv6_leak(
+            &eff(),
+            || Ok(v6_info("VPN Provider")),
+            |_, _| panic!("no action expected"),
+        )
+        .unwrap();
+        assert!(matches!(status, Ipv6Status::Tunnelled(_)));
+    }
+
+    #[test]
+    fn failed_ipv6_lookup_is_not_a_leak() {
+        let status = check_ipv6_leak(
+            &eff(),
+            || -> Result<IpInfo> { anyhow::bail!("no IPv6 address") },
+            |_, _| panic!("no action expected"),
+        )
+        .unwrap();
+        assert!(matches!(status, Ipv6Status::Unavailable(_)));
+    }
+
+    fn dns_eff() -> EffectiveConfig {
+        EffectiveConfig {
+            dns_leak_check: true,
+            allowed_dns: vec!["10.8.0.0/24".to_string(), "fd00::1".to_string()],
+            action_type: "restart-unit".to_string(),
+            action_arg: "openvpn@client".to_string(),
+            dry_run: true,
+            ..Default::default()
+        }
+    }
+
+    fn ips(list: &[&str]) -> Vec<IpAddr> {
+        list.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn allowed_resolvers_are_clean() {
+        let status = check_dns_leak(
+            &dns_eff(),
+            || Ok(ips(&["10.8.0.1", "fd00::1"])),
+            |_, _| panic!("no action expected"),
+        )
+        .unwrap();
+        assert_eq!(status, DnsStatus::Clean(ips(&["10.8.0.1", "fd00::1"])));
+    }
+
+    #[test]
+    fn isp_resolver_is_a_dns_leak() {
+        let ran = Cell::new(None);
+        let status = check_dns_leak(
+            &dns_eff(),
+            || Ok(ips(&["10.8.0.1", "192.168.1.1"])),
+            |a, _| ran.set(Some(a.clone())),
+        )
+        .unwrap();
+        assert_eq!(status, DnsStatus::Leak(ips(&["192.168.1.1"])));
+        assert_eq!(
+            ran.take(),
+            Some(actions::Action::RestartUnit("openvpn@client".to_string()))
+        );
+    }
+}
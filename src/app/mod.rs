@@ -1,5 +1,5 @@
 use anyhow::Result;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread::sleep;
@@ -7,32 +7,28 @@ use std::time::Duration;
 
 use crate::actions;
 use crate::cli::Args;
-use crate::config::Config;
-use crate::ip_api;
+use crate::config::{Config, EffectiveConfig};
+use crate::ip_api::{self, IpFamily, IpInfoProvider};
+use crate::networking::netns::{self, NetnsTarget};
 
+mod change;
 mod check;
+mod leak;
 
-pub use check::perform_check;
+pub use crate::config::{ChangeMode, CHANGE_MODES};
+pub use change::{ChangeTracker, ExitChange};
+pub use check::{perform_check, perform_check_with_tracker, CheckOutcome};
+pub use leak::{check_dns_leak, check_ipv6_leak, DnsStatus, Ipv6Status};
 
 /// Run the main application logic. Returns Ok(()) on clean shutdown, or Err on fatal error.
 pub fn run(args: Args, cfg: Config) -> Result<()> {
     info!("Starting check_vpn run()");
 
     // Start with the provided config merged with CLI args.
-    let mut current_cfg = cfg;
-    let mut eff = current_cfg.merge_with_args(&args);
+    let eff = cfg.merge_with_args(&args);
 
     // Validate merged configuration before we start the main loop.
-    if let Err(e) = crate::config::Config::validate_values(
-        eff.interval,
-        &eff.isp_to_check,
-        &eff.action_type,
-        &eff.action_arg,
-        &eff.connectivity_endpoints,
-        &eff.connectivity_ports,
-        eff.connectivity_timeout_secs,
-        eff.connectivity_retries,
-    ) {
+    if let Err(e) = crate::config::Config::validate_effective(&eff) {
         error!("Configuration validation failed: {}", e);
         for part in (e.0).iter() {
             error!("  - {}", part.trim());
@@ -40,12 +36,35 @@ pub fn run(args: Args, cfg: Config) -> Result<()> {
         std::process::exit(crate::config::EXIT_INVALID_CONFIG);
     }
 
+    // Probes, lookups and actions all run on one thread inside the
+    // namespace; threads it spawns (HTTP runtime, commands) inherit it.
+    match NetnsTarget::from_effective(&eff) {
+        Some(target) => {
+            info!("Running checks in network namespace {}", target);
+            netns::run_in(&target, move || run_checks(args, eff))
+        }
+        None => run_checks(args, eff),
+    }
+}
+
+/// The check loop (or single check with `run_once`) for a validated config.
+fn run_checks(args: Args, mut eff: EffectiveConfig) -> Result<()> {
     let action = actions::parse_action(&eff.action_type, &eff.action_arg);
     debug!("Configured action: {:?}", action);
 
+    let mut provider = ip_api::provider_from_config(&eff)?;
+    debug!("Using ip provider: {}", provider.name());
+    let mut provider_v6 = ipv6_provider(&eff)?;
+
     // If run_once requested, perform single check and exit. Use perform_check directly (testable).
     if eff.run_once {
-        perform_check(&eff, ip_api::get_isp, actions::run_action)?;
+        let outcome = perform_check(&eff, || provider.get_ip_info(), actions::run_action)?;
+        run_leak_checks(&eff, provider_v6.as_deref(), &outcome)?;
+        if eff.exit_on_error {
+            if let Some(code) = outcome.exit_code() {
+                std::process::exit(code);
+            }
+        }
         return Ok(());
     }
 
@@ -60,6 +79,9 @@ pub fn run(args: Args, cfg: Config) -> Result<()> {
 
     debug!("Starting main check loop (interval = {} sec)", eff.interval);
 
+    // Last seen public IP/ASN; kept across config reloads.
+    let mut tracker = ChangeTracker::default();
+
     while keep_running.load(Ordering::SeqCst) {
         // Check for config file updates unless run_once mode. This allows
         // configuration hot-reloading without restarting the service. Load a new
@@ -70,23 +92,32 @@ pub fn run(args: Args, cfg: Config) -> Result<()> {
                 Ok(new_config) => {
                     let new_eff = new_config.merge_with_args(&args);
                     // We should validate the new config before using it.
-                    if let Err(e) = crate::config::Config::validate_values(
-                        new_eff.interval,
-                        &new_eff.isp_to_check,
-                        &new_eff.action_type,
-                        &new_eff.action_arg,
-                        &new_eff.connectivity_endpoints,
-                        &new_eff.connectivity_ports,
-                        new_eff.connectivity_timeout_secs,
-                        new_eff.connectivity_retries,
-                    ) {
+                    if let Err(e) = crate::config::Config::validate_effective(&new_eff) {
                         error!("Reloaded config failed validation, keeping previous: {}", e);
                     } else {
-                        // Update both the base config and effective merged config
-                        current_cfg = new_config;
+                        if (&new_eff.netns, new_eff.netns_pid) != (&eff.netns, eff.netns_pid) {
+                            warn!("Changing netns or netns_pid takes effect after a restart");
+                        }
+                        // Only rebuild the provider when the config actually
+                        // changed so health scores and rate-limit budgets survive.
+                        if new_eff != eff {
+                            match ip_api::provider_from_config(&new_eff) {
+                                Ok(p) => provider = p,
+                                Err(e) => {
+                                    error!("Failed to rebuild ip provider, keeping previous: {}", e)
+                                }
+                            }
+                            match ipv6_provider(&new_eff) {
+                                Ok(p) => provider_v6 = p,
+                                Err(e) => {
+                                    error!(
+                                        "Failed to rebuild IPv6 provider, keeping previous: {}",
+                                        e
+                                    )
+                                }
+                            }
+                        }
                         eff = new_eff;
-                        // Suppress unused variable warning - current_cfg is kept for potential future use
-                        let _ = &current_cfg;
                         debug!("Reloaded configuration successfully");
                         if eff.run_once {
                             debug!("Config now has run_once=true, will exit after this iteration");
@@ -103,7 +134,19 @@ pub fn run(args: Args, cfg: Config) -> Result<()> {
         }
 
         // Execute the single check using the current effective configuration.
-        perform_check(&eff, ip_api::get_isp, actions::run_action)?;
+        let outcome = perform_check_with_tracker(
+            &eff,
+            &mut tracker,
+            || provider.get_ip_info(),
+            actions::run_action,
+        )?;
+        run_leak_checks(&eff, provider_v6.as_deref(), &outcome)?;
+        if eff.exit_on_error && !outcome.is_transient() {
+            if let Some(code) = outcome.exit_code() {
+                error!("Exiting on error as requested (exit code {})", code);
+                std::process::exit(code);
+            }
+        }
 
         // Sleep but wake earlier if we are asked to stop; use the possibly-updated interval.
         let mut slept = 0u64;
@@ -119,3 +162,42 @@ pub fn run(args: Args, cfg: Config) -> Result<()> {
     // no-op: metrics server removed
     Ok(())
 }
+
+/// The IPv6-only provider for the dual-stack check, when it is enabled.
+fn ipv6_provider(eff: &EffectiveConfig) -> Result<Option<Box<dyn IpInfoProvider>>> {
+    if !eff.ipv6_leak_check {
+        return Ok(None);
+    }
+    ip_api::provider_for_family(eff, Some(IpFamily::V6)).map(Some)
+}
+
+/// Run the enabled leak checks after the main check. Nothing runs when the
+/// VPN service or tunnel is down or the tunnel is bypassed, the IPv6 lookup
+/// is skipped while offline, and the DNS check once the VPN already counts
+/// as lost (its action has just run).
+fn run_leak_checks(
+    eff: &EffectiveConfig,
+    provider_v6: Option<&dyn IpInfoProvider>,
+    outcome: &CheckOutcome,
+) -> Result<()> {
+    if matches!(
+        outcome,
+        CheckOutcome::ServiceDown(_) | CheckOutcome::TunnelDown(_) | CheckOutcome::RouteBypass(_)
+    ) {
+        return Ok(());
+    }
+    let online = !matches!(
+        outcome,
+        CheckOutcome::Offline | CheckOutcome::ConnectivityError(_)
+    );
+    if let (Some(provider_v6), true) = (provider_v6, online) {
+        check_ipv6_leak(eff, || provider_v6.get_ip_info(), actions::run_action)?;
+    }
+    if eff.dns_leak_check && !matches!(outcome, CheckOutcome::VpnLost) {
+        let resolvers = || crate::networking::active_resolvers(&eff.resolv_conf_path);
+        if let Err(e) = check_dns_leak(eff, resolvers, actions::run_action) {
+            error!("DNS leak check failed: {:#}", e);
+        }
+    }
+    Ok(())
+}
@@ -12,7 +12,7 @@ use std::path::PathBuf;
 /// This struct is intentionally lean: it only represents the raw CLI values as
 /// parsed by `clap`. Higher-level merging with XML config and validation is
 /// performed in `crate::config` so the responsibilities remain separated.
-#[derive(Parser, Debug, Clone)]
+#[derive(Parser, Debug, Clone, Default)]
 #[command(author, version, about = "VPN checker (Rust port)")]
 pub struct Args {
     /// Seconds between checks (overrides config)
@@ -26,12 +26,20 @@ pub struct Args {
     #[arg(short = 'i', long)]
     pub isp_to_check: Option<String>,
 
-    /// Action type to run when VPN is lost. One of: reboot, restart-unit, command
+    /// How ISP names are compared: exact, case-insensitive, glob, regex or
+    /// normalized-whitespace (overrides config)
+    #[arg(long = "isp-match-mode")]
+    pub isp_match_mode: Option<String>,
+
+    /// Action type to run when VPN is lost. One of: reboot, restart-unit,
+    /// command, openvpn-sigusr1, openvpn-sighup, nm-activate
     #[arg(short = 't', long)]
     pub vpn_lost_action_type: Option<String>,
 
     /// Argument for the action. For `restart-unit` this is the systemd unit
-    /// name. For `command` it's the command string. (overrides config)
+    /// name. For `command` it's the command string. For the OpenVPN signals
+    /// it's the management interface address, and for `nm-activate` the
+    /// NetworkManager connection profile. (overrides config)
     #[arg(short = 'a', long)]
     pub vpn_lost_action_arg: Option<String>,
 
@@ -59,6 +67,122 @@ pub struct Args {
     #[arg(long = "connectivity-retries")]
     pub connectivity_retries: Option<usize>,
 
+    /// Public-IP lookup provider. One of: ip-api, ipinfo, ifconfig.co, custom,
+    /// dns-opendns, dns-google, stun
+    /// (overrides config)
+    #[arg(long = "ip-provider")]
+    pub ip_provider: Option<String>,
+
+    /// Endpoint URL for the lookup provider; required for `custom` (overrides config)
+    #[arg(long = "ip-provider-url")]
+    pub ip_provider_url: Option<String>,
+
+    /// Lookup strategy: `single`, `failover` (fall back through the configured
+    /// `ip_providers`) or `quorum` (query every `ip_providers` entry and act
+    /// only when enough agree). (overrides config)
+    #[arg(long = "provider-strategy")]
+    pub provider_strategy: Option<String>,
+
+    /// Number of providers that must report VPN lost in `quorum` mode
+    /// (overrides config)
+    #[arg(long = "provider-quorum")]
+    pub provider_quorum: Option<usize>,
+
+    /// Maximum lookups per minute per provider; 0 disables the limit
+    /// (default: 45 for ip-api, unlimited otherwise). (overrides config)
+    #[arg(long = "rate-limit-per-minute")]
+    pub rate_limit_per_minute: Option<u32>,
+
+    /// Timeout in seconds for each public-IP lookup request (overrides config)
+    #[arg(long = "lookup-timeout")]
+    pub lookup_timeout_secs: Option<u64>,
+
+    /// Attempts per public-IP lookup, including the first (overrides config)
+    #[arg(long = "lookup-retries")]
+    pub lookup_retries: Option<usize>,
+
+    /// Largest accepted lookup response in bytes (overrides config)
+    #[arg(long = "max-response-bytes")]
+    pub max_response_bytes: Option<usize>,
+
+    /// User-Agent header sent with lookups (overrides config)
+    #[arg(long = "user-agent")]
+    pub user_agent: Option<String>,
+
+    /// PEM file with extra CA certificates for HTTPS lookups (overrides config)
+    #[arg(long = "tls-ca-bundle")]
+    pub tls_ca_bundle: Option<String>,
+
+    /// How lookups are judged: `denylist` (VPN lost when the exit matches
+    /// `isp_to_check` & co.) or `allowlist` (VPN lost when the exit is not one
+    /// of the configured `allowed_*` exits). (overrides config)
+    #[arg(long = "match-mode")]
+    pub match_mode: Option<String>,
+
+    /// Act when the public exit changes between checks: `off`, `ip` (new IP
+    /// or ASN) or `asn` (new ASN only). (overrides config)
+    #[arg(long = "change-detection")]
+    pub change_detection: Option<String>,
+
+    /// Also check the public address over IPv6 and treat IPv6 egress via the
+    /// home ISP as a leak (overrides config)
+    #[arg(long = "ipv6-leak-check", action = clap::ArgAction::SetTrue)]
+    pub ipv6_leak_check: bool,
+
+    /// Compare the system's DNS resolvers with `allowed_dns` and treat a
+    /// mismatch as a DNS leak (overrides config)
+    #[arg(long = "dns-leak-check", action = clap::ArgAction::SetTrue)]
+    pub dns_leak_check: bool,
+
+    /// Tunnel interface that must exist and be up, e.g. `wg0` (overrides config)
+    #[arg(long = "tunnel-interface")]
+    pub tunnel_interface: Option<String>,
+
+    /// Verify the routes to the connectivity endpoints use the tunnel
+    /// interface (overrides config)
+    #[arg(long = "route-check", action = clap::ArgAction::SetTrue)]
+    pub route_check: bool,
+
+    /// WireGuard interface whose peers must have a recent handshake, e.g.
+    /// `wg0` (overrides config)
+    #[arg(long = "wireguard-interface")]
+    pub wireguard_interface: Option<String>,
+
+    /// OpenVPN management interface to query, `host:port` or a unix socket
+    /// path (overrides config)
+    #[arg(long = "openvpn-management")]
+    pub openvpn_management: Option<String>,
+
+    /// NetworkManager connection profile (id or UUID) that must be active
+    /// (overrides config)
+    #[arg(long = "nm-connection")]
+    pub nm_connection: Option<String>,
+
+    /// systemd unit running the VPN, e.g. `wg-quick@wg0.service`; reported
+    /// as "VPN service down" when failed or inactive (overrides config)
+    #[arg(long = "vpn-unit")]
+    pub vpn_unit: Option<String>,
+
+    /// Send connectivity probes and IP lookups out of this interface
+    /// (SO_BINDTODEVICE), e.g. `wg0` (overrides config)
+    #[arg(long = "bind-interface")]
+    pub bind_interface: Option<String>,
+
+    /// Send connectivity probes and IP lookups from this local address
+    /// (overrides config)
+    #[arg(long = "source-address")]
+    pub source_address: Option<String>,
+
+    /// Run the checks in this network namespace: a name from `ip netns`
+    /// or a path to a namespace file (overrides config)
+    #[arg(long = "netns")]
+    pub netns: Option<String>,
+
+    /// Run the checks in the network namespace of this PID, e.g. a
+    /// container's init process (overrides config)
+    #[arg(long = "netns-pid")]
+    pub netns_pid: Option<u32>,
+
     /// Run only a single iteration and exit (useful for testing)
     #[arg(long = "run-once", action = clap::ArgAction::SetTrue)]
     pub run_once: bool,
@@ -72,7 +196,6 @@ pub struct Args {
     pub config: Option<PathBuf>,
 
     // Metrics flags removed — metrics server is out of scope for this build.
-
     /// Exit with non-zero codes on errors even in long-running mode (useful for health checks)
     #[arg(long = "exit-on-error", action = clap::ArgAction::SetTrue)]
     pub exit_on_error: bool,
@@ -93,8 +216,8 @@ impl Args {
 #[cfg(test)]
 mod tests {
     use super::Args;
-    use std::path::PathBuf;
     use clap::Parser;
+    use std::path::PathBuf;
 
     #[test]
     fn parse_args_connectivity_and_flags() {
@@ -142,4 +265,41 @@ mod tests {
         let p: PathBuf = args.config.unwrap();
         assert_eq!(p, PathBuf::from("/tmp/test_config.xml"));
     }
+
+    #[test]
+    fn parse_args_ip_provider() {
+        let argv = vec![
+            "check_vpn",
+            "--ip-provider",
+            "custom",
+            "--ip-provider-url",
+            "https://example.com/ip",
+        ];
+        let args = Args::parse_from(argv);
+        assert_eq!(args.ip_provider.as_deref(), Some("custom"));
+        assert_eq!(
+            args.ip_provider_url.as_deref(),
+            Some("https://example.com/ip")
+        );
+    }
+
+    #[test]
+    fn parse_args_lookup_settings() {
+        let argv = vec![
+            "check_vpn",
+            "--lookup-timeout",
+            "10",
+            "--lookup-retries",
+            "3",
+            "--max-response-bytes",
+            "4096",
+            "--user-agent",
+            "my-agent/1.0",
+        ];
+        let args = Args::parse_from(argv);
+        assert_eq!(args.lookup_timeout_secs, Some(10));
+        assert_eq!(args.lookup_retries, Some(3));
+        assert_eq!(args.max_response_bytes, Some(4096));
+        assert_eq!(args.user_agent.as_deref(), Some("my-agent/1.0"));
+    }
 }
@@ -0,0 +1,24 @@
+/// Names accepted for the `change_detection` config value.
+pub const CHANGE_MODES: [&str; 3] = ["off", "ip", "asn"];
+
+/// What counts as a change of the public exit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeMode {
+    /// No change detection (default).
+    Off,
+    /// Any new public IP or ASN, e.g. a hop to another VPN server.
+    Ip,
+    /// Only a new ASN, i.e. a different network rather than a new address.
+    Asn,
+}
+
+impl ChangeMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "off" => Some(ChangeMode::Off),
+            "ip" => Some(ChangeMode::Ip),
+            "asn" => Some(ChangeMode::Asn),
+            _ => None,
+        }
+    }
+}
@@ -8,9 +8,11 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
+pub mod change_mode;
 pub mod serde_helpers;
 pub mod validation;
 
+pub use change_mode::{ChangeMode, CHANGE_MODES};
 pub use serde_helpers::serialize_option_bool;
 pub use validation::ValidationErrors;
 
@@ -22,6 +24,9 @@ pub const EXIT_CONNECTIVITY_DNS: i32 = 3;
 pub const EXIT_CONNECTIVITY_FAILURE: i32 = 4;
 /// Failed to determine ISP (IP API) when considered fatal
 pub const EXIT_ISP_FAILURE: i32 = 5;
+/// Lookup skipped or refused because of rate limiting (only reported for
+/// `run_once`; a long-running service keeps going and retries later)
+pub const EXIT_LOOKUP_RATE_LIMITED: i32 = 6;
 
 #[cfg_attr(
     feature = "xml_strict",
@@ -38,6 +43,13 @@ pub struct Config {
     pub interval: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub isp_to_check: Option<String>,
+    /// Additional ISP patterns (repeatable), matched like `isp_to_check`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub isp_pattern: Option<Vec<String>>,
+    /// How ISP names are compared: exact | case-insensitive | glob | regex |
+    /// normalized-whitespace (applies to isp_to_check, isp_pattern and allowed_isp)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub isp_match_mode: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub vpn_lost_action_type: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -62,13 +74,192 @@ pub struct Config {
     pub connectivity_timeout_secs: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub connectivity_retries: Option<usize>,
+    // Public-IP lookup provider: ip-api | ipinfo | ifconfig.co | custom
+    // | dns-opendns | dns-google | stun
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_provider: Option<String>,
+    /// Endpoint override for the provider (required for `custom`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_provider_url: Option<String>,
+    /// Dotted JSON path holding the ISP name for the `custom` provider (default `isp`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_provider_isp_field: Option<String>,
+    /// How lookups are made: `single` (default, uses `ip_provider`),
+    /// `failover` (tries `ip_providers` in order, preferring healthy ones) or
+    /// `quorum` (queries every `ip_providers` entry and requires agreement)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_strategy: Option<String>,
+    /// Providers used by the multi-provider strategies (repeatable). Each entry
+    /// is a provider name, optionally `name=<url>`, e.g. `custom=https://x/json`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_providers: Option<Vec<String>>,
+    /// Number of providers that must report VPN lost before acting in
+    /// `quorum` mode (default: a strict majority)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_quorum: Option<usize>,
+    /// Client-side request budget per provider (token bucket, requests per
+    /// minute). Defaults to 45 for ip-api and unlimited otherwise; 0 disables
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit_per_minute: Option<u32>,
+    /// Per-request timeout for public-IP lookups in seconds (default 5)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lookup_timeout_secs: Option<u64>,
+    /// Attempts per lookup, including the first (default 1; the legacy
+    /// CHECK_VPN_RETRY_COUNT env var is still honoured when unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lookup_retries: Option<usize>,
+    /// Largest accepted lookup response in bytes (default 5 MiB; the legacy
+    /// CHECK_VPN_MAX_RESPONSE_BYTES env var is still honoured when unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_response_bytes: Option<usize>,
+    /// User-Agent header sent with lookups (default `check_vpn/0.1`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// PEM file with extra CA certificates trusted for HTTPS lookups
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_ca_bundle: Option<String>,
+    /// Accepted HTTPS server keys as `sha256/<base64>` SPKI pins (repeatable).
+    /// When set, a lookup whose server key matches none of them fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_spki_pin: Option<Vec<String>>,
+    /// STUN servers (`host[:port]`, repeatable) tried in order by the `stun`
+    /// provider. Defaults to Google's and Cloudflare's public servers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stun_servers: Option<Vec<String>>,
+    /// Local IP-to-ASN database (iptoasn TSV or MaxMind `.mmdb`) used to fill
+    /// in ASN, ISP and country for the looked-up IP. Reloaded when it changes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn_db_path: Option<String>,
+    // Additional VPN-lost conditions, evaluated on the full lookup result.
+    // Each may be repeated; the VPN counts as lost when any of them matches.
+    /// Organisation names (exact) that indicate the VPN is lost
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org_to_check: Option<Vec<String>>,
+    /// Autonomous system numbers ("AS12345" or "12345") that indicate the VPN is lost
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn_to_check: Option<Vec<String>>,
+    /// Country codes or names that indicate the VPN is lost
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country_to_check: Option<Vec<String>>,
+    /// If set, an exit in any country not listed here indicates the VPN is lost
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_country: Option<Vec<String>>,
+    /// Public IP networks (CIDR or single addresses) that indicate the VPN is lost
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cidr_to_check: Option<Vec<String>>,
+    /// `denylist` (default): the conditions above mean VPN lost.
+    /// `allowlist`: any exit not matching the `allowed_*` entries means VPN lost.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_mode: Option<String>,
+    /// Expected VPN exit ISP names (allowlist mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_isp: Option<Vec<String>>,
+    /// Expected VPN exit ASNs (allowlist mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_asn: Option<Vec<String>>,
+    /// Expected VPN exit networks (allowlist mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_cidr: Option<Vec<String>>,
+    /// Act when the public exit changes between checks: `off` (default),
+    /// `ip` (new IP or ASN) or `asn` (new ASN only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_detection: Option<String>,
+    /// Action run on a detected change (`reboot`, `restart-unit`, `command`).
+    /// Without one, changes are only logged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_action_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_action_arg: Option<String>,
+    /// Also look up the public address over IPv6 (and force the main lookup
+    /// over IPv4) to catch IPv6 traffic bypassing an IPv4-only tunnel
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::config::serialize_option_bool"
+    )]
+    pub ipv6_leak_check: Option<bool>,
+    /// Action run when IPv6 egress goes via the home ISP (`reboot`,
+    /// `restart-unit`, `command`). Without one, leaks are only logged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6_leak_action_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6_leak_action_arg: Option<String>,
+    /// Compare the system's DNS resolvers (resolv.conf and systemd-resolved)
+    /// with `allowed_dns` and run the VPN-lost action on a mismatch
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::config::serialize_option_bool"
+    )]
+    pub dns_leak_check: Option<bool>,
+    /// Resolvers (addresses or CIDR networks, repeatable) allowed while the
+    /// VPN is up, e.g. the tunnel's DNS server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_dns: Option<Vec<String>>,
+    /// resolv.conf file to read (default `/etc/resolv.conf`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolv_conf_path: Option<String>,
+    /// Tunnel interface (`tun0`, `wg0`, ...) that must exist and be up. It
+    /// is checked locally before the connectivity check
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tunnel_interface: Option<String>,
+    /// Action run when the tunnel interface is missing or down (`reboot`,
+    /// `restart-unit`, `command`). Defaults to the VPN-lost action
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tunnel_action_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tunnel_action_arg: Option<String>,
+    /// Also verify that the routes to the connectivity endpoints go out via
+    /// `tunnel_interface`; a bypass runs the tunnel action
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::config::serialize_option_bool"
+    )]
+    pub route_check: Option<bool>,
+    /// WireGuard interface whose peers must have a recent handshake
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wireguard_interface: Option<String>,
+    /// Oldest acceptable peer handshake in seconds (default 180)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wireguard_max_handshake_age_secs: Option<u64>,
+    /// OpenVPN management interface (`host:port` or unix socket path) whose
+    /// state must be CONNECTED
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub openvpn_management: Option<String>,
+    /// NetworkManager connection profile (id or UUID) that must be active
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nm_connection: Option<String>,
+    /// systemd unit running the VPN (e.g. `wg-quick@wg0.service`). It must
+    /// not be failed or inactive; checked before anything else
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vpn_unit: Option<String>,
+    /// Action run when `vpn_unit` is down. Defaults to the VPN-lost action
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vpn_unit_action_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vpn_unit_action_arg: Option<String>,
+    /// Interface the connectivity probes and IP lookups go out of
+    /// (`SO_BINDTODEVICE`), e.g. `wg0` or `eth0` in split-tunnel setups
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind_interface: Option<String>,
+    /// Local address the connectivity probes and IP lookups are sent from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_address: Option<String>,
+    /// Network namespace the checks run in: a name from `ip netns` or an
+    /// absolute path to a namespace file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub netns: Option<String>,
+    /// Run the checks in the network namespace of this process, e.g. a
+    /// container's init (`docker inspect -f '{{.State.Pid}}' <name>`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub netns_pid: Option<u32>,
 }
 
 /// Effective configuration after merging CLI args and XML config/defaults.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct EffectiveConfig {
     pub interval: u64,
     pub isp_to_check: String,
+    pub isp_pattern: Vec<String>,
+    pub isp_match_mode: String,
     pub action_type: String,
     pub action_arg: String,
     pub dry_run: bool,
@@ -78,11 +269,67 @@ pub struct EffectiveConfig {
     pub connectivity_retries: usize,
     pub run_once: bool,
     pub exit_on_error: bool,
+    pub ip_provider: String,
+    pub ip_provider_url: Option<String>,
+    pub ip_provider_isp_field: Option<String>,
+    pub provider_strategy: String,
+    pub ip_providers: Vec<String>,
+    pub provider_quorum: Option<usize>,
+    pub rate_limit_per_minute: Option<u32>,
+    pub lookup_timeout_secs: u64,
+    pub lookup_retries: usize,
+    pub max_response_bytes: usize,
+    pub user_agent: String,
+    pub tls_ca_bundle: Option<String>,
+    pub tls_spki_pin: Vec<String>,
+    pub stun_servers: Vec<String>,
+    pub asn_db_path: Option<String>,
+    pub org_to_check: Vec<String>,
+    pub asn_to_check: Vec<String>,
+    pub country_to_check: Vec<String>,
+    pub expected_country: Vec<String>,
+    pub cidr_to_check: Vec<String>,
+    pub match_mode: String,
+    pub allowed_isp: Vec<String>,
+    pub allowed_asn: Vec<String>,
+    pub allowed_cidr: Vec<String>,
+    pub change_detection: String,
+    pub change_action_type: Option<String>,
+    pub change_action_arg: String,
+    pub ipv6_leak_check: bool,
+    pub ipv6_leak_action_type: Option<String>,
+    pub ipv6_leak_action_arg: String,
+    pub dns_leak_check: bool,
+    pub allowed_dns: Vec<String>,
+    pub resolv_conf_path: String,
+    pub tunnel_interface: Option<String>,
+    pub tunnel_action_type: Option<String>,
+    pub tunnel_action_arg: String,
+    pub route_check: bool,
+    pub wireguard_interface: Option<String>,
+    pub wireguard_max_handshake_age_secs: u64,
+    pub openvpn_management: Option<String>,
+    pub nm_connection: Option<String>,
+    pub vpn_unit: Option<String>,
+    pub vpn_unit_action_type: Option<String>,
+    pub vpn_unit_action_arg: String,
+    pub bind_interface: Option<String>,
+    pub source_address: Option<String>,
+    pub netns: Option<String>,
+    pub netns_pid: Option<u32>,
+}
+
+impl Default for EffectiveConfig {
+    /// Defaults as produced by merging `Config::default()` with empty CLI args.
+    fn default() -> Self {
+        Config::default().merge_with_args(&crate::cli::Args::default())
+    }
 }
 
 impl Config {
     /// Merge CLI args and Config into an EffectiveConfig (CLI overrides Config)
     pub fn merge_with_args(&self, args: &crate::cli::Args) -> EffectiveConfig {
+        use crate::ip_api::client;
         use crate::networking;
 
         let interval = args.interval.or(self.interval).unwrap_or(60);
@@ -92,6 +339,14 @@ impl Config {
             .or_else(|| self.isp_to_check.clone())
             .unwrap_or_else(|| "Hutchison 3G UK Ltd".to_string());
 
+        let isp_pattern = self.isp_pattern.clone().unwrap_or_default();
+
+        let isp_match_mode = args
+            .isp_match_mode
+            .clone()
+            .or_else(|| self.isp_match_mode.clone())
+            .unwrap_or_else(|| "exact".to_string());
+
         let action_type = args
             .vpn_lost_action_type
             .clone()
@@ -146,9 +401,150 @@ impl Config {
 
         let run_once = args.run_once;
 
+        let ip_provider = args
+            .ip_provider
+            .clone()
+            .or_else(|| self.ip_provider.clone())
+            .unwrap_or_else(|| "ip-api".to_string());
+
+        let ip_provider_url = args
+            .ip_provider_url
+            .clone()
+            .or_else(|| self.ip_provider_url.clone());
+
+        let ip_provider_isp_field = self.ip_provider_isp_field.clone();
+
+        let provider_strategy = args
+            .provider_strategy
+            .clone()
+            .or_else(|| self.provider_strategy.clone())
+            .unwrap_or_else(|| "single".to_string());
+        let ip_providers = self.ip_providers.clone().unwrap_or_default();
+        let provider_quorum = args.provider_quorum.or(self.provider_quorum);
+        let rate_limit_per_minute = args.rate_limit_per_minute.or(self.rate_limit_per_minute);
+
+        let lookup_timeout_secs = args
+            .lookup_timeout_secs
+            .or(self.lookup_timeout_secs)
+            .unwrap_or(client::DEFAULT_LOOKUP_TIMEOUT_SECS);
+        let lookup_retries = args
+            .lookup_retries
+            .or(self.lookup_retries)
+            .unwrap_or_else(client::default_retries);
+        let max_response_bytes = args
+            .max_response_bytes
+            .or(self.max_response_bytes)
+            .unwrap_or_else(client::default_max_response_bytes);
+        let user_agent = args
+            .user_agent
+            .clone()
+            .or_else(|| self.user_agent.clone())
+            .unwrap_or_else(|| client::DEFAULT_USER_AGENT.to_string());
+        let tls_ca_bundle = args
+            .tls_ca_bundle
+            .clone()
+            .or_else(|| self.tls_ca_bundle.clone());
+        let tls_spki_pin = self.tls_spki_pin.clone().unwrap_or_default();
+        let stun_servers = self
+            .stun_servers
+            .clone()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| {
+                crate::ip_api::stun::DEFAULT_STUN_SERVERS
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect()
+            });
+        let asn_db_path = self.asn_db_path.clone();
+
+        let org_to_check = self.org_to_check.clone().unwrap_or_default();
+        let asn_to_check = self.asn_to_check.clone().unwrap_or_default();
+        let country_to_check = self.country_to_check.clone().unwrap_or_default();
+        let expected_country = self.expected_country.clone().unwrap_or_default();
+        let cidr_to_check = self.cidr_to_check.clone().unwrap_or_default();
+
+        let match_mode = args
+            .match_mode
+            .clone()
+            .or_else(|| self.match_mode.clone())
+            .unwrap_or_else(|| "denylist".to_string());
+        let allowed_isp = self.allowed_isp.clone().unwrap_or_default();
+        let allowed_asn = self.allowed_asn.clone().unwrap_or_default();
+        let allowed_cidr = self.allowed_cidr.clone().unwrap_or_default();
+
+        let change_detection = args
+            .change_detection
+            .clone()
+            .or_else(|| self.change_detection.clone())
+            .unwrap_or_else(|| "off".to_string());
+        let change_action_type = self.change_action_type.clone();
+        let change_action_arg = self.change_action_arg.clone().unwrap_or_default();
+
+        let ipv6_leak_check = if args.ipv6_leak_check {
+            true
+        } else {
+            self.ipv6_leak_check.unwrap_or(false)
+        };
+        let ipv6_leak_action_type = self.ipv6_leak_action_type.clone();
+        let ipv6_leak_action_arg = self.ipv6_leak_action_arg.clone().unwrap_or_default();
+
+        let dns_leak_check = if args.dns_leak_check {
+            true
+        } else {
+            self.dns_leak_check.unwrap_or(false)
+        };
+        let allowed_dns = self.allowed_dns.clone().unwrap_or_default();
+        let resolv_conf_path = self
+            .resolv_conf_path
+            .clone()
+            .unwrap_or_else(|| networking::DEFAULT_RESOLV_CONF.to_string());
+
+        let tunnel_interface = args
+            .tunnel_interface
+            .clone()
+            .or_else(|| self.tunnel_interface.clone());
+        let tunnel_action_type = self.tunnel_action_type.clone();
+        let tunnel_action_arg = self.tunnel_action_arg.clone().unwrap_or_default();
+        let route_check = if args.route_check {
+            true
+        } else {
+            self.route_check.unwrap_or(false)
+        };
+
+        let wireguard_interface = args
+            .wireguard_interface
+            .clone()
+            .or_else(|| self.wireguard_interface.clone());
+        let wireguard_max_handshake_age_secs = self
+            .wireguard_max_handshake_age_secs
+            .unwrap_or(networking::wireguard::DEFAULT_HANDSHAKE_MAX_AGE_SECS);
+        let openvpn_management = args
+            .openvpn_management
+            .clone()
+            .or_else(|| self.openvpn_management.clone());
+        let nm_connection = args
+            .nm_connection
+            .clone()
+            .or_else(|| self.nm_connection.clone());
+        let vpn_unit = args.vpn_unit.clone().or_else(|| self.vpn_unit.clone());
+        let vpn_unit_action_type = self.vpn_unit_action_type.clone();
+        let vpn_unit_action_arg = self.vpn_unit_action_arg.clone().unwrap_or_default();
+        let bind_interface = args
+            .bind_interface
+            .clone()
+            .or_else(|| self.bind_interface.clone());
+        let source_address = args
+            .source_address
+            .clone()
+            .or_else(|| self.source_address.clone());
+        let netns = args.netns.clone().or_else(|| self.netns.clone());
+        let netns_pid = args.netns_pid.or(self.netns_pid);
+
         EffectiveConfig {
             interval,
             isp_to_check,
+            isp_pattern,
+            isp_match_mode,
             action_type,
             action_arg,
             dry_run,
@@ -158,6 +554,54 @@ impl Config {
             connectivity_retries,
             run_once,
             exit_on_error,
+            ip_provider,
+            ip_provider_url,
+            ip_provider_isp_field,
+            provider_strategy,
+            ip_providers,
+            provider_quorum,
+            rate_limit_per_minute,
+            lookup_timeout_secs,
+            lookup_retries,
+            max_response_bytes,
+            user_agent,
+            tls_ca_bundle,
+            tls_spki_pin,
+            stun_servers,
+            asn_db_path,
+            org_to_check,
+            asn_to_check,
+            country_to_check,
+            expected_country,
+            cidr_to_check,
+            match_mode,
+            allowed_isp,
+            allowed_asn,
+            allowed_cidr,
+            change_detection,
+            change_action_type,
+            change_action_arg,
+            ipv6_leak_check,
+            ipv6_leak_action_type,
+            ipv6_leak_action_arg,
+            dns_leak_check,
+            allowed_dns,
+            resolv_conf_path,
+            tunnel_interface,
+            tunnel_action_type,
+            tunnel_action_arg,
+            route_check,
+            wireguard_interface,
+            wireguard_max_handshake_age_secs,
+            openvpn_management,
+            nm_connection,
+            vpn_unit,
+            vpn_unit_action_type,
+            vpn_unit_action_arg,
+            bind_interface,
+            source_address,
+            netns,
+            netns_pid,
         }
     }
 }
@@ -189,6 +633,14 @@ impl Config {
             connectivity_retries,
         )
     }
+
+    /// Validate every field of a merged `EffectiveConfig`.
+    ///
+    /// This runs `validate_values` and the checks for settings added since,
+    /// reporting all problems together.
+    pub fn validate_effective(eff: &EffectiveConfig) -> std::result::Result<(), ValidationErrors> {
+        validation::validate_effective(eff)
+    }
 }
 
 impl Default for Config {
@@ -196,6 +648,8 @@ impl Default for Config {
         Config {
             interval: Some(60),
             isp_to_check: Some("Hutchison 3G UK Ltd".to_string()),
+            isp_pattern: None,
+            isp_match_mode: Some("exact".to_string()),
             vpn_lost_action_type: Some("reboot".to_string()),
             vpn_lost_action_arg: Some("/sbin/shutdown -r now".to_string()),
             dry_run: Some(false),
@@ -208,6 +662,54 @@ impl Default for Config {
             connectivity_timeout_secs: Some(crate::networking::DEFAULT_TIMEOUT_SECS),
             connectivity_retries: Some(crate::networking::DEFAULT_RETRIES),
             exit_on_error: Some(false),
+            ip_provider: Some("ip-api".to_string()),
+            ip_provider_url: None,
+            ip_provider_isp_field: None,
+            provider_strategy: Some("single".to_string()),
+            ip_providers: None,
+            provider_quorum: None,
+            rate_limit_per_minute: None,
+            lookup_timeout_secs: Some(crate::ip_api::client::DEFAULT_LOOKUP_TIMEOUT_SECS),
+            lookup_retries: None,
+            max_response_bytes: None,
+            user_agent: Some(crate::ip_api::client::DEFAULT_USER_AGENT.to_string()),
+            tls_ca_bundle: None,
+            tls_spki_pin: None,
+            stun_servers: None,
+            asn_db_path: None,
+            org_to_check: None,
+            asn_to_check: None,
+            country_to_check: None,
+            expected_country: None,
+            cidr_to_check: None,
+            match_mode: Some("denylist".to_string()),
+            allowed_isp: None,
+            allowed_asn: None,
+            allowed_cidr: None,
+            change_detection: Some("off".to_string()),
+            change_action_type: None,
+            change_action_arg: None,
+            ipv6_leak_check: Some(false),
+            ipv6_leak_action_type: None,
+            ipv6_leak_action_arg: None,
+            dns_leak_check: Some(false),
+            allowed_dns: None,
+            resolv_conf_path: None,
+            tunnel_interface: None,
+            tunnel_action_type: None,
+            tunnel_action_arg: None,
+            route_check: Some(false),
+            wireguard_interface: None,
+            wireguard_max_handshake_age_secs: None,
+            openvpn_management: None,
+            nm_connection: None,
+            vpn_unit: None,
+            vpn_unit_action_type: None,
+            vpn_unit_action_arg: None,
+            bind_interface: None,
+            source_address: None,
+            netns: None,
+            netns_pid: None,
         }
     }
 }
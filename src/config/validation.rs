@@ -1,5 +1,25 @@
 use std::fmt;
 
+use super::{ChangeMode, EffectiveConfig, CHANGE_MODES};
+use crate::ip_api::info::parse_asn;
+use crate::ip_api::provider::{
+    parse_provider_spec, ProviderKind, PROVIDER_NAMES, PROVIDER_STRATEGIES,
+};
+use crate::ip_api::{dns, stun, tls};
+use crate::matching::{Cidr, MatchMode, Pattern, PatternMode, MATCH_MODES, PATTERN_MODES};
+use crate::networking;
+
+/// Action types accepted for `vpn_lost_action_type` and the other
+/// `*_action_type` settings.
+const ACTION_TYPES: [&str; 6] = [
+    "reboot",
+    "restart-unit",
+    "command",
+    "openvpn-sigusr1",
+    "openvpn-sighup",
+    "nm-activate",
+];
+
 /// ValidationErrors represents one or more config validation problems.
 #[derive(Debug, PartialEq, Eq)]
 pub struct ValidationErrors(pub Vec<String>);
@@ -34,20 +54,23 @@ pub fn validate_values(
         errors.push("isp_to_check must be a non-empty string".to_string());
     }
 
-    let allowed = ["reboot", "restart-unit", "command"];
-    if !allowed.contains(&action_type) {
+    if !ACTION_TYPES.contains(&action_type) {
         errors.push(format!(
             "vpn_lost_action_type must be one of: {}",
-            allowed.join(", ")
+            ACTION_TYPES.join(", ")
         ));
     }
 
-    // For restart-unit and command, ensure arg is non-empty
-    if (action_type == "restart-unit" || action_type == "command") && action_arg.trim().is_empty() {
-        errors.push(
-            "vpn_lost_action_arg must be provided for restart-unit and command action types"
-                .to_string(),
-        );
+    // Every action but reboot needs an argument
+    if ACTION_TYPES.contains(&action_type) && action_type != "reboot" {
+        if action_arg.trim().is_empty() {
+            errors.push(format!(
+                "vpn_lost_action_arg must be provided for the {} action type",
+                action_type
+            ));
+        } else if let Err(e) = check_openvpn_action_arg(action_type, action_arg) {
+            errors.push(format!("vpn_lost_action_arg: {}", e));
+        }
     }
 
     // Connectivity checks: endpoints and ports must be present and timeout/retries sensible
@@ -76,9 +99,429 @@ pub fn validate_values(
     }
 }
 
+/// Validate a merged configuration: the original `validate_values` checks
+/// plus the newer settings that are not part of that signature.
+pub fn validate_effective(eff: &EffectiveConfig) -> std::result::Result<(), ValidationErrors> {
+    let mut errors = match validate_values(
+        eff.interval,
+        &eff.isp_to_check,
+        &eff.action_type,
+        &eff.action_arg,
+        &eff.connectivity_endpoints,
+        &eff.connectivity_ports,
+        eff.connectivity_timeout_secs,
+        eff.connectivity_retries,
+    ) {
+        Ok(()) => Vec::new(),
+        Err(ValidationErrors(e)) => e,
+    };
+
+    match ProviderKind::parse(&eff.ip_provider) {
+        None => errors.push(format!(
+            "ip_provider must be one of: {}",
+            PROVIDER_NAMES.join(", ")
+        )),
+        Some(ProviderKind::Custom) => {
+            if eff
+                .ip_provider_url
+                .as_deref()
+                .is_none_or(|u| u.trim().is_empty())
+            {
+                errors.push("ip_provider_url must be provided for the custom provider".to_string());
+            }
+        }
+        Some(_) => {}
+    }
+
+    if let Some(url) = &eff.ip_provider_url {
+        match ProviderKind::parse(&eff.ip_provider) {
+            Some(kind) if !kind.is_http() => {
+                if let Err(e) = check_endpoint(kind, url) {
+                    errors.push(format!("ip_provider_url: {}", e));
+                }
+            }
+            _ => {
+                if !(url.starts_with("http://") || url.starts_with("https://")) {
+                    errors.push("ip_provider_url must start with http:// or https://".to_string());
+                }
+            }
+        }
+    }
+
+    if eff.lookup_timeout_secs == 0 {
+        errors.push("lookup_timeout_secs must be greater than zero".to_string());
+    }
+    if eff.lookup_retries == 0 {
+        errors.push("lookup_retries must be at least 1".to_string());
+    }
+    if eff.max_response_bytes == 0 {
+        errors.push("max_response_bytes must be greater than zero".to_string());
+    }
+    if eff.user_agent.trim().is_empty()
+        || reqwest::header::HeaderValue::from_str(&eff.user_agent).is_err()
+    {
+        errors.push("user_agent must be a non-empty, printable header value".to_string());
+    }
+
+    if let Some(path) = &eff.tls_ca_bundle {
+        if let Err(e) = tls::load_ca_bundle(path) {
+            errors.push(format!("tls_ca_bundle: {:#}", e));
+        }
+    }
+    for pin in &eff.tls_spki_pin {
+        if let Err(e) = tls::normalize_pin(pin) {
+            errors.push(format!("tls_spki_pin: {}", e));
+        }
+    }
+    if !eff.tls_spki_pin.is_empty() {
+        for url in lookup_urls(eff) {
+            if !url.starts_with("https://") {
+                errors.push(format!(
+                    "tls_spki_pin requires https provider URLs, got '{}'",
+                    url
+                ));
+            }
+        }
+    }
+
+    for spec in &eff.ip_providers {
+        match parse_provider_spec(spec) {
+            Err(e) => errors.push(format!("ip_providers: {}", e)),
+            Ok((kind, Some(endpoint))) if !kind.is_http() => {
+                if let Err(e) = check_endpoint(kind, &endpoint) {
+                    errors.push(format!("ip_providers: {}", e));
+                }
+            }
+            Ok(_) => {}
+        }
+    }
+
+    for server in &eff.stun_servers {
+        if let Err(e) = stun::validate_server(server) {
+            errors.push(format!("stun_servers: {}", e));
+        }
+    }
+
+    // Only check the file exists: parsing a full database on every reload
+    // would be slow, and a broken file is reported when it is loaded.
+    if let Some(path) = &eff.asn_db_path {
+        if !std::path::Path::new(path).is_file() {
+            errors.push(format!("asn_db_path '{}' is not a readable file", path));
+        }
+    }
+
+    match eff.provider_strategy.as_str() {
+        "single" => {}
+        "failover" => {
+            if eff.ip_providers.is_empty() {
+                errors.push(
+                    "failover provider_strategy requires at least one ip_providers entry"
+                        .to_string(),
+                );
+            }
+        }
+        "quorum" => {
+            let n = eff.ip_providers.len();
+            if n < 2 {
+                errors.push(
+                    "quorum provider_strategy requires at least two ip_providers".to_string(),
+                );
+            }
+            if let Some(q) = eff.provider_quorum {
+                if q == 0 || q > n {
+                    errors.push(format!(
+                        "provider_quorum must be between 1 and the number of ip_providers ({})",
+                        n
+                    ));
+                }
+            }
+        }
+        _ => errors.push(format!(
+            "provider_strategy must be one of: {}",
+            PROVIDER_STRATEGIES.join(", ")
+        )),
+    }
+
+    // ISP patterns are compiled here so a bad regex is rejected at load time
+    // rather than silently never matching.
+    match PatternMode::parse(&eff.isp_match_mode) {
+        None => errors.push(format!(
+            "isp_match_mode must be one of: {}",
+            PATTERN_MODES.join(", ")
+        )),
+        Some(mode) => {
+            for (name, list) in [
+                ("isp_to_check", std::slice::from_ref(&eff.isp_to_check)),
+                ("isp_pattern", eff.isp_pattern.as_slice()),
+                ("allowed_isp", eff.allowed_isp.as_slice()),
+            ] {
+                for p in list {
+                    if let Err(e) = Pattern::compile(p, mode) {
+                        errors.push(format!("{}: {}", name, e));
+                    }
+                }
+            }
+        }
+    }
+
+    if eff.isp_pattern.iter().any(|s| s.trim().is_empty()) {
+        errors.push("isp_pattern contains an empty string".to_string());
+    }
+
+    for asn in &eff.asn_to_check {
+        if parse_asn(asn).is_none() {
+            errors.push(format!(
+                "asn_to_check entry '{}' is not a valid ASN (expected e.g. AS12345)",
+                asn
+            ));
+        }
+    }
+
+    for cidr in &eff.cidr_to_check {
+        if let Err(e) = cidr.parse::<Cidr>() {
+            errors.push(format!("cidr_to_check: {}", e));
+        }
+    }
+
+    match MatchMode::parse(&eff.match_mode) {
+        None => errors.push(format!(
+            "match_mode must be one of: {}",
+            MATCH_MODES.join(", ")
+        )),
+        Some(MatchMode::Allowlist) => {
+            if eff.allowed_isp.is_empty()
+                && eff.allowed_asn.is_empty()
+                && eff.allowed_cidr.is_empty()
+            {
+                errors.push(
+                    "allowlist match_mode requires at least one allowed_isp, allowed_asn or allowed_cidr"
+                        .to_string(),
+                );
+            }
+        }
+        Some(MatchMode::Denylist) => {}
+    }
+
+    for asn in &eff.allowed_asn {
+        if parse_asn(asn).is_none() {
+            errors.push(format!("allowed_asn entry '{}' is not a valid ASN", asn));
+        }
+    }
+
+    for cidr in &eff.allowed_cidr {
+        if let Err(e) = cidr.parse::<Cidr>() {
+            errors.push(format!("allowed_cidr: {}", e));
+        }
+    }
+
+    for cidr in &eff.allowed_dns {
+        if let Err(e) = cidr.parse::<Cidr>() {
+            errors.push(format!("allowed_dns: {}", e));
+        }
+    }
+    if eff.dns_leak_check && eff.allowed_dns.is_empty() {
+        errors.push("dns_leak_check requires at least one allowed_dns entry".to_string());
+    }
+
+    for (name, list) in [
+        ("org_to_check", &eff.org_to_check),
+        ("country_to_check", &eff.country_to_check),
+        ("expected_country", &eff.expected_country),
+        ("allowed_isp", &eff.allowed_isp),
+    ] {
+        if list.iter().any(|s| s.trim().is_empty()) {
+            errors.push(format!("{} contains an empty string", name));
+        }
+    }
+
+    match ChangeMode::parse(&eff.change_detection) {
+        None => errors.push(format!(
+            "change_detection must be one of: {}",
+            CHANGE_MODES.join(", ")
+        )),
+        // A single check has no earlier exit to compare against.
+        Some(ChangeMode::Ip | ChangeMode::Asn) if eff.run_once => {
+            errors.push("change_detection cannot be used with run_once".to_string())
+        }
+        Some(_) => {}
+    }
+    check_optional_action(
+        "change",
+        eff.change_action_type.as_deref(),
+        &eff.change_action_arg,
+        &mut errors,
+    );
+    if let Some(name) = &eff.tunnel_interface {
+        if !networking::is_valid_interface_name(name) {
+            errors.push(format!(
+                "tunnel_interface '{}' is not a valid interface name",
+                name
+            ));
+        }
+    }
+    if let Some(name) = &eff.wireguard_interface {
+        if !networking::is_valid_interface_name(name) {
+            errors.push(format!(
+                "wireguard_interface '{}' is not a valid interface name",
+                name
+            ));
+        }
+    }
+    if let Some(addr) = &eff.openvpn_management {
+        if let Err(e) = addr.parse::<networking::openvpn::ManagementAddr>() {
+            errors.push(format!("openvpn_management: {}", e));
+        }
+    }
+    if eff
+        .nm_connection
+        .as_deref()
+        .is_some_and(|n| n.trim().is_empty())
+    {
+        errors.push("nm_connection must not be empty".to_string());
+    }
+    if eff.wireguard_max_handshake_age_secs == 0 {
+        errors.push("wireguard_max_handshake_age_secs must be at least 1".to_string());
+    }
+    if eff.route_check && eff.tunnel_interface.is_none() {
+        errors.push("route_check requires tunnel_interface to be set".to_string());
+    }
+    if let Some(name) = &eff.bind_interface {
+        if !networking::is_valid_interface_name(name) {
+            errors.push(format!(
+                "bind_interface '{}' is not a valid interface name",
+                name
+            ));
+        }
+    }
+    if let Some(addr) = &eff.source_address {
+        if addr.parse::<std::net::IpAddr>().is_err() {
+            errors.push(format!("source_address '{}' is not an IP address", addr));
+        } else if eff.ipv6_leak_check {
+            errors.push(
+                "ipv6_leak_check needs both address families and cannot be combined with source_address"
+                    .to_string(),
+            );
+        }
+    }
+    if eff.netns.is_some() && eff.netns_pid.is_some() {
+        errors.push("netns and netns_pid cannot both be set".to_string());
+    }
+    if let Some(ns) = &eff.netns {
+        if !ns.starts_with('/') && !networking::netns::is_valid_netns_name(ns) {
+            errors.push(format!(
+                "netns '{}' must be a namespace name or an absolute path",
+                ns
+            ));
+        }
+    }
+    if eff.netns_pid == Some(0) {
+        errors.push("netns_pid must be a process ID greater than 0".to_string());
+    }
+    if let Some(unit) = &eff.vpn_unit {
+        if !networking::systemd::is_valid_unit_name(unit) {
+            errors.push(format!("vpn_unit '{}' is not a valid unit name", unit));
+        }
+    }
+    check_optional_action(
+        "vpn_unit",
+        eff.vpn_unit_action_type.as_deref(),
+        &eff.vpn_unit_action_arg,
+        &mut errors,
+    );
+    check_optional_action(
+        "tunnel",
+        eff.tunnel_action_type.as_deref(),
+        &eff.tunnel_action_arg,
+        &mut errors,
+    );
+    check_optional_action(
+        "ipv6_leak",
+        eff.ipv6_leak_action_type.as_deref(),
+        &eff.ipv6_leak_action_arg,
+        &mut errors,
+    );
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationErrors(errors))
+    }
+}
+
+/// Validate an optional `<name>_action_type`/`<name>_action_arg` pair with
+/// the same rules as the VPN-lost action.
+fn check_optional_action(
+    name: &str,
+    action_type: Option<&str>,
+    action_arg: &str,
+    errors: &mut Vec<String>,
+) {
+    let Some(action_type) = action_type else {
+        return;
+    };
+    if !ACTION_TYPES.contains(&action_type) {
+        errors.push(format!(
+            "{}_action_type must be one of: {}",
+            name,
+            ACTION_TYPES.join(", ")
+        ));
+    } else if action_type != "reboot" && action_arg.trim().is_empty() {
+        errors.push(format!(
+            "{}_action_arg must be provided for the {} action type",
+            name, action_type
+        ));
+    } else if let Err(e) = check_openvpn_action_arg(action_type, action_arg) {
+        errors.push(format!("{}_action_arg: {}", name, e));
+    }
+}
+
+/// The OpenVPN signal actions take a management interface address.
+fn check_openvpn_action_arg(action_type: &str, action_arg: &str) -> Result<(), String> {
+    if action_type.starts_with("openvpn-") {
+        action_arg
+            .parse::<networking::openvpn::ManagementAddr>()
+            .map(|_| ())
+    } else {
+        Ok(())
+    }
+}
+
+/// Validate the `host[:port]` endpoint of a DNS or STUN provider.
+fn check_endpoint(kind: ProviderKind, endpoint: &str) -> Result<(), String> {
+    if kind.is_dns() {
+        dns::validate_resolver(endpoint)
+    } else {
+        stun::validate_server(endpoint)
+    }
+}
+
+/// HTTP URLs the configured lookup strategy will request (unknown provider
+/// names are skipped; they are reported separately, and DNS/STUN providers
+/// have no URL).
+fn lookup_urls(eff: &EffectiveConfig) -> Vec<String> {
+    if eff.provider_strategy == "single" {
+        let Some(kind) = ProviderKind::parse(&eff.ip_provider).filter(|k| k.is_http()) else {
+            return Vec::new();
+        };
+        return eff
+            .ip_provider_url
+            .clone()
+            .or_else(|| kind.default_url())
+            .into_iter()
+            .collect();
+    }
+    eff.ip_providers
+        .iter()
+        .filter_map(|spec| parse_provider_spec(spec).ok())
+        .filter(|(kind, _)| kind.is_http())
+        .filter_map(|(kind, url)| url.or_else(|| kind.default_url()))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::validate_values;
+    use super::{validate_effective, validate_values};
+    use crate::config::EffectiveConfig;
     use crate::networking;
 
     #[test]
@@ -179,4 +622,427 @@ mod tests {
             .to_string()
             .contains("vpn_lost_action_arg must be provided"));
     }
+
+    #[test]
+    fn validate_effective_defaults_ok() {
+        assert!(validate_effective(&EffectiveConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn validate_effective_unknown_provider() {
+        let eff = EffectiveConfig {
+            ip_provider: "whatismyip".to_string(),
+            ..Default::default()
+        };
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("ip_provider must be one of"));
+    }
+
+    #[test]
+    fn validate_effective_custom_provider_needs_url() {
+        let mut eff = EffectiveConfig {
+            ip_provider: "custom".to_string(),
+            ..Default::default()
+        };
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("ip_provider_url must be provided"));
+
+        eff.ip_provider_url = Some("ftp://example.com/ip".to_string());
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("must start with http"));
+
+        eff.ip_provider_url = Some("https://example.com/ip".to_string());
+        assert!(validate_effective(&eff).is_ok());
+    }
+
+    #[test]
+    fn validate_effective_dns_provider_resolver() {
+        let mut eff = EffectiveConfig {
+            ip_provider: "dns-opendns".to_string(),
+            ..Default::default()
+        };
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.ip_provider_url = Some("208.67.220.220:5353".to_string());
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.ip_provider_url = Some("https://example.com/ip".to_string());
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("invalid DNS resolver address"), "{}", err);
+
+        eff.ip_provider_url = None;
+        eff.provider_strategy = "failover".to_string();
+        eff.ip_providers = vec!["dns-google=not a host".into()];
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(
+            err.contains("ip_providers: invalid DNS resolver"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_effective_change_detection() {
+        let mut eff = EffectiveConfig {
+            change_detection: "ip".to_string(),
+            ..Default::default()
+        };
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.change_detection = "sometimes".to_string();
+        eff.change_action_type = Some("command".to_string());
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("change_detection must be one of"), "{}", err);
+        assert!(
+            err.contains("change_action_arg must be provided"),
+            "{}",
+            err
+        );
+
+        eff.change_detection = "asn".to_string();
+        eff.change_action_type = Some("notify".to_string());
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("change_action_type must be one of"), "{}", err);
+
+        eff.change_action_type = None;
+        eff.run_once = true;
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(
+            err.contains("change_detection cannot be used with run_once"),
+            "{}",
+            err
+        );
+        eff.change_detection = "off".to_string();
+        assert!(validate_effective(&eff).is_ok());
+    }
+
+    #[test]
+    fn validate_effective_tunnel_interface() {
+        let mut eff = EffectiveConfig {
+            tunnel_interface: Some("wg0".to_string()),
+            ..Default::default()
+        };
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.tunnel_interface = Some("wg0/../eth0".to_string());
+        eff.tunnel_action_type = Some("restart-unit".to_string());
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("not a valid interface name"), "{}", err);
+        assert!(
+            err.contains("tunnel_action_arg must be provided"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_effective_wireguard() {
+        let mut eff = EffectiveConfig {
+            wireguard_interface: Some("wg0".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(eff.wireguard_max_handshake_age_secs, 180);
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.wireguard_interface = Some("wg 0".to_string());
+        eff.wireguard_max_handshake_age_secs = 0;
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("wireguard_interface 'wg 0'"), "{}", err);
+        assert!(err.contains("must be at least 1"), "{}", err);
+    }
+
+    #[test]
+    fn validate_effective_openvpn() {
+        let mut eff = EffectiveConfig {
+            openvpn_management: Some("/run/openvpn/client.sock".to_string()),
+            action_type: "openvpn-sigusr1".to_string(),
+            action_arg: "127.0.0.1:7505".to_string(),
+            ..Default::default()
+        };
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.openvpn_management = Some("localhost".to_string());
+        eff.tunnel_action_type = Some("openvpn-sighup".to_string());
+        eff.tunnel_action_arg = "client.sock".to_string();
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("openvpn_management: 'localhost'"), "{}", err);
+        assert!(err.contains("tunnel_action_arg: 'client.sock'"), "{}", err);
+    }
+
+    #[test]
+    fn validate_effective_socket_bind() {
+        let mut eff = EffectiveConfig {
+            bind_interface: Some("wg0".to_string()),
+            source_address: Some("10.8.0.6".to_string()),
+            ..Default::default()
+        };
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.bind_interface = Some("wg0:1".to_string());
+        eff.source_address = Some("10.8.0".to_string());
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("bind_interface 'wg0:1'"), "{}", err);
+        assert!(
+            err.contains("source_address '10.8.0' is not an IP"),
+            "{}",
+            err
+        );
+
+        eff.bind_interface = None;
+        eff.source_address = Some("fd00::6".to_string());
+        eff.ipv6_leak_check = true;
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(
+            err.contains("cannot be combined with source_address"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_effective_netns() {
+        let mut eff = EffectiveConfig {
+            netns: Some("vpn".to_string()),
+            ..Default::default()
+        };
+        assert!(validate_effective(&eff).is_ok());
+        eff.netns = Some("/run/docker/netns/1a2b".to_string());
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.netns = Some("../vpn".to_string());
+        eff.netns_pid = Some(0);
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("netns '../vpn'"), "{}", err);
+        assert!(err.contains("cannot both be set"), "{}", err);
+        assert!(err.contains("netns_pid must be"), "{}", err);
+    }
+
+    #[test]
+    fn validate_effective_vpn_unit() {
+        let mut eff = EffectiveConfig {
+            vpn_unit: Some("wg-quick@wg0.service".to_string()),
+            vpn_unit_action_type: Some("restart-unit".to_string()),
+            vpn_unit_action_arg: "wg-quick@wg0.service".to_string(),
+            ..Default::default()
+        };
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.vpn_unit = Some("wg-quick".to_string());
+        eff.vpn_unit_action_type = Some("stop".to_string());
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(
+            err.contains("vpn_unit 'wg-quick' is not a valid unit name"),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains("vpn_unit_action_type must be one of"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_effective_network_manager() {
+        let mut eff = EffectiveConfig {
+            nm_connection: Some("work-vpn".to_string()),
+            action_type: "nm-activate".to_string(),
+            action_arg: "work-vpn".to_string(),
+            ..Default::default()
+        };
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.nm_connection = Some(" ".to_string());
+        eff.action_arg = String::new();
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("nm_connection must not be empty"), "{}", err);
+        assert!(
+            err.contains("vpn_lost_action_arg must be provided for the nm-activate"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_effective_dns_leak_check() {
+        let mut eff = EffectiveConfig {
+            dns_leak_check: true,
+            ..Default::default()
+        };
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("requires at least one allowed_dns"), "{}", err);
+
+        eff.allowed_dns = vec!["10.8.0.1".into(), "fd00::/8".into()];
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.allowed_dns.push("vpn-dns".into());
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("allowed_dns: invalid IP address"), "{}", err);
+    }
+
+    #[test]
+    fn validate_effective_ipv6_leak_action() {
+        let mut eff = EffectiveConfig {
+            ipv6_leak_check: true,
+            ipv6_leak_action_type: Some("restart-unit".to_string()),
+            ipv6_leak_action_arg: "wg-quick@wg0".to_string(),
+            ..Default::default()
+        };
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.ipv6_leak_action_arg = String::new();
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(
+            err.contains("ipv6_leak_action_arg must be provided"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_effective_stun_servers() {
+        let mut eff = EffectiveConfig {
+            ip_provider: "stun".to_string(),
+            ..Default::default()
+        };
+        assert!(!eff.stun_servers.is_empty());
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.stun_servers = vec!["stun:stun.example.org".into()];
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("stun_servers: invalid STUN server"), "{}", err);
+
+        eff.stun_servers = vec!["stun.example.org".into()];
+        eff.ip_provider_url = Some("https://example.com".into());
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(
+            err.contains("ip_provider_url: invalid STUN server"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_effective_match_rules() {
+        let eff = EffectiveConfig {
+            asn_to_check: vec!["AS64500".into(), "nope".into()],
+            cidr_to_check: vec!["203.0.113.0/24".into(), "203.0.113.0/99".into()],
+            expected_country: vec![" ".into()],
+            ..Default::default()
+        };
+        let errs = validate_effective(&eff).unwrap_err().0;
+        assert_eq!(errs.len(), 3, "unexpected errors: {:?}", errs);
+        assert!(errs[0].contains("'nope' is not a valid ASN"));
+        assert!(errs[1].contains("invalid prefix length"));
+        assert!(errs[2].contains("expected_country contains an empty string"));
+    }
+
+    #[test]
+    fn validate_effective_allowlist_mode() {
+        let mut eff = EffectiveConfig {
+            match_mode: "allowlist".into(),
+            ..Default::default()
+        };
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("requires at least one allowed_isp"));
+
+        eff.allowed_asn = vec!["AS42708".into()];
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.allowed_cidr = vec!["bogus".into()];
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("allowed_cidr"));
+
+        eff.match_mode = "maybe".into();
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("match_mode must be one of: denylist, allowlist"));
+    }
+
+    #[test]
+    fn validate_effective_rejects_bad_regex() {
+        let mut eff = EffectiveConfig {
+            isp_pattern: vec!["^Hutchison.*".into(), "Three (UK".into()],
+            isp_match_mode: "regex".into(),
+            ..Default::default()
+        };
+        let errs = validate_effective(&eff).unwrap_err().0;
+        assert_eq!(errs.len(), 1, "unexpected errors: {:?}", errs);
+        assert!(errs[0].starts_with("isp_pattern: invalid regex 'Three (UK'"));
+
+        eff.isp_pattern.pop();
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.isp_match_mode = "fuzzy".into();
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("isp_match_mode must be one of"));
+    }
+
+    #[test]
+    fn validate_effective_quorum_strategy() {
+        let mut eff = EffectiveConfig {
+            provider_strategy: "quorum".into(),
+            ip_providers: vec!["ip-api".into()],
+            ..Default::default()
+        };
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("requires at least two ip_providers"));
+
+        eff.ip_providers.push("ipinfo".into());
+        eff.ip_providers.push("custom".into());
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("custom provider entries need a URL"));
+
+        eff.ip_providers[2] = "custom=https://lookup.example/json".into();
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.provider_quorum = Some(4);
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("provider_quorum must be between 1 and"));
+
+        eff.provider_strategy = "random".into();
+        assert!(validate_effective(&eff).is_err());
+    }
+
+    #[test]
+    fn validate_effective_lookup_settings() {
+        let eff = EffectiveConfig {
+            lookup_timeout_secs: 0,
+            lookup_retries: 0,
+            max_response_bytes: 0,
+            user_agent: "bad\nagent".into(),
+            ..Default::default()
+        };
+        let errs = validate_effective(&eff).unwrap_err().0;
+        assert_eq!(errs.len(), 4, "{:?}", errs);
+    }
+
+    #[test]
+    fn validate_effective_failover_needs_providers() {
+        let mut eff = EffectiveConfig {
+            provider_strategy: "failover".into(),
+            ..Default::default()
+        };
+        assert!(validate_effective(&eff).is_err());
+        eff.ip_providers = vec!["ipinfo".into()];
+        assert!(validate_effective(&eff).is_ok());
+    }
+
+    #[test]
+    fn validate_effective_tls_settings() {
+        let mut eff = EffectiveConfig {
+            tls_spki_pin: vec!["sha256/tJNV4o/LESY35TRGTA4nietgeSVXNR2QeFq2tiNi1JI=".into()],
+            ..Default::default()
+        };
+        // The default ip-api endpoint is plain http.
+        let err = validate_effective(&eff).unwrap_err().to_string();
+        assert!(err.contains("requires https provider URLs"), "{}", err);
+
+        eff.ip_provider_url = Some("https://127.0.0.1:8443/json".into());
+        assert!(validate_effective(&eff).is_ok());
+
+        eff.tls_spki_pin.push("md5/abc".into());
+        eff.tls_ca_bundle = Some("/nonexistent/ca.pem".into());
+        let errs = validate_effective(&eff).unwrap_err().0;
+        assert_eq!(errs.len(), 2, "{:?}", errs);
+    }
 }
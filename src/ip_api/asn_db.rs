@@ -0,0 +1,320 @@
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde::Deserialize;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use super::info::IpInfo;
+use super::provider::IpInfoProvider;
+
+/// Marker that starts the metadata section of every MaxMind DB file.
+const MMDB_METADATA_MARKER: &[u8] = b"\xab\xcd\xefMaxMind.com";
+
+/// What the offline database knows about an address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsnRecord {
+    pub asn: u32,
+    pub as_name: Option<String>,
+    /// ISO 3166-1 alpha-2 country code, when the database has one
+    pub country_code: Option<String>,
+}
+
+/// A local IP-to-ASN database, loaded fully into memory.
+///
+/// Two formats are understood: the iptoasn.com TSV dump
+/// (`ip2asn-combined.tsv`, uncompressed) and MaxMind DB files such as
+/// GeoLite2-ASN. The format is detected from the file contents.
+pub enum AsnDatabase {
+    /// Ranges sorted by start address; IPv4 sorts before IPv6.
+    Tsv(Vec<(IpAddr, IpAddr, AsnRecord)>),
+    Mmdb(maxminddb::Reader<Vec<u8>>),
+}
+
+#[derive(Deserialize)]
+struct MmdbRecord {
+    autonomous_system_number: Option<u32>,
+    autonomous_system_organization: Option<String>,
+    country: Option<MmdbCountry>,
+}
+
+#[derive(Deserialize)]
+struct MmdbCountry {
+    iso_code: Option<String>,
+}
+
+impl AsnDatabase {
+    /// Load a database file, detecting its format.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let is_mmdb = bytes
+            .windows(MMDB_METADATA_MARKER.len())
+            .any(|w| w == MMDB_METADATA_MARKER);
+        if is_mmdb {
+            let invalid = |e| anyhow::anyhow!("invalid MaxMind DB {}: {}", path.display(), e);
+            let reader = maxminddb::Reader::from_source(bytes).map_err(invalid)?;
+            // Check the whole file once, so lookups never walk a corrupt
+            // search tree or data section.
+            reader.verify().map_err(invalid)?;
+            return Ok(AsnDatabase::Mmdb(reader));
+        }
+        let text = String::from_utf8(bytes)
+            .map_err(|_| anyhow::anyhow!("{} is neither a TSV nor a MaxMind DB", path.display()))?;
+        Self::parse_tsv(&text).with_context(|| format!("invalid ASN TSV {}", path.display()))
+    }
+
+    /// Parse iptoasn TSV lines: `range_start range_end AS_number country
+    /// AS_description`. Unrouted ranges (AS 0) are skipped.
+    pub fn parse_tsv(text: &str) -> Result<Self> {
+        let mut ranges = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut cols = line.split('\t');
+            let mut next = || cols.next().map(str::trim);
+            let (Some(start), Some(end), Some(asn)) = (next(), next(), next()) else {
+                anyhow::bail!("line {}: expected at least 3 tab-separated columns", n + 1);
+            };
+            let country = next();
+            let name = next();
+            let parse_ip = |s: &str| {
+                s.parse::<IpAddr>()
+                    .map_err(|_| anyhow::anyhow!("line {}: invalid address '{}'", n + 1, s))
+            };
+            let (start, end) = (parse_ip(start)?, parse_ip(end)?);
+            let asn: u32 = asn
+                .parse()
+                .map_err(|_| anyhow::anyhow!("line {}: invalid AS number '{}'", n + 1, asn))?;
+            if asn == 0 {
+                continue;
+            }
+            let known = |s: Option<&str>| {
+                s.filter(|s| !s.is_empty() && *s != "None" && *s != "Unknown")
+                    .map(str::to_string)
+            };
+            ranges.push((
+                start,
+                end,
+                AsnRecord {
+                    asn,
+                    as_name: known(name),
+                    country_code: known(country),
+                },
+            ));
+        }
+        ranges.sort_by_key(|r| r.0);
+        Ok(AsnDatabase::Tsv(ranges))
+    }
+
+    /// Look up the record covering `ip`, if any.
+    pub fn lookup(&self, ip: IpAddr) -> Option<AsnRecord> {
+        match self {
+            AsnDatabase::Tsv(ranges) => {
+                let idx = ranges.partition_point(|(start, _, _)| *start <= ip);
+                let (start, end, rec) = ranges.get(idx.checked_sub(1)?)?;
+                (start.is_ipv4() == ip.is_ipv4() && ip <= *end).then(|| rec.clone())
+            }
+            AsnDatabase::Mmdb(reader) => {
+                let rec: MmdbRecord = reader.lookup(ip).ok()?.decode().ok()??;
+                Some(AsnRecord {
+                    asn: rec.autonomous_system_number?,
+                    as_name: rec.autonomous_system_organization,
+                    country_code: rec.country.and_then(|c| c.iso_code),
+                })
+            }
+        }
+    }
+}
+
+/// An `AsnDatabase` that is reloaded when its file changes on disk.
+///
+/// The file's modification time and size are checked on every lookup. A
+/// failed reload keeps the previous database and logs a warning once; the
+/// file is only read again after it changes.
+pub struct AsnDbFile {
+    path: PathBuf,
+    state: Mutex<DbState>,
+}
+
+#[derive(Default)]
+struct DbState {
+    loaded: Option<(FileStamp, Arc<AsnDatabase>)>,
+    /// Stamp of a file that failed to load, and why.
+    failed: Option<(FileStamp, String)>,
+}
+
+/// Modification time and size of the database file when it was read.
+type FileStamp = (Option<SystemTime>, u64);
+
+impl AsnDbFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        AsnDbFile {
+            path: path.into(),
+            state: Mutex::new(DbState::default()),
+        }
+    }
+
+    /// The current database, (re)loading it first if the file changed.
+    pub fn current(&self) -> Result<Arc<AsnDatabase>> {
+        let stamp: Result<FileStamp> = fs::metadata(&self.path)
+            .map(|m| (m.modified().ok(), m.len()))
+            .with_context(|| format!("failed to read {}", self.path.display()));
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Ok(stamp) = &stamp {
+            if let Some((loaded, db)) = &state.loaded {
+                if stamp == loaded {
+                    return Ok(db.clone());
+                }
+            }
+            // Unchanged since the last failed load: don't read it again.
+            if let Some((failed, reason)) = &state.failed {
+                if stamp == failed {
+                    return match &state.loaded {
+                        Some((_, old)) => Ok(old.clone()),
+                        None => Err(anyhow::anyhow!("{}", reason)),
+                    };
+                }
+            }
+        }
+        let loaded = stamp.and_then(|stamp| match AsnDatabase::load(&self.path) {
+            Ok(db) => Ok((stamp, db)),
+            Err(e) => {
+                state.failed = Some((stamp, format!("{:#}", e)));
+                Err(e)
+            }
+        });
+        match (loaded, state.loaded.as_ref()) {
+            (Ok((stamp, db)), _) => {
+                debug!("loaded ASN database {}", self.path.display());
+                let db = Arc::new(db);
+                state.loaded = Some((stamp, db.clone()));
+                state.failed = None;
+                Ok(db)
+            }
+            (Err(e), Some((_, old))) => {
+                warn!(
+                    "failed to reload ASN database {}, keeping previous: {:#}",
+                    self.path.display(),
+                    e
+                );
+                Ok(old.clone())
+            }
+            (Err(e), None) => Err(e),
+        }
+    }
+
+    /// Fill the ASN, AS name, country and (when missing) ISP/org of `info`
+    /// from the database. Fields the lookup already reported are kept.
+    pub fn enrich(&self, info: &mut IpInfo) -> Result<()> {
+        let Some(ip) = info.ip else {
+            return Ok(());
+        };
+        let Some(rec) = self.current()?.lookup(ip) else {
+            debug!("{} not found in ASN database", ip);
+            return Ok(());
+        };
+        info.asn = info.asn.or(Some(rec.asn));
+        if info.as_name.is_none() {
+            info.as_name = rec.as_name.clone();
+        }
+        if info.isp.is_none() {
+            info.isp = rec.as_name.clone();
+        }
+        if info.org.is_none() {
+            info.org = rec.as_name.clone();
+        }
+        if info.country_code.is_none() {
+            info.country_code = rec.country_code;
+        }
+        Ok(())
+    }
+}
+
+/// Wraps another provider (which only needs to report the public IP) and
+/// resolves ASN/ISP/country from a local database, so no third party sees
+/// the address when paired with the DNS or STUN providers.
+pub struct AsnDbProvider {
+    inner: Box<dyn IpInfoProvider>,
+    db: Arc<AsnDbFile>,
+}
+
+impl AsnDbProvider {
+    /// `db` may be shared by several providers so the file is held once.
+    pub fn new(inner: Box<dyn IpInfoProvider>, db: Arc<AsnDbFile>) -> Self {
+        AsnDbProvider { inner, db }
+    }
+}
+
+impl IpInfoProvider for AsnDbProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn get_ip_info(&self) -> Result<IpInfo> {
+        let mut info = self.inner.get_ip_info()?;
+        self.db.enrich(&mut info)?;
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn tsv_lookup_finds_covering_range() {
+        let db = AsnDatabase::parse_tsv(
+            "203.0.113.0\t203.0.113.255\t64500\tNL\tExample Transit\n\
+             1.0.0.0\t1.0.0.255\t13335\tUS\tCLOUDFLARENET\n\
+             203.0.114.0\t203.0.114.255\t0\tNone\tNot routed\n\
+             2001:db8::\t2001:db8::ffff\t64502\tSE\tExample v6\n",
+        )
+        .unwrap();
+        let rec = db.lookup(ip("203.0.113.7")).unwrap();
+        assert_eq!(rec.asn, 64500);
+        assert_eq!(rec.as_name.as_deref(), Some("Example Transit"));
+        assert_eq!(rec.country_code.as_deref(), Some("NL"));
+        assert_eq!(db.lookup(ip("1.0.0.1")).unwrap().asn, 13335);
+        assert_eq!(db.lookup(ip("2001:db8::1")).unwrap().asn, 64502);
+        assert!(db.lookup(ip("203.0.114.1")).is_none());
+        assert!(db.lookup(ip("9.9.9.9")).is_none());
+        assert!(db.lookup(ip("::1")).is_none());
+    }
+
+    #[test]
+    fn tsv_rejects_garbage() {
+        assert!(AsnDatabase::parse_tsv("not a tsv line\n").is_err());
+        assert!(AsnDatabase::parse_tsv("1.0.0.0\t1.0.0.255\tAS1\n").is_err());
+    }
+
+    #[test]
+    fn enrich_keeps_reported_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("asn.tsv");
+        fs::write(
+            &path,
+            "203.0.113.0\t203.0.113.255\t64500\tNL\tExample Transit\n",
+        )
+        .unwrap();
+        let db = AsnDbFile::new(&path);
+
+        let mut info = IpInfo {
+            ip: Some(ip("203.0.113.7")),
+            isp: Some("Reported ISP".into()),
+            ..Default::default()
+        };
+        db.enrich(&mut info).unwrap();
+        assert_eq!(info.asn, Some(64500));
+        assert_eq!(info.isp.as_deref(), Some("Reported ISP"));
+        assert_eq!(info.org.as_deref(), Some("Example Transit"));
+        assert_eq!(info.country_code.as_deref(), Some("NL"));
+    }
+}
@@ -1,75 +1,241 @@
 use anyhow::{Context, Result};
 use reqwest::blocking::Client;
 use reqwest::header::RETRY_AFTER;
-use serde::Deserialize;
+use serde_json::Value;
 use std::io::Read;
 use std::thread::sleep;
 use std::time::Duration;
 
+use super::error::IpLookupError;
+use super::family::{self, FamilyResolver, IpFamily};
+use super::info::IpInfo;
+use super::ratelimit::RateLimiter;
+use super::tls;
+use crate::config::EffectiveConfig;
+use crate::networking::SocketBind;
+
 /// Default maximum response body size in bytes before we reject the response.
-const DEFAULT_MAX_RESPONSE_BYTES: usize = 5 * 1024 * 1024; // 5MB
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 5 * 1024 * 1024; // 5MB
+/// Default per-request timeout for lookups.
+pub const DEFAULT_LOOKUP_TIMEOUT_SECS: u64 = 5;
+/// Default User-Agent sent with lookups.
+pub const DEFAULT_USER_AGENT: &str = "check_vpn/0.1";
 /// Maximum Retry-After seconds to respect (clamp large values)
 const MAX_RETRY_AFTER_SECS: u64 = 60;
 
-#[derive(Deserialize, Debug)]
-struct IpApiResponse {
-    isp: Option<String>,
+/// HTTP request policy shared by the lookup providers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpSettings {
+    pub timeout: Duration,
+    pub retries: usize,
+    pub max_response_bytes: usize,
+    pub user_agent: String,
+    /// PEM bundle of extra CA certificates to trust for HTTPS providers.
+    pub ca_bundle: Option<String>,
+    /// Accepted server keys as `sha256/<base64>` SPKI pins; empty disables pinning.
+    pub spki_pins: Vec<String>,
+    /// Force lookups over one address family; `None` lets the OS pick.
+    pub family: Option<IpFamily>,
+    /// Interface and/or source address lookups are sent from.
+    pub bind: SocketBind,
+}
+
+impl HttpSettings {
+    /// Settings from the merged configuration (`lookup_timeout_secs`,
+    /// `lookup_retries`, `max_response_bytes`, `user_agent`).
+    pub fn from_effective(eff: &EffectiveConfig) -> Self {
+        HttpSettings {
+            timeout: Duration::from_secs(eff.lookup_timeout_secs),
+            retries: eff.lookup_retries,
+            max_response_bytes: eff.max_response_bytes,
+            user_agent: eff.user_agent.clone(),
+            ca_bundle: eff.tls_ca_bundle.clone(),
+            spki_pins: eff
+                .tls_spki_pin
+                .iter()
+                .filter_map(|p| tls::normalize_pin(p).ok())
+                .collect(),
+            family: None,
+            bind: SocketBind::from_effective(eff),
+        }
+    }
+
+    /// Build a blocking client with this timeout, User-Agent and CA bundle.
+    /// TLS peer information is kept on responses when pins are configured.
+    /// With a `family` set, host names only resolve to addresses of that
+    /// family and the socket is bound to it. `bind` pins the interface and
+    /// source address; a source address also restricts names to its family.
+    pub fn build_client(&self) -> Result<Client> {
+        let mut builder = Client::builder()
+            .user_agent(self.user_agent.as_str())
+            .timeout(self.timeout)
+            .tls_info(!self.spki_pins.is_empty());
+        let source = self.bind.source_address;
+        if let Some(family) = self.family.or(source.map(IpFamily::of)) {
+            if source.is_some_and(|ip| !family.matches(&ip)) {
+                anyhow::bail!(
+                    "source_address {} cannot be used for {} lookups",
+                    source.unwrap(),
+                    family
+                );
+            }
+            builder = builder
+                .dns_resolver(std::sync::Arc::new(FamilyResolver(family)))
+                .local_address(source.unwrap_or(family.unspecified()));
+        }
+        if let Some(name) = &self.bind.interface {
+            builder = builder.interface(name);
+        }
+        if let Some(path) = &self.ca_bundle {
+            for cert in tls::load_ca_bundle(path)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        builder.build().context("failed to build http client")
+    }
+}
+
+/// Defaults honour the legacy `CHECK_VPN_RETRY_COUNT` and
+/// `CHECK_VPN_MAX_RESPONSE_BYTES` environment variables.
+impl Default for HttpSettings {
+    fn default() -> Self {
+        HttpSettings {
+            timeout: Duration::from_secs(DEFAULT_LOOKUP_TIMEOUT_SECS),
+            retries: default_retries(),
+            max_response_bytes: default_max_response_bytes(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            ca_bundle: None,
+            spki_pins: Vec::new(),
+            family: None,
+            bind: SocketBind::default(),
+        }
+    }
 }
 
 /// Query ip-api.com for the current public ISP using a provided blocking HTTP client.
 ///
 /// This function is test-friendly because callers can inject a client and URL.
 pub fn get_isp_with_client_and_url(client: &Client, url: &str, retries: usize) -> Result<String> {
-    let retries = std::cmp::max(1, retries);
+    get_ip_info_with_client_and_url(client, url, retries)?
+        .isp
+        .ok_or_else(|| IpLookupError::MissingField("isp".to_string()).into())
+}
 
-    // Allow tests to override max response bytes via env var
-    let max_bytes: usize = std::env::var("CHECK_VPN_MAX_RESPONSE_BYTES")
-        .ok()
-        .and_then(|s| s.parse::<usize>().ok())
-        .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+/// Query ip-api.com for the full public-IP record (IP, ISP, org, ASN,
+/// country, city, timezone) using a provided blocking HTTP client.
+pub fn get_ip_info_with_client_and_url(
+    client: &Client,
+    url: &str,
+    retries: usize,
+) -> Result<IpInfo> {
+    let buf = fetch_body_with_client_and_url(client, url, retries)?;
+    let v: Value = serde_json::from_slice(&buf).map_err(|e| IpLookupError::Parse(e.to_string()))?;
+    Ok(IpInfo::from_ip_api(&v))
+}
+
+/// Fetch the raw response body from `url`, applying the retry, Retry-After and
+/// response size policies shared by every HTTP-based lookup provider.
+pub fn fetch_body_with_client_and_url(
+    client: &Client,
+    url: &str,
+    retries: usize,
+) -> Result<Vec<u8>> {
+    let settings = HttpSettings {
+        retries,
+        ..HttpSettings::default()
+    };
+    fetch_body_with_settings(client, url, &settings, None)
+}
 
-    let mut last_err: Option<anyhow::Error> = None;
+/// Like `fetch_body_with_client_and_url`, using the retry count, size cap and
+/// SPKI pins from `settings`. A pin mismatch fails the lookup without retrying.
+/// When a `limiter` is given every attempt (including retries) first takes a
+/// token from it. Rate-limit headers and 429 responses are fed back into the
+/// limiter, and when the budget is exhausted the lookup is skipped with an
+/// error instead of sending the request.
+pub fn fetch_body_with_settings(
+    client: &Client,
+    url: &str,
+    settings: &HttpSettings,
+    limiter: Option<&RateLimiter>,
+) -> Result<Vec<u8>> {
+    if let Some(f) = settings.family {
+        family::check_url(url, f)?;
+    }
+    let retries = std::cmp::max(1, settings.retries);
+    let max_bytes = settings.max_response_bytes;
+
+    let mut last_err: Option<IpLookupError> = None;
 
     for attempt in 0..retries {
+        if let Some(limiter) = limiter {
+            if let Err(wait) = limiter.try_acquire() {
+                // Local budget exhausted: skip the request, VPN state unknown.
+                return Err(IpLookupError::RateLimited {
+                    retry_after: Some(wait),
+                }
+                .into());
+            }
+        }
+
         let resp = client.get(url).send();
 
         match resp {
             Ok(r) => {
+                // Never trust anything (not even rate-limit headers) from an
+                // unpinned server.
+                tls::verify_pins(&r, &settings.spki_pins)?;
                 let status = r.status();
+                if let Some(limiter) = limiter {
+                    limiter.observe_headers(r.headers());
+                }
                 // debug prints removed; keep logic compact
                 if !status.is_success() {
                     // Special handling for 429 Too Many Requests where Retry-After may help
                     if status.as_u16() == 429 {
-                        last_err = Some(anyhow::anyhow!("non-success status: {}", status));
+                        let retry_after = parse_retry_after_secs(&r).map(Duration::from_secs);
+                        last_err = Some(IpLookupError::RateLimited { retry_after });
+                        if let Some(limiter) = limiter {
+                            // Respect the server's back-off instead of sleeping here;
+                            // the next check will retry once the budget allows it.
+                            let wait =
+                                retry_after.unwrap_or(Duration::from_secs(MAX_RETRY_AFTER_SECS));
+                            limiter.block_for(wait);
+                            return Err(IpLookupError::RateLimited {
+                                retry_after: Some(wait),
+                            }
+                            .into());
+                        }
                         if attempt + 1 < retries {
-                            if let Some(secs) = parse_retry_after_secs(&r) {
-                                let secs = std::cmp::min(secs, MAX_RETRY_AFTER_SECS);
-                                sleep(Duration::from_secs(secs));
+                            if let Some(d) = retry_after {
+                                sleep(std::cmp::min(d, Duration::from_secs(MAX_RETRY_AFTER_SECS)));
                                 continue;
                             }
                             sleep(Duration::from_millis(500 * (attempt as u64 + 1)));
                             continue;
                         }
-                        return Err(anyhow::anyhow!("non-success status: {}", status));
+                        return Err(IpLookupError::RateLimited { retry_after }.into());
                     }
 
+                    let err = IpLookupError::Http {
+                        status: status.as_u16(),
+                    };
                     // Retry on server errors
                     if status.is_server_error() {
-                        last_err = Some(anyhow::anyhow!("non-success status: {}", status));
+                        last_err = Some(err.clone());
                         if attempt + 1 < retries {
                             sleep(Duration::from_millis(500 * (attempt as u64 + 1)));
                             continue;
                         }
-                        return Err(anyhow::anyhow!("non-success status: {}", status));
                     }
 
-                    return Err(anyhow::anyhow!("non-success status: {}", status));
+                    return Err(err.into());
                 }
 
                 // Enforce max content-length when provided
                 match r.content_length() {
                     Some(len) if (len as usize) > max_bytes => {
-                        return Err(anyhow::anyhow!("response too large: {} bytes", len));
+                        return Err(IpLookupError::TooLarge { limit: max_bytes }.into());
                     }
                     _ => {}
                 }
@@ -77,21 +243,22 @@ pub fn get_isp_with_client_and_url(client: &Client, url: &str, retries: usize) -
                 // Read response body with a cap to avoid unbounded allocations
                 let mut buf: Vec<u8> = Vec::new();
                 let mut reader = r.take((max_bytes as u64) + 1);
-                reader
-                    .read_to_end(&mut buf)
-                    .context("failed to read response body")?;
+                if let Err(e) = reader.read_to_end(&mut buf) {
+                    let err = if e.kind() == std::io::ErrorKind::TimedOut {
+                        IpLookupError::Timeout
+                    } else {
+                        IpLookupError::Transport(format!("failed to read response body: {}", e))
+                    };
+                    return Err(err.into());
+                }
                 if buf.len() > max_bytes {
-                    return Err(anyhow::anyhow!("response too large (>{} bytes)", max_bytes));
+                    return Err(IpLookupError::TooLarge { limit: max_bytes }.into());
                 }
 
-                let parsed: IpApiResponse =
-                    serde_json::from_slice(&buf).context("failed to parse json")?;
-                return parsed
-                    .isp
-                    .ok_or_else(|| anyhow::anyhow!("isp field missing in response"));
+                return Ok(buf);
             }
             Err(e) => {
-                last_err = Some(anyhow::anyhow!("http request failed: {}", e));
+                last_err = Some(IpLookupError::from_reqwest(&e));
                 if attempt + 1 < retries {
                     sleep(Duration::from_millis(500 * (attempt as u64 + 1)));
                     continue;
@@ -100,7 +267,9 @@ pub fn get_isp_with_client_and_url(client: &Client, url: &str, retries: usize) -
         }
     }
 
-    Err(last_err.unwrap_or_else(|| anyhow::anyhow!("failed to query ip api")))
+    Err(last_err
+        .unwrap_or_else(|| IpLookupError::Transport("failed to query ip api".to_string()))
+        .into())
 }
 
 fn parse_retry_after_secs(resp: &reqwest::blocking::Response) -> Option<u64> {
@@ -110,24 +279,41 @@ fn parse_retry_after_secs(resp: &reqwest::blocking::Response) -> Option<u64> {
         .and_then(|s| s.parse::<u64>().ok())
 }
 
-/// Backwards-compatible helper: build a client and use env vars like before.
-pub fn get_isp() -> Result<String> {
-    // Allow tests to override the endpoint via environment variable.
-    let url = std::env::var("CHECK_VPN_TEST_URL")
-        .unwrap_or_else(|_| "http://ip-api.com/json".to_string());
-    // Simple configurable retry policy via env var CHECK_VPN_RETRY_COUNT (default 1)
-    let retries: usize = std::env::var("CHECK_VPN_RETRY_COUNT")
+/// Default ip-api endpoint. Tests may override it via `CHECK_VPN_TEST_URL`.
+pub(crate) fn default_ip_api_url() -> String {
+    std::env::var("CHECK_VPN_TEST_URL").unwrap_or_else(|_| "http://ip-api.com/json".to_string())
+}
+
+/// Retry count for lookups when not configured: env var CHECK_VPN_RETRY_COUNT
+/// (kept for backwards compatibility), else 1.
+pub(crate) fn default_retries() -> usize {
+    std::env::var("CHECK_VPN_RETRY_COUNT")
         .ok()
         .and_then(|s| s.parse().ok())
-        .unwrap_or(1);
+        .unwrap_or(1)
+}
 
-    let client = Client::builder()
-        .user_agent("check_vpn/0.1")
-        .timeout(Duration::from_secs(5))
-        .build()
-        .context("failed to build http client")?;
+/// Response size cap when not configured: env var CHECK_VPN_MAX_RESPONSE_BYTES
+/// (kept for backwards compatibility), else `DEFAULT_MAX_RESPONSE_BYTES`.
+pub(crate) fn default_max_response_bytes() -> usize {
+    std::env::var("CHECK_VPN_MAX_RESPONSE_BYTES")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES)
+}
+
+/// Backwards-compatible helper: build a client and use env vars like before.
+pub fn get_isp() -> Result<String> {
+    let settings = HttpSettings::default();
+    let client = settings.build_client()?;
+    get_isp_with_client_and_url(&client, &default_ip_api_url(), settings.retries)
+}
 
-    get_isp_with_client_and_url(&client, &url, retries)
+/// Like `get_isp` but returns the full ip-api record.
+pub fn get_ip_info() -> Result<IpInfo> {
+    let settings = HttpSettings::default();
+    let client = settings.build_client()?;
+    get_ip_info_with_client_and_url(&client, &default_ip_api_url(), settings.retries)
 }
 
 #[cfg(test)]
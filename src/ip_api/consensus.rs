@@ -0,0 +1,220 @@
+use anyhow::Result;
+use log::{debug, warn};
+
+use super::error::IpLookupError;
+use super::info::IpInfo;
+use super::provider::IpInfoProvider;
+
+/// Smallest number of votes that is a strict majority of `n`.
+pub fn majority(n: usize) -> usize {
+    n / 2 + 1
+}
+
+/// Queries several providers and only reports a "VPN lost" answer when at
+/// least `quorum` of them agree, so one bad lookup cannot trigger an action.
+///
+/// Each successful answer is judged with `is_lost`. The provider returns an
+/// answer from the winning side so `perform_check` reaches the same verdict
+/// when it evaluates the result again:
+/// - `quorum` or more lost votes: a lost answer.
+/// - otherwise, if any provider said active: an active answer.
+/// - otherwise (everything failed, or too few lost votes and no active ones):
+///   an error, i.e. the VPN state is unknown for this check.
+pub struct QuorumProvider {
+    providers: Vec<Box<dyn IpInfoProvider>>,
+    quorum: usize,
+    is_lost: Box<dyn Fn(&IpInfo) -> bool>,
+}
+
+impl QuorumProvider {
+    pub fn new(
+        providers: Vec<Box<dyn IpInfoProvider>>,
+        quorum: usize,
+        is_lost: Box<dyn Fn(&IpInfo) -> bool>,
+    ) -> Self {
+        QuorumProvider {
+            providers,
+            quorum: quorum.max(1),
+            is_lost,
+        }
+    }
+}
+
+impl IpInfoProvider for QuorumProvider {
+    fn name(&self) -> &str {
+        "quorum"
+    }
+
+    fn get_ip_info(&self) -> Result<IpInfo> {
+        let mut lost: Vec<(&str, IpInfo)> = Vec::new();
+        let mut active: Vec<(&str, IpInfo)> = Vec::new();
+        let mut failed: Vec<(&str, anyhow::Error)> = Vec::new();
+
+        for p in &self.providers {
+            match p.get_ip_info() {
+                Ok(info) => {
+                    let verdict = (self.is_lost)(&info);
+                    debug!(
+                        "quorum: {} says {} ({})",
+                        p.name(),
+                        if verdict { "VPN lost" } else { "VPN active" },
+                        info
+                    );
+                    if verdict {
+                        lost.push((p.name(), info));
+                    } else {
+                        active.push((p.name(), info));
+                    }
+                }
+                Err(e) => {
+                    debug!("quorum: {} failed: {}", p.name(), e);
+                    failed.push((p.name(), e));
+                }
+            }
+        }
+
+        if !lost.is_empty() && !active.is_empty() {
+            warn!(
+                "Providers disagree: VPN lost per [{}], active per [{}]",
+                describe(&lost),
+                describe(&active)
+            );
+        }
+        for (name, e) in &failed {
+            warn!("Provider {} failed during quorum check: {}", name, e);
+        }
+
+        if lost.len() >= self.quorum {
+            return Ok(lost.swap_remove(0).1);
+        }
+        if !lost.is_empty() {
+            warn!(
+                "Only {} of {} required providers report VPN lost; not acting",
+                lost.len(),
+                self.quorum
+            );
+        }
+        if let Some((_, info)) = active.into_iter().next() {
+            return Ok(info);
+        }
+        Err(IpLookupError::wrap_all(
+            failed.iter().map(|(_, e)| e),
+            format!(
+                "no provider quorum: {} lost vote(s), {} failure(s), {} required",
+                lost.len(),
+                failed.len(),
+                self.quorum
+            ),
+        ))
+    }
+}
+
+fn describe(votes: &[(&str, IpInfo)]) -> String {
+    votes
+        .iter()
+        .map(|(name, info)| format!("{}: {}", name, info))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(&'static str, Option<&'static str>);
+
+    impl IpInfoProvider for Fixed {
+        fn name(&self) -> &str {
+            self.0
+        }
+        fn get_ip_info(&self) -> Result<IpInfo> {
+            match self.1 {
+                Some(isp) => Ok(IpInfo::from(isp.to_string())),
+                None => Err(anyhow::anyhow!("lookup failed")),
+            }
+        }
+    }
+
+    fn quorum_of(answers: &[Option<&'static str>], quorum: usize) -> QuorumProvider {
+        let providers = answers
+            .iter()
+            .map(|a| Box::new(Fixed("fixed", *a)) as Box<dyn IpInfoProvider>)
+            .collect();
+        QuorumProvider::new(
+            providers,
+            quorum,
+            Box::new(|i: &IpInfo| i.isp.as_deref() == Some("Home")),
+        )
+    }
+
+    #[test]
+    fn majority_sizes() {
+        assert_eq!(majority(1), 1);
+        assert_eq!(majority(2), 2);
+        assert_eq!(majority(3), 2);
+        assert_eq!(majority(4), 3);
+    }
+
+    #[test]
+    fn lost_only_with_enough_votes() {
+        let q = quorum_of(&[Some("Home"), Some("Home"), Some("VPN")], 2);
+        assert_eq!(q.get_isp().unwrap(), "Home");
+
+        let q = quorum_of(&[Some("Home"), Some("VPN"), Some("VPN")], 2);
+        assert_eq!(q.get_isp().unwrap(), "VPN");
+    }
+
+    #[test]
+    fn failures_do_not_count_as_votes() {
+        // One lost vote and two failures: not enough agreement either way.
+        let q = quorum_of(&[Some("Home"), None, None], 2);
+        assert!(q.get_ip_info().is_err());
+
+        // Active answers still win when lost votes fall short.
+        let q = quorum_of(&[Some("Home"), None, Some("VPN")], 2);
+        assert_eq!(q.get_isp().unwrap(), "VPN");
+    }
+
+    struct Limited;
+
+    impl IpInfoProvider for Limited {
+        fn name(&self) -> &str {
+            "limited"
+        }
+        fn get_ip_info(&self) -> Result<IpInfo> {
+            Err(IpLookupError::RateLimited { retry_after: None }.into())
+        }
+    }
+
+    #[test]
+    fn rate_limited_votes_keep_the_error_type() {
+        let is_lost = || Box::new(|i: &IpInfo| i.isp.as_deref() == Some("Home"));
+        let q = QuorumProvider::new(
+            vec![
+                Box::new(Fixed("fixed", Some("Home"))),
+                Box::new(Limited),
+                Box::new(Limited),
+            ],
+            2,
+            is_lost(),
+        );
+        let err = q.get_ip_info().unwrap_err();
+        assert!(err.to_string().contains("no provider quorum"), "{}", err);
+        assert_eq!(
+            err.downcast_ref::<IpLookupError>(),
+            Some(&IpLookupError::RateLimited { retry_after: None })
+        );
+
+        // Other failures mean the state is unknown for another reason.
+        let q = QuorumProvider::new(
+            vec![Box::new(Limited), Box::new(Fixed("fixed", None))],
+            2,
+            is_lost(),
+        );
+        assert!(q
+            .get_ip_info()
+            .unwrap_err()
+            .downcast_ref::<IpLookupError>()
+            .is_none());
+    }
+}
@@ -0,0 +1,477 @@
+use anyhow::Result;
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use super::error::IpLookupError;
+use super::family::{self, IpFamily};
+use super::info::IpInfo;
+use super::provider::IpInfoProvider;
+use crate::networking::SocketBind;
+
+/// OpenDNS resolver1.opendns.com, which answers `myip.opendns.com` with the
+/// address the query came from.
+pub const OPENDNS_RESOLVER: &str = "208.67.222.222:53";
+/// Google's ns1.google.com, which answers `o-o.myaddr.l.google.com` TXT with
+/// the address the query came from.
+pub const GOOGLE_RESOLVER: &str = "216.239.32.10:53";
+/// IPv6 address of resolver1.opendns.com, used for IPv6-only lookups.
+pub const OPENDNS_RESOLVER_V6: &str = "[2620:119:35::35]:53";
+/// IPv6 address of ns1.google.com, used for IPv6-only lookups.
+pub const GOOGLE_RESOLVER_V6: &str = "[2001:4860:4802:32::a]:53";
+
+const OPENDNS_NAME: &str = "myip.opendns.com";
+const GOOGLE_NAME: &str = "o-o.myaddr.l.google.com";
+
+const TYPE_A: u16 = 1;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+/// Which "what is my IP" DNS service to ask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsService {
+    /// `myip.opendns.com` A (or AAAA when the resolver is IPv6).
+    OpenDns,
+    /// `o-o.myaddr.l.google.com` TXT.
+    Google,
+}
+
+/// Public-IP provider that sends one raw UDP DNS query to a resolver.
+///
+/// Useful on networks that block or intercept HTTP lookups. Only the public
+/// IP is learned, so pair it with `cidr_to_check`/`allowed_cidr` conditions.
+pub struct DnsProvider {
+    service: DnsService,
+    resolver: String,
+    timeout: Duration,
+    retries: usize,
+    family: Option<IpFamily>,
+    bind: SocketBind,
+}
+
+impl DnsService {
+    /// The service's own resolver, reachable over `family` (IPv4 when
+    /// `None`).
+    pub fn default_resolver(&self, family: Option<IpFamily>) -> &'static str {
+        match (self, family) {
+            (DnsService::OpenDns, Some(IpFamily::V6)) => OPENDNS_RESOLVER_V6,
+            (DnsService::OpenDns, _) => OPENDNS_RESOLVER,
+            (DnsService::Google, Some(IpFamily::V6)) => GOOGLE_RESOLVER_V6,
+            (DnsService::Google, _) => GOOGLE_RESOLVER,
+        }
+    }
+}
+
+impl DnsProvider {
+    /// `resolver` is `host[:port]` (port 53 when omitted; IPv6 literals need
+    /// brackets when a port is given).
+    pub fn new(service: DnsService, resolver: &str, timeout: Duration, retries: usize) -> Self {
+        DnsProvider {
+            service,
+            resolver: resolver.to_string(),
+            timeout,
+            retries: retries.max(1),
+            family: None,
+            bind: SocketBind::default(),
+        }
+    }
+
+    /// Only query the resolver over `family`, so the answer is the public
+    /// address of that family.
+    pub fn with_family(mut self, family: Option<IpFamily>) -> Self {
+        self.family = family;
+        self
+    }
+
+    /// Send requests from the interface and/or source address in `bind`.
+    pub fn with_bind(mut self, bind: SocketBind) -> Self {
+        self.bind = bind;
+        self
+    }
+
+    fn resolver_addr(&self) -> std::result::Result<SocketAddr, IpLookupError> {
+        match self.family {
+            None => parse_resolver(&self.resolver).map_err(IpLookupError::Transport),
+            Some(f) => resolve_host_port(&self.resolver, 53, Some(f)).ok_or_else(|| {
+                IpLookupError::Transport(format!(
+                    "DNS resolver '{}' has no {} address",
+                    self.resolver, f
+                ))
+            }),
+        }
+    }
+
+    fn query_once(&self, addr: SocketAddr) -> std::result::Result<IpAddr, IpLookupError> {
+        let (name, qtype) = match self.service {
+            DnsService::OpenDns if addr.is_ipv6() => (OPENDNS_NAME, TYPE_AAAA),
+            DnsService::OpenDns => (OPENDNS_NAME, TYPE_A),
+            DnsService::Google => (GOOGLE_NAME, TYPE_TXT),
+        };
+        let io = |e: std::io::Error| match e.kind() {
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => IpLookupError::Timeout,
+            _ => IpLookupError::Transport(format!("dns query to {} failed: {}", addr, e)),
+        };
+        let id = query_id().map_err(io)?;
+        let query = build_query(id, name, qtype);
+
+        let deadline = Instant::now() + self.timeout;
+        let socket = self.bind.udp_socket(&addr).map_err(io)?;
+        socket.connect(addr).map_err(io)?;
+        socket.send(&query).map_err(io)?;
+
+        let mut buf = [0u8; 1500];
+        loop {
+            // One deadline for the whole query, however many stray
+            // datagrams arrive before the answer.
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(IpLookupError::Timeout);
+            }
+            socket.set_read_timeout(Some(remaining)).map_err(io)?;
+            let n = socket.recv(&mut buf).map_err(io)?;
+            // Ignore stray datagrams that do not answer our query.
+            if n >= 2 && u16::from_be_bytes([buf[0], buf[1]]) == id {
+                return parse_response(&buf[..n], name, qtype);
+            }
+        }
+    }
+}
+
+impl IpInfoProvider for DnsProvider {
+    fn name(&self) -> &str {
+        match self.service {
+            DnsService::OpenDns => "dns-opendns",
+            DnsService::Google => "dns-google",
+        }
+    }
+
+    fn get_ip_info(&self) -> Result<IpInfo> {
+        let addr = self.resolver_addr()?;
+        let mut last_err = IpLookupError::Timeout;
+        for _ in 0..self.retries {
+            match self.query_once(addr) {
+                Ok(ip) => {
+                    return Ok(IpInfo {
+                        ip: Some(ip),
+                        ..Default::default()
+                    })
+                }
+                // Retry only what may be transient.
+                Err(e @ (IpLookupError::Timeout | IpLookupError::Transport(_))) => last_err = e,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(last_err.into())
+    }
+}
+
+/// Parse `host[:port]` into a socket address, defaulting to port 53.
+pub fn parse_resolver(s: &str) -> std::result::Result<SocketAddr, String> {
+    resolve_host_port(s, 53, None)
+        .ok_or_else(|| format!("invalid DNS resolver address '{}'", s.trim()))
+}
+
+/// Check that `s` looks like `host[:port]` without resolving it, so config
+/// validation does not depend on DNS being up.
+pub fn validate_resolver(s: &str) -> std::result::Result<(), String> {
+    if is_host_port(s) {
+        Ok(())
+    } else {
+        Err(format!("invalid DNS resolver address '{}'", s.trim()))
+    }
+}
+
+/// Resolve `host[:port]` (IPv6 literals bracketed when a port is given) to
+/// its first socket address of `family` (any family when `None`). Shared
+/// with the STUN provider.
+pub(crate) fn resolve_host_port(
+    s: &str,
+    default_port: u16,
+    family: Option<IpFamily>,
+) -> Option<SocketAddr> {
+    let s = s.trim();
+    if let Ok(ip) = s.parse::<IpAddr>() {
+        return Some(SocketAddr::new(ip, default_port)).filter(|a| in_family(a, family));
+    }
+    if let Ok(addr) = s.parse::<SocketAddr>() {
+        return Some(addr).filter(|a| in_family(a, family));
+    }
+    let with_port = if s.contains(':') {
+        s.to_string()
+    } else {
+        format!("{}:{}", s, default_port)
+    };
+    family::resolve_filtered(&with_port, family)
+        .ok()
+        .and_then(|addrs| addrs.into_iter().next())
+}
+
+fn in_family(addr: &SocketAddr, family: Option<IpFamily>) -> bool {
+    family.is_none_or(|f| f.matches(&addr.ip()))
+}
+
+/// Syntactic `host[:port]` check that does not touch the network.
+pub(crate) fn is_host_port(s: &str) -> bool {
+    let s = s.trim();
+    if s.parse::<IpAddr>().is_ok() || s.parse::<SocketAddr>().is_ok() {
+        return true;
+    }
+    let (host, port) = match s.rsplit_once(':') {
+        Some((h, p)) => (h, Some(p)),
+        None => (s, None),
+    };
+    let host_ok = !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+    host_ok && port.is_none_or(|p| p.parse::<u16>().is_ok_and(|p| p > 0))
+}
+
+/// A random query ID, so an off-path attacker cannot guess it and spoof
+/// the answer.
+fn query_id() -> std::io::Result<u16> {
+    let mut b = [0u8; 2];
+    // SAFETY: `b` is a writable buffer of `b.len()` bytes.
+    let n = unsafe { libc::getrandom(b.as_mut_ptr().cast(), b.len(), 0) };
+    if n != b.len() as isize {
+        std::fs::File::open("/dev/urandom")?.read_exact(&mut b)?;
+    }
+    Ok(u16::from_ne_bytes(b))
+}
+
+/// Encode a standard recursive query for `name`/`qtype` (class IN).
+pub fn build_query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+    let mut q = Vec::with_capacity(18 + name.len());
+    q.extend_from_slice(&id.to_be_bytes());
+    q.extend_from_slice(&0x0100u16.to_be_bytes()); // RD
+    q.extend_from_slice(&1u16.to_be_bytes()); // QDCOUNT
+    q.extend_from_slice(&[0, 0, 0, 0, 0, 0]); // AN/NS/AR
+    q.extend_from_slice(&encode_question(name, qtype));
+    q
+}
+
+/// The question section for `name`/`qtype` (class IN), uncompressed.
+fn encode_question(name: &str, qtype: u16) -> Vec<u8> {
+    let mut q = Vec::with_capacity(6 + name.len());
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        q.push(label.len() as u8);
+        q.extend_from_slice(label.as_bytes());
+    }
+    q.push(0);
+    q.extend_from_slice(&qtype.to_be_bytes());
+    q.extend_from_slice(&CLASS_IN.to_be_bytes());
+    q
+}
+
+/// Extract the first address of type `qtype` from a DNS response to a
+/// query for `name`. The response must echo that single question. TXT
+/// answers must contain an IP address as text.
+pub fn parse_response(
+    msg: &[u8],
+    name: &str,
+    qtype: u16,
+) -> std::result::Result<IpAddr, IpLookupError> {
+    let malformed = || IpLookupError::Parse("malformed DNS response".to_string());
+    if msg.len() < 12 {
+        return Err(malformed());
+    }
+    let flags = u16::from_be_bytes([msg[2], msg[3]]);
+    if flags & 0x8000 == 0 {
+        return Err(malformed());
+    }
+    if flags & 0x0200 != 0 {
+        return Err(IpLookupError::Parse("truncated DNS response".to_string()));
+    }
+    let rcode = flags & 0x000F;
+    if rcode != 0 {
+        return Err(IpLookupError::Parse(format!(
+            "DNS server returned rcode {}",
+            rcode
+        )));
+    }
+    let qdcount = u16::from_be_bytes([msg[4], msg[5]]);
+    let ancount = u16::from_be_bytes([msg[6], msg[7]]);
+
+    // Names compare case-insensitively (resolvers may randomize case); the
+    // type and class must match exactly.
+    let question = encode_question(name, qtype);
+    let (qname, qtail) = question.split_at(question.len() - 4);
+    let echoed = msg.get(12..12 + question.len());
+    let matches = echoed.is_some_and(|q| {
+        q[..qname.len()].eq_ignore_ascii_case(qname) && &q[qname.len()..] == qtail
+    });
+    if qdcount != 1 || !matches {
+        return Err(IpLookupError::Parse(
+            "DNS answer does not match the query".to_string(),
+        ));
+    }
+    let mut pos = 12 + question.len();
+    for _ in 0..ancount {
+        pos = skip_name(msg, pos).ok_or_else(malformed)?;
+        let rr = msg.get(pos..pos + 10).ok_or_else(malformed)?;
+        let rtype = u16::from_be_bytes([rr[0], rr[1]]);
+        let rdlen = usize::from(u16::from_be_bytes([rr[8], rr[9]]));
+        let rdata = msg.get(pos + 10..pos + 10 + rdlen).ok_or_else(malformed)?;
+        pos += 10 + rdlen;
+
+        if rtype != qtype {
+            continue;
+        }
+        let ip = match rtype {
+            TYPE_A if rdlen == 4 => Some(IpAddr::from([rdata[0], rdata[1], rdata[2], rdata[3]])),
+            TYPE_AAAA if rdlen == 16 => {
+                let mut b = [0u8; 16];
+                b.copy_from_slice(rdata);
+                Some(IpAddr::from(b))
+            }
+            TYPE_TXT => txt_strings(rdata).find_map(|s| s.trim().parse().ok()),
+            _ => None,
+        };
+        if let Some(ip) = ip {
+            return Ok(ip);
+        }
+    }
+    Err(IpLookupError::MissingField("ip".to_string()))
+}
+
+/// Return the offset just past the (possibly compressed) name at `pos`.
+fn skip_name(msg: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            l if l & 0xC0 == 0xC0 => return Some(pos + 2),
+            l => pos += 1 + usize::from(l),
+        }
+    }
+}
+
+fn txt_strings(rdata: &[u8]) -> impl Iterator<Item = String> + '_ {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let len = usize::from(*rdata.get(pos)?);
+        let s = rdata.get(pos + 1..pos + 1 + len)?;
+        pos += 1 + len;
+        Some(String::from_utf8_lossy(s).into_owned())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv6Addr;
+
+    fn response(query: &[u8], rtype: u16, rdata: &[u8]) -> Vec<u8> {
+        let mut r = query.to_vec();
+        r[2] = 0x81;
+        r[3] = 0x80;
+        r[7] = 1; // ANCOUNT
+        r.extend_from_slice(&[0xC0, 0x0C]);
+        r.extend_from_slice(&rtype.to_be_bytes());
+        r.extend_from_slice(&CLASS_IN.to_be_bytes());
+        r.extend_from_slice(&0u32.to_be_bytes());
+        r.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        r.extend_from_slice(rdata);
+        r
+    }
+
+    #[test]
+    fn build_query_encodes_labels() {
+        let q = build_query(0x1234, "myip.opendns.com", TYPE_A);
+        assert_eq!(&q[..2], &[0x12, 0x34]);
+        assert_eq!(&q[12..17], b"\x04myip");
+        assert_eq!(&q[q.len() - 4..], &[0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn parses_a_aaaa_and_txt_answers() {
+        let q = build_query(1, OPENDNS_NAME, TYPE_A);
+        let r = response(&q, TYPE_A, &[203, 0, 113, 7]);
+        assert_eq!(
+            parse_response(&r, OPENDNS_NAME, TYPE_A).unwrap(),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+
+        let v6: Ipv6Addr = "2001:db8::7".parse().unwrap();
+        let q = build_query(1, OPENDNS_NAME, TYPE_AAAA);
+        let r = response(&q, TYPE_AAAA, &v6.octets());
+        assert_eq!(
+            parse_response(&r, OPENDNS_NAME, TYPE_AAAA).unwrap(),
+            IpAddr::V6(v6)
+        );
+
+        let q = build_query(1, GOOGLE_NAME, TYPE_TXT);
+        let r = response(&q, TYPE_TXT, b"\x0c198.51.100.9");
+        assert_eq!(
+            parse_response(&r, GOOGLE_NAME, TYPE_TXT).unwrap(),
+            "198.51.100.9".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_errors_and_empty_answers() {
+        let mut q = build_query(1, OPENDNS_NAME, TYPE_A);
+        q[2] = 0x81;
+        q[3] = 0x83; // NXDOMAIN
+        assert!(matches!(
+            parse_response(&q, OPENDNS_NAME, TYPE_A),
+            Err(IpLookupError::Parse(_))
+        ));
+
+        q[3] = 0x80;
+        assert!(matches!(
+            parse_response(&q, OPENDNS_NAME, TYPE_A),
+            Err(IpLookupError::MissingField(_))
+        ));
+        assert!(parse_response(&q[..8], OPENDNS_NAME, TYPE_A).is_err());
+    }
+
+    #[test]
+    fn rejects_answers_to_other_questions() {
+        let q = build_query(1, "myip.example.com", TYPE_A);
+        let r = response(&q, TYPE_A, &[203, 0, 113, 7]);
+        assert!(matches!(
+            parse_response(&r, OPENDNS_NAME, TYPE_A),
+            Err(IpLookupError::Parse(_))
+        ));
+
+        let q = build_query(1, OPENDNS_NAME, TYPE_AAAA);
+        let r = response(&q, TYPE_A, &[203, 0, 113, 7]);
+        assert!(matches!(
+            parse_response(&r, OPENDNS_NAME, TYPE_A),
+            Err(IpLookupError::Parse(_))
+        ));
+
+        // 0x20 case randomization still matches.
+        let q = build_query(1, "MyIP.OpenDNS.com", TYPE_A);
+        let r = response(&q, TYPE_A, &[203, 0, 113, 7]);
+        assert!(parse_response(&r, OPENDNS_NAME, TYPE_A).is_ok());
+    }
+
+    #[test]
+    fn parse_resolver_defaults_port() {
+        assert_eq!(
+            parse_resolver("208.67.222.222").unwrap(),
+            "208.67.222.222:53".parse().unwrap()
+        );
+        assert_eq!(
+            parse_resolver("127.0.0.1:5353").unwrap(),
+            "127.0.0.1:5353".parse().unwrap()
+        );
+        assert_eq!(
+            parse_resolver("2620:119:35::35").unwrap(),
+            "[2620:119:35::35]:53".parse().unwrap()
+        );
+        assert!(parse_resolver("not a resolver").is_err());
+    }
+
+    #[test]
+    fn validate_resolver_is_syntactic() {
+        assert!(validate_resolver("resolver1.opendns.com").is_ok());
+        assert!(validate_resolver("ns1.google.com:53").is_ok());
+        assert!(validate_resolver("[2620:119:35::35]:53").is_ok());
+        assert!(validate_resolver("https://example.com").is_err());
+        assert!(validate_resolver("host:0").is_err());
+    }
+}
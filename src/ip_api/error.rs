@@ -0,0 +1,111 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// Why a public-IP lookup failed.
+///
+/// Lookup functions still return `anyhow::Result` for compatibility; the
+/// error inside is an `IpLookupError` whenever the failure comes from the
+/// HTTP layer (also through the failover and quorum strategies, see
+/// `wrap_all`), so callers can `downcast_ref::<IpLookupError>()` and react to
+/// the kind of failure (e.g. back off on rate limits instead of alerting).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpLookupError {
+    /// The provider answered 429, or our own request budget is exhausted.
+    /// `retry_after` is when the next request may be sent, if known.
+    RateLimited { retry_after: Option<Duration> },
+    /// The provider answered with a non-success HTTP status.
+    Http { status: u16 },
+    /// The response body exceeded the configured size limit (in bytes).
+    TooLarge { limit: usize },
+    /// The response body could not be parsed (original error string).
+    Parse(String),
+    /// The response parsed but lacked a required field (field name).
+    MissingField(String),
+    /// Connecting, sending or reading failed (original error string).
+    Transport(String),
+    /// The request did not complete within the lookup timeout.
+    Timeout,
+    /// TLS checks beyond the usual chain validation failed, e.g. the server
+    /// key does not match any configured SPKI pin.
+    Tls(String),
+}
+
+impl IpLookupError {
+    /// Classify a reqwest error as `Timeout` or `Transport`.
+    pub(crate) fn from_reqwest(e: &reqwest::Error) -> Self {
+        if e.is_timeout() {
+            IpLookupError::Timeout
+        } else {
+            IpLookupError::Transport(e.to_string())
+        }
+    }
+
+    /// The error for a lookup where every provider in `errors` failed,
+    /// reported as `msg`. The combined kind (see `combine`) stays the root
+    /// cause, so callers can still tell rate limiting apart.
+    pub(crate) fn wrap_all<'a>(
+        errors: impl IntoIterator<Item = &'a anyhow::Error>,
+        msg: String,
+    ) -> anyhow::Error {
+        match Self::combine(errors) {
+            Some(kind) => anyhow::Error::new(kind).context(msg),
+            None => anyhow::anyhow!(msg),
+        }
+    }
+
+    /// The typed failure of a lookup that asked several providers:
+    /// `RateLimited` (with the soonest known retry) when every provider was
+    /// rate limited, otherwise the last other typed failure. None when no
+    /// provider failed with an `IpLookupError`.
+    fn combine<'a>(errors: impl IntoIterator<Item = &'a anyhow::Error>) -> Option<Self> {
+        let mut all_rate_limited = true;
+        let mut any = false;
+        let mut retry: Option<Duration> = None;
+        let mut last = None;
+        for e in errors {
+            any = true;
+            match e.downcast_ref::<IpLookupError>() {
+                Some(IpLookupError::RateLimited { retry_after }) => {
+                    retry = match (retry, *retry_after) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    };
+                }
+                other => {
+                    all_rate_limited = false;
+                    if let Some(kind) = other {
+                        last = Some(kind.clone());
+                    }
+                }
+            }
+        }
+        if any && all_rate_limited {
+            Some(IpLookupError::RateLimited { retry_after: retry })
+        } else {
+            last
+        }
+    }
+}
+
+impl fmt::Display for IpLookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpLookupError::RateLimited {
+                retry_after: Some(d),
+            } => write!(f, "rate limited (retry in {}s)", d.as_secs().max(1)),
+            IpLookupError::RateLimited { retry_after: None } => write!(f, "rate limited"),
+            IpLookupError::Http { status } => write!(f, "non-success status: {}", status),
+            IpLookupError::TooLarge { limit } => {
+                write!(f, "response too large (>{} bytes)", limit)
+            }
+            IpLookupError::Parse(s) => write!(f, "failed to parse response: {}", s),
+            IpLookupError::MissingField(name) => write!(f, "{} field missing in response", name),
+            IpLookupError::Transport(s) => write!(f, "http request failed: {}", s),
+            IpLookupError::Timeout => write!(f, "request timed out"),
+            IpLookupError::Tls(s) => write!(f, "TLS verification failed: {}", s),
+        }
+    }
+}
+
+impl Error for IpLookupError {}
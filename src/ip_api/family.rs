@@ -0,0 +1,118 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+use super::error::IpLookupError;
+
+/// An IP address family a lookup can be forced onto.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    pub fn of(ip: IpAddr) -> Self {
+        if ip.is_ipv4() {
+            IpFamily::V4
+        } else {
+            IpFamily::V6
+        }
+    }
+
+    pub fn matches(&self, ip: &IpAddr) -> bool {
+        match self {
+            IpFamily::V4 => ip.is_ipv4(),
+            IpFamily::V6 => ip.is_ipv6(),
+        }
+    }
+
+    /// The wildcard address of this family, for binding sockets.
+    pub fn unspecified(&self) -> IpAddr {
+        match self {
+            IpFamily::V4 => Ipv4Addr::UNSPECIFIED.into(),
+            IpFamily::V6 => Ipv6Addr::UNSPECIFIED.into(),
+        }
+    }
+}
+
+impl fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IpFamily::V4 => "IPv4",
+            IpFamily::V6 => "IPv6",
+        })
+    }
+}
+
+/// Resolve `host:port` and keep only the addresses of `family` (all when
+/// `None`).
+pub(crate) fn resolve_filtered(
+    host_port: &str,
+    family: Option<IpFamily>,
+) -> std::io::Result<Vec<SocketAddr>> {
+    Ok(host_port
+        .to_socket_addrs()?
+        .filter(|a| family.is_none_or(|f| f.matches(&a.ip())))
+        .collect())
+}
+
+/// reqwest resolver that only returns addresses of one family, so the
+/// connection cannot silently fall back to the other one.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FamilyResolver(pub IpFamily);
+
+impl Resolve for FamilyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let family = self.0;
+        let host = format!("{}:0", name.as_str());
+        Box::pin(async move {
+            let addrs = resolve_filtered(&host, Some(family))?;
+            if addrs.is_empty() {
+                return Err(
+                    format!("{} has no {} address", host.trim_end_matches(":0"), family).into(),
+                );
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Reject URLs whose host is an address literal of the other family; those
+/// bypass the resolver.
+pub(crate) fn check_url(url: &str, family: IpFamily) -> Result<(), IpLookupError> {
+    let host = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.trim_matches(['[', ']']).to_string()));
+    match host.and_then(|h| h.parse::<IpAddr>().ok()) {
+        Some(ip) if !family.matches(&ip) => Err(IpLookupError::Transport(format!(
+            "{} is not an {} address",
+            ip, family
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_url_rejects_other_family_literals() {
+        assert!(check_url("http://127.0.0.1:8080/json", IpFamily::V4).is_ok());
+        assert!(check_url("http://127.0.0.1:8080/json", IpFamily::V6).is_err());
+        assert!(check_url("http://[::1]:8080/json", IpFamily::V6).is_ok());
+        assert!(check_url("http://[::1]:8080/json", IpFamily::V4).is_err());
+        assert!(check_url("https://ipinfo.io/json", IpFamily::V6).is_ok());
+    }
+
+    #[test]
+    fn resolve_filtered_keeps_one_family() {
+        let v4 = resolve_filtered("127.0.0.1:53", Some(IpFamily::V4)).unwrap();
+        assert_eq!(v4.len(), 1);
+        assert!(resolve_filtered("127.0.0.1:53", Some(IpFamily::V6))
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+use serde_json::Value;
+use std::fmt;
+use std::net::IpAddr;
+
+/// Everything we learn about the current public IP from a lookup.
+///
+/// Fields are optional because providers report different subsets; ip-api.com
+/// fills all of them (see `examples/ip_api.json`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IpInfo {
+    /// Public IP address as seen by the provider (ip-api `query`)
+    pub ip: Option<IpAddr>,
+    pub isp: Option<String>,
+    pub org: Option<String>,
+    /// Autonomous system number, e.g. 42708 for "AS42708 GleSYS AB"
+    pub asn: Option<u32>,
+    /// Name of the autonomous system when reported separately from the ISP
+    pub as_name: Option<String>,
+    pub country: Option<String>,
+    /// ISO 3166-1 alpha-2 country code, e.g. "SE"
+    pub country_code: Option<String>,
+    pub city: Option<String>,
+    pub timezone: Option<String>,
+}
+
+impl IpInfo {
+    /// Map an ip-api.com JSON response.
+    pub fn from_ip_api(v: &Value) -> Self {
+        let (asn, as_name) = split_as_field(str_field(v, "as"));
+        IpInfo {
+            ip: ip_field(v, "query"),
+            isp: owned(str_field(v, "isp")),
+            org: owned(str_field(v, "org")),
+            asn,
+            as_name,
+            country: owned(str_field(v, "country")),
+            country_code: owned(str_field(v, "countryCode")),
+            city: owned(str_field(v, "city")),
+            timezone: owned(str_field(v, "timezone")),
+        }
+    }
+
+    /// Map an ipinfo.io JSON response. ipinfo has no separate ISP name, so
+    /// the organisation from `org` ("AS42708 GleSYS AB") doubles as the ISP.
+    pub fn from_ipinfo(v: &Value) -> Self {
+        let (asn, as_name) = split_as_field(str_field(v, "org"));
+        IpInfo {
+            ip: ip_field(v, "ip"),
+            isp: as_name.clone(),
+            org: as_name.clone(),
+            asn,
+            as_name,
+            country: None,
+            country_code: owned(str_field(v, "country")),
+            city: owned(str_field(v, "city")),
+            timezone: owned(str_field(v, "timezone")),
+        }
+    }
+
+    /// Map an ifconfig.co JSON response (`asn_org` is used as the ISP).
+    pub fn from_ifconfig_co(v: &Value) -> Self {
+        let (asn, _) = split_as_field(str_field(v, "asn"));
+        IpInfo {
+            ip: ip_field(v, "ip"),
+            isp: owned(str_field(v, "asn_org")),
+            org: owned(str_field(v, "asn_org")),
+            asn,
+            as_name: owned(str_field(v, "asn_org")),
+            country: owned(str_field(v, "country")),
+            country_code: owned(str_field(v, "country_iso")),
+            city: owned(str_field(v, "city")),
+            timezone: owned(str_field(v, "time_zone")),
+        }
+    }
+
+    /// Map a custom endpoint: ip-api field names are used for everything
+    /// except the ISP, which is read from `isp_field` (a dotted path).
+    pub fn from_custom(v: &Value, isp_field: &str) -> Self {
+        IpInfo {
+            isp: owned(str_field(v, isp_field)),
+            ..IpInfo::from_ip_api(v)
+        }
+    }
+}
+
+/// Lets lookups that only know the ISP (including test closures) feed
+/// `perform_check` directly.
+impl From<String> for IpInfo {
+    fn from(isp: String) -> Self {
+        IpInfo {
+            isp: Some(isp),
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Display for IpInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ISP: {}", self.isp.as_deref().unwrap_or("unknown"))?;
+        if let Some(ip) = self.ip {
+            write!(f, ", IP: {}", ip)?;
+        }
+        if let Some(asn) = self.asn {
+            write!(f, ", AS{}", asn)?;
+        }
+        if let Some(org) = &self.org {
+            write!(f, ", org: {}", org)?;
+        }
+        if let Some(cc) = &self.country_code {
+            write!(f, ", country: {}", cc)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolve a dotted path (e.g. `data.isp`) to a string value in a JSON document.
+pub(crate) fn str_field<'a>(v: &'a Value, path: &str) -> Option<&'a str> {
+    path.split('.')
+        .try_fold(v, |cur, key| cur.get(key))
+        .and_then(|leaf| leaf.as_str())
+}
+
+fn ip_field(v: &Value, path: &str) -> Option<IpAddr> {
+    str_field(v, path).and_then(|s| s.trim().parse().ok())
+}
+
+fn owned(s: Option<&str>) -> Option<String> {
+    s.map(|s| s.to_string())
+}
+
+/// Split an "AS<number> <name>" value into its number and name. Values
+/// without an `AS` prefix are treated as a plain name.
+fn split_as_field(raw: Option<&str>) -> (Option<u32>, Option<String>) {
+    let Some(raw) = raw else {
+        return (None, None);
+    };
+    let (head, rest) = match raw.split_once(' ') {
+        Some((h, r)) => (h, Some(r.trim())),
+        None => (raw, None),
+    };
+    match parse_asn(head) {
+        Some(n) => (Some(n), rest.filter(|r| !r.is_empty()).map(str::to_string)),
+        None => (None, Some(raw.to_string())),
+    }
+}
+
+/// Parse "AS42708", "as42708" or "42708" into 42708.
+pub fn parse_asn(s: &str) -> Option<u32> {
+    let s = s.trim();
+    let digits = match s.get(..2) {
+        Some(prefix) if s.len() > 2 && prefix.eq_ignore_ascii_case("AS") => &s[2..],
+        _ => s,
+    };
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_ip_api_maps_example_response() {
+        let raw = std::fs::read_to_string("examples/ip_api.json").expect("example json");
+        let v: Value = serde_json::from_str(&raw).expect("parse");
+        let info = IpInfo::from_ip_api(&v);
+        assert_eq!(info.ip, Some("46.246.8.48".parse().unwrap()));
+        assert_eq!(info.isp.as_deref(), Some("GleSYS AB"));
+        assert_eq!(info.org.as_deref(), Some("PRIVATE INTERNET ACCESS, Inc"));
+        assert_eq!(info.asn, Some(42708));
+        assert_eq!(info.as_name.as_deref(), Some("GleSYS AB"));
+        assert_eq!(info.country.as_deref(), Some("Sweden"));
+        assert_eq!(info.country_code.as_deref(), Some("SE"));
+        assert_eq!(info.city.as_deref(), Some("Stockholm"));
+        assert_eq!(info.timezone.as_deref(), Some("Europe/Stockholm"));
+    }
+
+    #[test]
+    fn str_field_follows_dotted_path() {
+        let v: Value = serde_json::json!({ "data": { "isp": "Nested ISP" }, "isp": 3 });
+        assert_eq!(str_field(&v, "data.isp"), Some("Nested ISP"));
+        // non-string leaves are treated as missing
+        assert_eq!(str_field(&v, "isp"), None);
+        assert_eq!(str_field(&v, "data.missing"), None);
+    }
+
+    #[test]
+    fn split_as_field_variants() {
+        assert_eq!(
+            split_as_field(Some("AS42708 GleSYS AB")),
+            (Some(42708), Some("GleSYS AB".to_string()))
+        );
+        assert_eq!(split_as_field(Some("AS42708")), (Some(42708), None));
+        assert_eq!(
+            split_as_field(Some("ASTRA Networks")),
+            (None, Some("ASTRA Networks".to_string()))
+        );
+        assert_eq!(split_as_field(None), (None, None));
+    }
+
+    #[test]
+    fn parse_asn_accepts_prefix_and_bare_numbers() {
+        assert_eq!(parse_asn("AS42708"), Some(42708));
+        assert_eq!(parse_asn("as42708"), Some(42708));
+        assert_eq!(parse_asn(" 42708 "), Some(42708));
+        assert_eq!(parse_asn("AS"), None);
+        assert_eq!(parse_asn("GleSYS"), None);
+        assert_eq!(parse_asn("Åland"), None);
+    }
+
+    #[test]
+    fn display_includes_known_fields() {
+        let info = IpInfo {
+            ip: Some("203.0.113.7".parse().unwrap()),
+            isp: Some("Example ISP".into()),
+            asn: Some(64500),
+            country_code: Some("SE".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            info.to_string(),
+            "ISP: Example ISP, IP: 203.0.113.7, AS64500, country: SE"
+        );
+    }
+}
@@ -1,6 +1,39 @@
 /// ip-api client and helpers.
 /// The implementation lives in `client.rs` and the module re-exports the
 /// public functions so callers continue to use `crate::ip_api::get_isp`.
+/// Pluggable lookup services live in `provider.rs` and the `IpInfo` record
+/// they return in `info.rs`.
+/// Typed lookup failures live in `error.rs`.
+/// `consensus.rs` and `pool.rs` combine several providers.
+/// `dns.rs` and `stun.rs` discover the public IP over plain DNS or STUN
+/// instead of HTTP, and `asn_db.rs` resolves ASN/ISP from a local database.
+/// `family.rs` forces a lookup over IPv4 or IPv6 only.
+pub mod asn_db;
 pub mod client;
+pub mod consensus;
+pub mod dns;
+pub mod error;
+pub mod family;
+pub mod info;
+pub mod pool;
+pub mod provider;
+pub mod ratelimit;
+pub mod stun;
+pub mod tls;
 
-pub use client::{get_isp, get_isp_with_client_and_url};
+pub use asn_db::{AsnDatabase, AsnDbFile, AsnDbProvider, AsnRecord};
+pub use client::{
+    get_ip_info, get_ip_info_with_client_and_url, get_isp, get_isp_with_client_and_url,
+    HttpSettings,
+};
+pub use consensus::QuorumProvider;
+pub use dns::{DnsProvider, DnsService};
+pub use error::IpLookupError;
+pub use family::IpFamily;
+pub use info::IpInfo;
+pub use pool::{ProviderHealth, ProviderPool};
+pub use provider::{
+    provider_for_family, provider_from_config, HttpProvider, IpInfoProvider, ProviderKind,
+};
+pub use ratelimit::RateLimiter;
+pub use stun::StunProvider;
@@ -0,0 +1,301 @@
+use anyhow::Result;
+use log::{debug, warn};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::error::IpLookupError;
+use super::info::IpInfo;
+use super::provider::IpInfoProvider;
+
+/// Number of recent lookups remembered per provider.
+const HEALTH_WINDOW: usize = 10;
+
+/// Age after which a lookup no longer counts towards a provider's health,
+/// so a demoted provider becomes primary again once its failures expire.
+pub const HEALTH_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// Average latency above which a provider's score starts to drop.
+const SLOW_LATENCY: Duration = Duration::from_secs(1);
+
+/// Recent outcomes of one provider in a `ProviderPool`.
+#[derive(Debug, Clone, Default)]
+pub struct ProviderHealth {
+    /// Most recent results, newest last: when, and `true` for success.
+    outcomes: VecDeque<(Instant, bool)>,
+    /// Latencies of the recent successful lookups.
+    latencies: VecDeque<(Instant, Duration)>,
+}
+
+impl ProviderHealth {
+    fn record(&mut self, ok: bool, latency: Duration) {
+        let now = Instant::now();
+        self.outcomes.push_back((now, ok));
+        if self.outcomes.len() > HEALTH_WINDOW {
+            self.outcomes.pop_front();
+        }
+        if ok {
+            self.latencies.push_back((now, latency));
+            if self.latencies.len() > HEALTH_WINDOW {
+                self.latencies.pop_front();
+            }
+        }
+    }
+
+    /// Forget lookups older than `HEALTH_MAX_AGE` at `now`.
+    fn expire(&mut self, now: Instant) {
+        let fresh = |at: &Instant| now.saturating_duration_since(*at) <= HEALTH_MAX_AGE;
+        self.outcomes.retain(|(at, _)| fresh(at));
+        self.latencies.retain(|(at, _)| fresh(at));
+    }
+
+    /// Share of recent lookups that succeeded. Providers that have not been
+    /// tried yet count as fully healthy.
+    pub fn success_rate(&self) -> f64 {
+        if self.outcomes.is_empty() {
+            return 1.0;
+        }
+        let ok = self.outcomes.iter().filter(|(_, ok)| *ok).count();
+        ok as f64 / self.outcomes.len() as f64
+    }
+
+    /// Average latency of recent successful lookups, if any.
+    pub fn avg_latency(&self) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        Some(self.latencies.iter().map(|(_, l)| *l).sum::<Duration>() / self.latencies.len() as u32)
+    }
+
+    /// Success rate, scaled down for providers slower than `SLOW_LATENCY`.
+    pub fn score(&self) -> f64 {
+        let rate = self.success_rate();
+        match self.avg_latency() {
+            Some(l) if l > SLOW_LATENCY => rate * SLOW_LATENCY.as_secs_f64() / l.as_secs_f64(),
+            _ => rate,
+        }
+    }
+}
+
+/// Ordered failover across several providers.
+///
+/// Each check tries providers from the healthiest down and returns the first
+/// successful answer. Providers with equal scores keep their configured
+/// order, so the first entry stays primary while it is healthy, and takes
+/// over again once its failures are older than `HEALTH_MAX_AGE`.
+pub struct ProviderPool {
+    providers: Vec<Box<dyn IpInfoProvider>>,
+    health: RefCell<Vec<ProviderHealth>>,
+}
+
+impl ProviderPool {
+    pub fn new(providers: Vec<Box<dyn IpInfoProvider>>) -> Self {
+        let health = RefCell::new(vec![ProviderHealth::default(); providers.len()]);
+        ProviderPool { providers, health }
+    }
+
+    /// Provider indices in the order they will be tried on the next check.
+    pub fn order(&self) -> Vec<usize> {
+        self.order_at(Instant::now())
+    }
+
+    fn order_at(&self, now: Instant) -> Vec<usize> {
+        let mut health = self.health.borrow_mut();
+        health.iter_mut().for_each(|h| h.expire(now));
+        let mut order: Vec<usize> = (0..self.providers.len()).collect();
+        // sort_by is stable, so ties keep the configured order
+        order.sort_by(|a, b| health[*b].score().total_cmp(&health[*a].score()));
+        order
+    }
+
+    /// Snapshot of each provider's health, in configured order.
+    pub fn health(&self) -> Vec<(String, ProviderHealth)> {
+        let health = self.health.borrow();
+        self.providers
+            .iter()
+            .zip(health.iter())
+            .map(|(p, h)| (p.name().to_string(), h.clone()))
+            .collect()
+    }
+
+    fn log_scores(&self) {
+        let health = self.health.borrow();
+        let scores: Vec<String> = self
+            .providers
+            .iter()
+            .zip(health.iter())
+            .map(|(p, h)| {
+                let latency = h
+                    .avg_latency()
+                    .map(|l| format!("{}ms", l.as_millis()))
+                    .unwrap_or_else(|| "-".to_string());
+                format!(
+                    "{} success {:.0}% latency {} score {:.2}",
+                    p.name(),
+                    h.success_rate() * 100.0,
+                    latency,
+                    h.score()
+                )
+            })
+            .collect();
+        debug!("provider health: {}", scores.join("; "));
+    }
+}
+
+impl IpInfoProvider for ProviderPool {
+    fn name(&self) -> &str {
+        "failover"
+    }
+
+    fn get_ip_info(&self) -> Result<IpInfo> {
+        let mut errors = Vec::new();
+        for idx in self.order() {
+            let p = &self.providers[idx];
+            let start = Instant::now();
+            let res = p.get_ip_info();
+            self.health.borrow_mut()[idx].record(res.is_ok(), start.elapsed());
+            match res {
+                Ok(info) => {
+                    if !errors.is_empty() {
+                        warn!("Lookup succeeded via fallback provider {}", p.name());
+                    }
+                    self.log_scores();
+                    return Ok(info);
+                }
+                Err(e) => {
+                    warn!("Provider {} failed: {}; trying next", p.name(), e);
+                    errors.push((p.name(), e));
+                }
+            }
+        }
+        self.log_scores();
+        let summary = errors
+            .iter()
+            .map(|(name, e)| format!("{}: {}", name, e))
+            .collect::<Vec<_>>()
+            .join("; ");
+        Err(IpLookupError::wrap_all(
+            errors.iter().map(|(_, e)| e),
+            format!("all providers failed: {}", summary),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[derive(Default)]
+    struct Script {
+        ok: Cell<bool>,
+        calls: Cell<usize>,
+    }
+
+    struct Scripted {
+        name: &'static str,
+        script: Rc<Script>,
+    }
+
+    impl IpInfoProvider for Scripted {
+        fn name(&self) -> &str {
+            self.name
+        }
+        fn get_ip_info(&self) -> Result<IpInfo> {
+            self.script.calls.set(self.script.calls.get() + 1);
+            if self.script.ok.get() {
+                Ok(IpInfo::from(self.name.to_string()))
+            } else {
+                Err(anyhow::anyhow!("HTTP 429"))
+            }
+        }
+    }
+
+    fn scripted(name: &'static str, ok: bool) -> (Box<dyn IpInfoProvider>, Rc<Script>) {
+        let script = Rc::new(Script::default());
+        script.ok.set(ok);
+        let p = Scripted {
+            name,
+            script: script.clone(),
+        };
+        (Box::new(p), script)
+    }
+
+    #[test]
+    fn falls_back_to_next_provider() {
+        let (a, _) = scripted("a", false);
+        let (b, _) = scripted("b", true);
+        let pool = ProviderPool::new(vec![a, b]);
+        assert_eq!(pool.get_isp().unwrap(), "b");
+    }
+
+    #[test]
+    fn prefers_healthy_provider_on_later_checks() {
+        let (a, a_script) = scripted("a", false);
+        let (b, _) = scripted("b", true);
+        let pool = ProviderPool::new(vec![a, b]);
+
+        pool.get_ip_info().unwrap();
+        assert_eq!(pool.order(), vec![1, 0]);
+
+        // The failing primary is no longer tried first while its failure
+        // is recent...
+        a_script.ok.set(true);
+        assert_eq!(pool.get_isp().unwrap(), "b");
+        assert_eq!(a_script.calls.get(), 1);
+
+        // ...and healthy providers keep their configured order.
+        let health = pool.health();
+        assert_eq!(health[0].0, "a");
+        assert_eq!(health[0].1.success_rate(), 0.0);
+        assert_eq!(health[1].1.success_rate(), 1.0);
+    }
+
+    #[test]
+    fn recovered_primary_takes_over_once_failures_expire() {
+        let (a, a_script) = scripted("a", false);
+        let (b, _) = scripted("b", true);
+        let pool = ProviderPool::new(vec![a, b]);
+        pool.get_ip_info().unwrap();
+        a_script.ok.set(true);
+        assert_eq!(pool.order(), vec![1, 0]);
+
+        let later = Instant::now() + HEALTH_MAX_AGE + Duration::from_secs(1);
+        assert_eq!(pool.order_at(later), vec![0, 1]);
+        assert_eq!(pool.get_isp().unwrap(), "a");
+        assert_eq!(a_script.calls.get(), 2);
+    }
+
+    #[test]
+    fn all_failing_reports_every_error() {
+        let (a, _) = scripted("a", false);
+        let (b, _) = scripted("b", false);
+        let pool = ProviderPool::new(vec![a, b]);
+        let err = pool.get_ip_info().unwrap_err().to_string();
+        assert!(err.contains("a: HTTP 429") && err.contains("b: HTTP 429"));
+    }
+
+    #[test]
+    fn success_rate_uses_recent_window() {
+        let mut h = ProviderHealth::default();
+        for _ in 0..HEALTH_WINDOW {
+            h.record(false, Duration::ZERO);
+        }
+        for _ in 0..HEALTH_WINDOW {
+            h.record(true, Duration::from_millis(10));
+        }
+        assert_eq!(h.success_rate(), 1.0);
+        assert_eq!(h.avg_latency(), Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn slow_providers_score_lower() {
+        let mut fast = ProviderHealth::default();
+        fast.record(true, Duration::from_millis(100));
+        let mut slow = ProviderHealth::default();
+        slow.record(true, Duration::from_secs(4));
+        assert_eq!(fast.score(), 1.0);
+        assert!((slow.score() - 0.25).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,455 @@
+use anyhow::Result;
+use reqwest::blocking::Client;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::asn_db::{AsnDbFile, AsnDbProvider};
+use super::client::{self, HttpSettings};
+use super::consensus::{self, QuorumProvider};
+use super::dns::{self, DnsProvider, DnsService};
+use super::error::IpLookupError;
+use super::family::IpFamily;
+use super::info::IpInfo;
+use super::pool::ProviderPool;
+use super::ratelimit::{RateLimiter, IP_API_FREE_TIER_PER_MINUTE};
+use super::stun::StunProvider;
+use crate::config::EffectiveConfig;
+use crate::matching::MatchRules;
+
+/// Names accepted for the `ip_provider` config value / `--ip-provider` flag.
+pub const PROVIDER_NAMES: [&str; 7] = [
+    "ip-api",
+    "ipinfo",
+    "ifconfig.co",
+    "custom",
+    "dns-opendns",
+    "dns-google",
+    "stun",
+];
+
+/// A source of public-IP information (address, ISP, ASN, country...).
+///
+/// Implementations must be cheap to call repeatedly; `perform_check` invokes
+/// the configured provider once per check iteration.
+pub trait IpInfoProvider {
+    /// Short human-readable name used in logs.
+    fn name(&self) -> &str;
+
+    /// Look up everything the provider reports about the current public IP.
+    fn get_ip_info(&self) -> Result<IpInfo>;
+
+    /// Look up only the ISP name for the current public IP.
+    fn get_isp(&self) -> Result<String> {
+        self.get_ip_info()?
+            .isp
+            .ok_or_else(|| IpLookupError::MissingField("isp".to_string()).into())
+    }
+}
+
+/// The built-in lookup services.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    /// ip-api.com (`isp` field). The historical default.
+    IpApi,
+    /// ipinfo.io (`org` field, e.g. "AS42708 GleSYS AB").
+    IpInfo,
+    /// ifconfig.co (`asn_org` field).
+    IfconfigCo,
+    /// Any JSON endpoint; the ISP is read from a configurable field.
+    Custom,
+    /// `myip.opendns.com` over DNS (IP only, no ISP).
+    DnsOpenDns,
+    /// `o-o.myaddr.l.google.com` TXT over DNS (IP only, no ISP).
+    DnsGoogle,
+    /// STUN Binding request to `stun_servers` (IP only, no ISP).
+    Stun,
+}
+
+impl ProviderKind {
+    /// Parse a provider name as used in config/CLI. Returns None if unknown.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "ip-api" => Some(ProviderKind::IpApi),
+            "ipinfo" => Some(ProviderKind::IpInfo),
+            "ifconfig.co" => Some(ProviderKind::IfconfigCo),
+            "custom" => Some(ProviderKind::Custom),
+            "dns-opendns" => Some(ProviderKind::DnsOpenDns),
+            "dns-google" => Some(ProviderKind::DnsGoogle),
+            "stun" => Some(ProviderKind::Stun),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProviderKind::IpApi => "ip-api",
+            ProviderKind::IpInfo => "ipinfo",
+            ProviderKind::IfconfigCo => "ifconfig.co",
+            ProviderKind::Custom => "custom",
+            ProviderKind::DnsOpenDns => "dns-opendns",
+            ProviderKind::DnsGoogle => "dns-google",
+            ProviderKind::Stun => "stun",
+        }
+    }
+
+    /// True for the DNS kinds, whose endpoint is a resolver `host[:port]`
+    /// rather than an HTTP URL.
+    pub fn is_dns(&self) -> bool {
+        matches!(self, ProviderKind::DnsOpenDns | ProviderKind::DnsGoogle)
+    }
+
+    /// True for the kinds served by `HttpProvider`.
+    pub fn is_http(&self) -> bool {
+        !(self.is_dns() || *self == ProviderKind::Stun)
+    }
+
+    /// Default endpoint for the provider (the resolver address for DNS
+    /// kinds). `Custom` has none and requires a URL; `Stun` has none and
+    /// uses `stun_servers`.
+    pub fn default_url(&self) -> Option<String> {
+        match self {
+            ProviderKind::IpApi => Some(client::default_ip_api_url()),
+            ProviderKind::IpInfo => Some("https://ipinfo.io/json".to_string()),
+            ProviderKind::IfconfigCo => Some("https://ifconfig.co/json".to_string()),
+            ProviderKind::Custom => None,
+            ProviderKind::DnsOpenDns => Some(dns::OPENDNS_RESOLVER.to_string()),
+            ProviderKind::DnsGoogle => Some(dns::GOOGLE_RESOLVER.to_string()),
+            ProviderKind::Stun => None,
+        }
+    }
+
+    /// Requests per minute allowed when no `rate_limit_per_minute` is
+    /// configured. Only ip-api publishes (and enforces) a per-minute budget.
+    pub fn default_rate_limit(&self) -> Option<u32> {
+        match self {
+            ProviderKind::IpApi => Some(IP_API_FREE_TIER_PER_MINUTE),
+            _ => None,
+        }
+    }
+}
+
+/// HTTP/JSON provider used for all built-in services.
+///
+/// The retry and response-size policies are shared with
+/// `get_isp_with_client_and_url`; only the field mapping differs per kind.
+pub struct HttpProvider {
+    kind: ProviderKind,
+    url: String,
+    isp_field: String,
+    client: Client,
+    settings: HttpSettings,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl HttpProvider {
+    /// Create a provider with an injected client. `url` of None selects the
+    /// kind's default endpoint; `isp_field` is only consulted for `Custom`.
+    pub fn new(
+        kind: ProviderKind,
+        url: Option<&str>,
+        isp_field: Option<&str>,
+        client: Client,
+        retries: usize,
+    ) -> Result<Self> {
+        if !kind.is_http() {
+            anyhow::bail!("provider '{}' is not an HTTP provider", kind.as_str());
+        }
+        let url = match url {
+            Some(u) => u.to_string(),
+            None => kind
+                .default_url()
+                .ok_or_else(|| anyhow::anyhow!("provider '{}' requires a URL", kind.as_str()))?,
+        };
+        Ok(HttpProvider {
+            kind,
+            url,
+            isp_field: isp_field.unwrap_or("isp").to_string(),
+            client,
+            settings: HttpSettings {
+                retries,
+                ..HttpSettings::default()
+            },
+            limiter: None,
+        })
+    }
+
+    /// Use `settings` (retries, size cap, SPKI pins) for every request. The
+    /// client should come from `settings.build_client()` so pins can be
+    /// checked.
+    pub fn with_settings(mut self, settings: HttpSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Limit this provider to `per_minute` requests per minute with a token
+    /// bucket. `None` or `0` disables the limit.
+    pub fn with_rate_limit(mut self, per_minute: Option<u32>) -> Self {
+        self.limiter = per_minute
+            .filter(|n| *n > 0)
+            .map(|n| Arc::new(RateLimiter::per_minute(n)));
+        self
+    }
+
+    pub fn kind(&self) -> ProviderKind {
+        self.kind
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn map_response(&self, v: &Value) -> IpInfo {
+        match self.kind {
+            ProviderKind::IpApi => IpInfo::from_ip_api(v),
+            ProviderKind::IpInfo => IpInfo::from_ipinfo(v),
+            ProviderKind::IfconfigCo => IpInfo::from_ifconfig_co(v),
+            ProviderKind::Custom => IpInfo::from_custom(v, &self.isp_field),
+            ProviderKind::DnsOpenDns | ProviderKind::DnsGoogle | ProviderKind::Stun => {
+                unreachable!("non-HTTP kinds are rejected by HttpProvider::new")
+            }
+        }
+    }
+}
+
+impl IpInfoProvider for HttpProvider {
+    fn name(&self) -> &str {
+        self.kind.as_str()
+    }
+
+    fn get_ip_info(&self) -> Result<IpInfo> {
+        let buf = client::fetch_body_with_settings(
+            &self.client,
+            &self.url,
+            &self.settings,
+            self.limiter.as_deref(),
+        )?;
+        let v: Value =
+            serde_json::from_slice(&buf).map_err(|e| IpLookupError::Parse(e.to_string()))?;
+        Ok(self.map_response(&v))
+    }
+}
+
+/// Names accepted for the `provider_strategy` config value.
+pub const PROVIDER_STRATEGIES: [&str; 3] = ["single", "failover", "quorum"];
+
+/// Parse an `ip_providers` entry: a provider name, optionally followed by
+/// `=<url>` (e.g. `ipinfo` or `custom=https://lookup.example/json`). For DNS
+/// kinds the value is the resolver (e.g. `dns-opendns=208.67.220.220`), for
+/// `stun` a single STUN server used instead of `stun_servers`.
+pub fn parse_provider_spec(
+    spec: &str,
+) -> std::result::Result<(ProviderKind, Option<String>), String> {
+    let (name, url) = match spec.trim().split_once('=') {
+        Some((n, u)) => (n.trim(), Some(u.trim().to_string())),
+        None => (spec.trim(), None),
+    };
+    let kind = ProviderKind::parse(name).ok_or_else(|| {
+        format!(
+            "unknown provider '{}' (expected one of: {})",
+            name,
+            PROVIDER_NAMES.join(", ")
+        )
+    })?;
+    if kind == ProviderKind::Custom && url.is_none() {
+        return Err("custom provider entries need a URL (custom=<url>)".to_string());
+    }
+    Ok((kind, url))
+}
+
+/// Build the provider selected by the effective configuration.
+///
+/// With the `failover` strategy the `ip_providers` list becomes a
+/// `ProviderPool`. With the `quorum` strategy the `ip_providers` list is
+/// wrapped in a `QuorumProvider` that judges each answer with `MatchRules`
+/// built from the same configuration. With `asn_db_path` set, every
+/// provider is wrapped in an `AsnDbProvider` sharing one database, so
+/// answers are enriched before they are judged. With `ipv6_leak_check`
+/// enabled the lookup is forced over IPv4; see `provider_for_family` for
+/// the IPv6 side.
+pub fn provider_from_config(eff: &EffectiveConfig) -> Result<Box<dyn IpInfoProvider>> {
+    provider_for_family(eff, eff.ipv6_leak_check.then_some(IpFamily::V4))
+}
+
+/// Like `provider_from_config`, but every lookup (HTTP, DNS and STUN) only
+/// uses addresses of `family`, so the answer is the public address of that
+/// family. `None` lets the OS pick.
+pub fn provider_for_family(
+    eff: &EffectiveConfig,
+    family: Option<IpFamily>,
+) -> Result<Box<dyn IpInfoProvider>> {
+    let mut settings = HttpSettings {
+        family,
+        ..HttpSettings::from_effective(eff)
+    };
+    // A source address can only reach servers of its own family.
+    settings.family = settings
+        .family
+        .or(settings.bind.source_address.map(IpFamily::of));
+    let client = settings.build_client()?;
+    let ctx = BuildContext {
+        eff,
+        client: &client,
+        settings: &settings,
+        asn_db: eff
+            .asn_db_path
+            .as_ref()
+            .map(|path| Arc::new(AsnDbFile::new(path))),
+    };
+
+    match eff.provider_strategy.as_str() {
+        "failover" => {
+            let providers = providers_from_specs(&ctx)?;
+            Ok(Box::new(ProviderPool::new(providers)))
+        }
+        "quorum" => {
+            let providers = providers_from_specs(&ctx)?;
+            let quorum = eff
+                .provider_quorum
+                .unwrap_or_else(|| consensus::majority(providers.len()));
+            let rules = MatchRules::from_effective(eff).map_err(|e| anyhow::anyhow!(e))?;
+            Ok(Box::new(QuorumProvider::new(
+                providers,
+                quorum,
+                Box::new(move |info: &IpInfo| rules.vpn_lost_reason(info).is_some()),
+            )))
+        }
+        _ => {
+            let kind = ProviderKind::parse(&eff.ip_provider)
+                .ok_or_else(|| anyhow::anyhow!("unknown ip provider '{}'", eff.ip_provider))?;
+            build_provider(&ctx, kind, eff.ip_provider_url.as_deref())
+        }
+    }
+}
+
+/// What every provider built for one configuration shares.
+struct BuildContext<'a> {
+    eff: &'a EffectiveConfig,
+    client: &'a Client,
+    settings: &'a HttpSettings,
+    asn_db: Option<Arc<AsnDbFile>>,
+}
+
+fn providers_from_specs(ctx: &BuildContext) -> Result<Vec<Box<dyn IpInfoProvider>>> {
+    ctx.eff
+        .ip_providers
+        .iter()
+        .map(|spec| {
+            let (kind, url) = parse_provider_spec(spec).map_err(|e| anyhow::anyhow!(e))?;
+            build_provider(ctx, kind, url.as_deref())
+        })
+        .collect()
+}
+
+/// Build one provider of `kind`; `endpoint` overrides its default URL, DNS
+/// resolver or STUN server list.
+fn build_provider(
+    ctx: &BuildContext,
+    kind: ProviderKind,
+    endpoint: Option<&str>,
+) -> Result<Box<dyn IpInfoProvider>> {
+    let provider = lookup_provider(ctx, kind, endpoint)?;
+    Ok(match &ctx.asn_db {
+        Some(db) => Box::new(AsnDbProvider::new(provider, db.clone())),
+        None => provider,
+    })
+}
+
+fn lookup_provider(
+    ctx: &BuildContext,
+    kind: ProviderKind,
+    endpoint: Option<&str>,
+) -> Result<Box<dyn IpInfoProvider>> {
+    let BuildContext {
+        eff,
+        client,
+        settings,
+        ..
+    } = *ctx;
+    if kind == ProviderKind::Stun {
+        let servers = match endpoint {
+            Some(server) => vec![server.to_string()],
+            None => eff.stun_servers.clone(),
+        };
+        return Ok(Box::new(
+            StunProvider::new(
+                servers,
+                Duration::from_secs(eff.lookup_timeout_secs),
+                settings.retries,
+            )
+            .with_family(settings.family)
+            .with_bind(settings.bind.clone()),
+        ));
+    }
+    if kind.is_dns() {
+        let service = match kind {
+            ProviderKind::DnsGoogle => DnsService::Google,
+            _ => DnsService::OpenDns,
+        };
+        let resolver = endpoint.unwrap_or_else(|| service.default_resolver(settings.family));
+        return Ok(Box::new(
+            DnsProvider::new(
+                service,
+                resolver,
+                Duration::from_secs(eff.lookup_timeout_secs),
+                settings.retries,
+            )
+            .with_family(settings.family)
+            .with_bind(settings.bind.clone()),
+        ));
+    }
+    let provider = HttpProvider::new(
+        kind,
+        endpoint,
+        eff.ip_provider_isp_field.as_deref(),
+        client.clone(),
+        settings.retries,
+    )?
+    .with_settings(settings.clone())
+    .with_rate_limit(rate_limit(eff, kind));
+    Ok(Box::new(provider))
+}
+
+/// Configured per-minute budget, falling back to the provider's default.
+fn rate_limit(eff: &EffectiveConfig, kind: ProviderKind) -> Option<u32> {
+    eff.rate_limit_per_minute
+        .or_else(|| kind.default_rate_limit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_known_provider_names() {
+        for name in PROVIDER_NAMES {
+            let kind = ProviderKind::parse(name).expect("known provider");
+            assert_eq!(kind.as_str(), name);
+        }
+        assert!(ProviderKind::parse("nope").is_none());
+    }
+
+    #[test]
+    fn custom_requires_url() {
+        let client = Client::new();
+        assert!(HttpProvider::new(ProviderKind::Custom, None, None, client.clone(), 1).is_err());
+        assert!(HttpProvider::new(ProviderKind::DnsOpenDns, None, None, client, 1).is_err());
+    }
+
+    #[test]
+    fn parse_provider_spec_variants() {
+        assert_eq!(
+            parse_provider_spec("ipinfo"),
+            Ok((ProviderKind::IpInfo, None))
+        );
+        assert_eq!(
+            parse_provider_spec(" custom=https://x.example/json?fields=isp "),
+            Ok((
+                ProviderKind::Custom,
+                Some("https://x.example/json?fields=isp".to_string())
+            ))
+        );
+        assert!(parse_provider_spec("custom").is_err());
+        assert!(parse_provider_spec("nope=https://x").is_err());
+    }
+}
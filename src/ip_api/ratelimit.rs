@@ -0,0 +1,176 @@
+use reqwest::header::HeaderMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Requests per minute allowed by ip-api.com's free tier.
+pub const IP_API_FREE_TIER_PER_MINUTE: u32 = 45;
+
+/// A classic token bucket: `capacity` tokens, refilled continuously at
+/// `capacity` per minute. Server hints can lower the balance or block the
+/// bucket until a given instant.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+    blocked_until: Option<Instant>,
+}
+
+impl TokenBucket {
+    /// A full bucket allowing `per_minute` requests per minute.
+    pub fn per_minute(per_minute: u32, now: Instant) -> Self {
+        let capacity = f64::from(per_minute.max(1));
+        TokenBucket {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity / 60.0,
+            last_refill: now,
+            blocked_until: None,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Take one token. On failure returns how long to wait before a token
+    /// will be available.
+    pub fn try_acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        if let Some(until) = self.blocked_until {
+            if now < until {
+                return Err(until - now);
+            }
+            self.blocked_until = None;
+        }
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - self.tokens;
+            Err(Duration::from_secs_f64(missing / self.refill_per_sec))
+        }
+    }
+
+    /// Apply the server's view of our budget: `remaining` requests left in
+    /// the current window, which resets in `reset_in`. When nothing is left
+    /// the bucket stays closed until the reset.
+    pub fn observe(&mut self, remaining: Option<u32>, reset_in: Option<Duration>, now: Instant) {
+        self.refill(now);
+        if let Some(rem) = remaining {
+            self.tokens = self.tokens.min(f64::from(rem));
+            if rem == 0 {
+                let wait = reset_in.unwrap_or(Duration::from_secs(60));
+                self.block_for(wait, now);
+            }
+        }
+    }
+
+    /// Close the bucket for `wait` (e.g. after a 429 response).
+    pub fn block_for(&mut self, wait: Duration, now: Instant) {
+        let until = now + wait;
+        self.tokens = 0.0;
+        self.blocked_until = Some(self.blocked_until.map_or(until, |u| u.max(until)));
+    }
+}
+
+/// Thread-safe token bucket shared by all requests of one provider.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Mutex<TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn per_minute(per_minute: u32) -> Self {
+        RateLimiter {
+            bucket: Mutex::new(TokenBucket::per_minute(per_minute, Instant::now())),
+        }
+    }
+
+    /// Take one request from the budget, or return how long until one is free.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        self.bucket
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .try_acquire(Instant::now())
+    }
+
+    /// Feed ip-api's `X-Rl` (requests remaining) and `X-Ttl` (seconds until
+    /// the window resets) response headers back into the bucket.
+    pub fn observe_headers(&self, headers: &HeaderMap) {
+        let remaining = header_u64(headers, "x-rl").map(|v| v.min(u64::from(u32::MAX)) as u32);
+        let reset_in = header_u64(headers, "x-ttl").map(Duration::from_secs);
+        if remaining.is_none() {
+            return;
+        }
+        self.bucket
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .observe(remaining, reset_in, Instant::now());
+    }
+
+    /// Stop sending requests for `wait`.
+    pub fn block_for(&self, wait: Duration) {
+        self.bucket
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .block_for(wait, Instant::now());
+    }
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers
+        .get(name)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_allows_capacity_then_refills() {
+        let start = Instant::now();
+        let mut b = TokenBucket::per_minute(2, start);
+        assert!(b.try_acquire(start).is_ok());
+        assert!(b.try_acquire(start).is_ok());
+        let wait = b.try_acquire(start).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(30));
+        assert!(b.try_acquire(start + Duration::from_secs(30)).is_ok());
+    }
+
+    #[test]
+    fn server_hint_lowers_balance() {
+        let now = Instant::now();
+        let mut b = TokenBucket::per_minute(45, now);
+        b.observe(Some(1), Some(Duration::from_secs(20)), now);
+        assert!(b.try_acquire(now).is_ok());
+        assert!(b.try_acquire(now).is_err());
+    }
+
+    #[test]
+    fn exhausted_server_budget_blocks_until_reset() {
+        let now = Instant::now();
+        let mut b = TokenBucket::per_minute(45, now);
+        b.observe(Some(0), Some(Duration::from_secs(20)), now);
+        assert_eq!(b.try_acquire(now).unwrap_err(), Duration::from_secs(20));
+        assert!(b.try_acquire(now + Duration::from_secs(19)).is_err());
+        assert!(b.try_acquire(now + Duration::from_secs(20)).is_ok());
+    }
+
+    #[test]
+    fn observe_headers_reads_ip_api_headers() {
+        let limiter = RateLimiter::per_minute(45);
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Rl", "0".parse().unwrap());
+        headers.insert("X-Ttl", "30".parse().unwrap());
+        limiter.observe_headers(&headers);
+        let wait = limiter.try_acquire().unwrap_err();
+        assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30));
+    }
+}
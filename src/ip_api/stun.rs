@@ -0,0 +1,348 @@
+use anyhow::Result;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::dns;
+use super::error::IpLookupError;
+use super::family::IpFamily;
+use super::info::IpInfo;
+use super::provider::IpInfoProvider;
+use crate::networking::SocketBind;
+
+/// Servers queried when `stun_servers` is not configured.
+pub const DEFAULT_STUN_SERVERS: [&str; 2] = ["stun.l.google.com:19302", "stun.cloudflare.com:3478"];
+/// Port used when a server is given without one (RFC 5389 section 18.4).
+pub const DEFAULT_STUN_PORT: u16 = 3478;
+
+const MAGIC_COOKIE: u32 = 0x2112_A442;
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const BINDING_ERROR: u16 = 0x0111;
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_ERROR_CODE: u16 = 0x0009;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+/// Pre-RFC 5389 servers send XOR-MAPPED-ADDRESS under this type.
+const ATTR_XOR_MAPPED_ADDRESS_OLD: u16 = 0x8020;
+
+/// Public-IP provider that sends a STUN Binding request (RFC 5389) over UDP
+/// and reads the mapped address from the response.
+///
+/// STUN servers have no rate limits and are rarely blocked, but only the
+/// public IP is learned, so pair it with `cidr_to_check`/`allowed_cidr`.
+/// Servers are tried in order until one answers.
+pub struct StunProvider {
+    servers: Vec<String>,
+    timeout: Duration,
+    retries: usize,
+    family: Option<IpFamily>,
+    bind: SocketBind,
+}
+
+impl StunProvider {
+    /// `servers` are `host[:port]` entries (port 3478 when omitted).
+    pub fn new(servers: Vec<String>, timeout: Duration, retries: usize) -> Self {
+        StunProvider {
+            servers,
+            timeout,
+            retries: retries.max(1),
+            family: None,
+            bind: SocketBind::default(),
+        }
+    }
+
+    /// Only contact servers over `family`, so the mapped address is the
+    /// public address of that family.
+    pub fn with_family(mut self, family: Option<IpFamily>) -> Self {
+        self.family = family;
+        self
+    }
+
+    /// Send requests from the interface and/or source address in `bind`.
+    pub fn with_bind(mut self, bind: SocketBind) -> Self {
+        self.bind = bind;
+        self
+    }
+
+    fn query_once(&self, addr: SocketAddr) -> std::result::Result<IpAddr, IpLookupError> {
+        let txid = transaction_id();
+        let request = build_binding_request(&txid);
+
+        let io = |e: std::io::Error| match e.kind() {
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => IpLookupError::Timeout,
+            _ => IpLookupError::Transport(format!("stun request to {} failed: {}", addr, e)),
+        };
+        let socket = self.bind.udp_socket(&addr).map_err(io)?;
+        socket.set_read_timeout(Some(self.timeout)).map_err(io)?;
+        socket.connect(addr).map_err(io)?;
+        socket.send(&request).map_err(io)?;
+
+        let mut buf = [0u8; 1500];
+        loop {
+            let n = socket.recv(&mut buf).map_err(io)?;
+            // Ignore datagrams for other transactions.
+            if n >= 20 && buf[8..20] == txid {
+                return parse_binding_response(&buf[..n], &txid);
+            }
+        }
+    }
+
+    fn query_server(&self, server: &str) -> std::result::Result<IpAddr, IpLookupError> {
+        let addr =
+            dns::resolve_host_port(server, DEFAULT_STUN_PORT, self.family).ok_or_else(|| {
+                let family = self.family.map(|f| format!(" to an {} address", f));
+                IpLookupError::Transport(format!(
+                    "cannot resolve STUN server '{}'{}",
+                    server,
+                    family.unwrap_or_default()
+                ))
+            })?;
+        let mut last_err = IpLookupError::Timeout;
+        for _ in 0..self.retries {
+            match self.query_once(addr) {
+                Ok(ip) => return Ok(ip),
+                Err(e @ (IpLookupError::Timeout | IpLookupError::Transport(_))) => last_err = e,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_err)
+    }
+}
+
+impl IpInfoProvider for StunProvider {
+    fn name(&self) -> &str {
+        "stun"
+    }
+
+    fn get_ip_info(&self) -> Result<IpInfo> {
+        let mut last_err = IpLookupError::Transport("no STUN servers configured".to_string());
+        for server in &self.servers {
+            match self.query_server(server) {
+                Ok(ip) => {
+                    return Ok(IpInfo {
+                        ip: Some(ip),
+                        ..Default::default()
+                    })
+                }
+                Err(e) => {
+                    log::debug!("STUN server {} failed: {}", server, e);
+                    last_err = e;
+                }
+            }
+        }
+        Err(last_err.into())
+    }
+}
+
+/// Check a `stun_servers` entry without resolving it.
+pub fn validate_server(s: &str) -> std::result::Result<(), String> {
+    if dns::is_host_port(s) {
+        Ok(())
+    } else {
+        Err(format!("invalid STUN server address '{}'", s.trim()))
+    }
+}
+
+fn transaction_id() -> [u8; 12] {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let mut id = [0u8; 12];
+    id[..8].copy_from_slice(&nanos.to_be_bytes());
+    let tail = std::process::id() ^ COUNTER.fetch_add(1, Ordering::Relaxed).rotate_left(16);
+    id[8..].copy_from_slice(&tail.to_be_bytes());
+    id
+}
+
+/// Encode a Binding request without attributes.
+pub fn build_binding_request(txid: &[u8; 12]) -> Vec<u8> {
+    let mut m = Vec::with_capacity(20);
+    m.extend_from_slice(&BINDING_REQUEST.to_be_bytes());
+    m.extend_from_slice(&0u16.to_be_bytes());
+    m.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    m.extend_from_slice(txid);
+    m
+}
+
+/// Extract the mapped address from a Binding response. XOR-MAPPED-ADDRESS
+/// is preferred; MAPPED-ADDRESS is accepted from RFC 3489 servers.
+pub fn parse_binding_response(
+    msg: &[u8],
+    txid: &[u8; 12],
+) -> std::result::Result<IpAddr, IpLookupError> {
+    let malformed = || IpLookupError::Parse("malformed STUN response".to_string());
+    if msg.len() < 20 || msg[8..20] != *txid {
+        return Err(malformed());
+    }
+    let msg_type = u16::from_be_bytes([msg[0], msg[1]]);
+    let len = usize::from(u16::from_be_bytes([msg[2], msg[3]]));
+    let attrs = msg.get(20..20 + len).ok_or_else(malformed)?;
+
+    let mut mapped = None;
+    let mut xor_mapped = None;
+    let mut error = None;
+    let mut pos = 0;
+    while pos + 4 <= attrs.len() {
+        let attr_type = u16::from_be_bytes([attrs[pos], attrs[pos + 1]]);
+        let attr_len = usize::from(u16::from_be_bytes([attrs[pos + 2], attrs[pos + 3]]));
+        let value = attrs
+            .get(pos + 4..pos + 4 + attr_len)
+            .ok_or_else(malformed)?;
+        match attr_type {
+            ATTR_XOR_MAPPED_ADDRESS | ATTR_XOR_MAPPED_ADDRESS_OLD => {
+                xor_mapped = xor_mapped.or(decode_address(value, Some(txid)));
+            }
+            ATTR_MAPPED_ADDRESS => mapped = mapped.or(decode_address(value, None)),
+            ATTR_ERROR_CODE if value.len() >= 4 => {
+                let code = u16::from(value[2] & 0x07) * 100 + u16::from(value[3]);
+                let reason = String::from_utf8_lossy(&value[4..]).trim().to_string();
+                error = Some((code, reason));
+            }
+            _ => {}
+        }
+        // Attribute values are padded to a multiple of four bytes.
+        pos += 4 + attr_len.div_ceil(4) * 4;
+    }
+
+    match msg_type {
+        BINDING_SUCCESS => xor_mapped
+            .or(mapped)
+            .ok_or_else(|| IpLookupError::MissingField("ip".to_string())),
+        BINDING_ERROR => {
+            let (code, reason) = error.unwrap_or((0, String::new()));
+            Err(IpLookupError::Parse(format!(
+                "STUN server returned error {} {}",
+                code, reason
+            )))
+        }
+        _ => Err(malformed()),
+    }
+}
+
+/// Decode a (XOR-)MAPPED-ADDRESS value; `txid` selects XOR decoding.
+fn decode_address(value: &[u8], txid: Option<&[u8; 12]>) -> Option<IpAddr> {
+    let family = *value.get(1)?;
+    // XOR key: magic cookie followed by the transaction id (IPv4 only uses
+    // the cookie); all zeroes for plain MAPPED-ADDRESS.
+    let mut key = [0u8; 16];
+    if let Some(txid) = txid {
+        key[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        key[4..].copy_from_slice(txid);
+    }
+    let len = match family {
+        0x01 => 4,
+        0x02 => 16,
+        _ => return None,
+    };
+    let raw = value.get(4..4 + len)?;
+    let mut b = [0u8; 16];
+    for (out, (r, k)) in b.iter_mut().zip(raw.iter().zip(&key)) {
+        *out = r ^ k;
+    }
+    Some(if len == 4 {
+        IpAddr::from([b[0], b[1], b[2], b[3]])
+    } else {
+        IpAddr::from(b)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv6Addr;
+
+    const TXID: [u8; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+
+    fn response(msg_type: u16, attrs: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (t, v) in attrs {
+            body.extend_from_slice(&t.to_be_bytes());
+            body.extend_from_slice(&(v.len() as u16).to_be_bytes());
+            body.extend_from_slice(v);
+            body.resize(body.len().div_ceil(4) * 4, 0);
+        }
+        let mut m = Vec::new();
+        m.extend_from_slice(&msg_type.to_be_bytes());
+        m.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        m.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        m.extend_from_slice(&TXID);
+        m.extend_from_slice(&body);
+        m
+    }
+
+    #[test]
+    fn binding_request_layout() {
+        let m = build_binding_request(&TXID);
+        assert_eq!(m.len(), 20);
+        assert_eq!(&m[..4], &[0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(&m[4..8], &[0x21, 0x12, 0xA4, 0x42]);
+        assert_eq!(&m[8..], &TXID);
+    }
+
+    #[test]
+    fn decodes_xor_mapped_v4() {
+        // 203.0.113.7 XOR 0x2112A442
+        let value = vec![
+            0,
+            1,
+            0x21 ^ 0x12,
+            0x34,
+            203 ^ 0x21,
+            0x12,
+            113 ^ 0xA4,
+            7 ^ 0x42,
+        ];
+        let r = response(BINDING_SUCCESS, &[(ATTR_XOR_MAPPED_ADDRESS, value)]);
+        let ip = parse_binding_response(&r, &TXID).unwrap();
+        assert_eq!(ip, "203.0.113.7".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn decodes_xor_mapped_v6_and_plain_mapped() {
+        let v6: Ipv6Addr = "2001:db8::7".parse().unwrap();
+        let mut key = MAGIC_COOKIE.to_be_bytes().to_vec();
+        key.extend_from_slice(&TXID);
+        let mut value = vec![0, 2, 0, 0];
+        value.extend(v6.octets().iter().zip(&key).map(|(a, k)| a ^ k));
+        let r = response(BINDING_SUCCESS, &[(ATTR_XOR_MAPPED_ADDRESS, value)]);
+        assert_eq!(parse_binding_response(&r, &TXID).unwrap(), IpAddr::V6(v6));
+
+        let value = vec![0, 1, 0x0d, 0x96, 198, 51, 100, 9];
+        let r = response(
+            BINDING_SUCCESS,
+            &[(0x8022, b"soft".to_vec()), (ATTR_MAPPED_ADDRESS, value)],
+        );
+        assert_eq!(
+            parse_binding_response(&r, &TXID).unwrap(),
+            "198.51.100.9".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn reports_error_responses() {
+        let mut value = vec![0, 0, 4, 20];
+        value.extend_from_slice(b"Unknown Attribute");
+        let r = response(BINDING_ERROR, &[(ATTR_ERROR_CODE, value)]);
+        match parse_binding_response(&r, &TXID) {
+            Err(IpLookupError::Parse(msg)) => assert!(msg.contains("error 420"), "{}", msg),
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let r = response(BINDING_SUCCESS, &[]);
+        assert!(matches!(
+            parse_binding_response(&r, &TXID),
+            Err(IpLookupError::MissingField(_))
+        ));
+        assert!(parse_binding_response(&r, &[0; 12]).is_err());
+    }
+
+    #[test]
+    fn validate_server_is_syntactic() {
+        for s in DEFAULT_STUN_SERVERS {
+            assert!(validate_server(s).is_ok());
+        }
+        assert!(validate_server("stun:stun.example.org").is_err());
+    }
+}
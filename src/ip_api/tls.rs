@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use reqwest::blocking::Response;
+use reqwest::tls::TlsInfo;
+use reqwest::Certificate;
+use sha2::{Digest, Sha256};
+
+use super::error::IpLookupError;
+
+/// Prefix of pins in the `sha256/<base64>` form used by curl and HPKP.
+const PIN_PREFIX: &str = "sha256/";
+
+/// Load every certificate from a PEM bundle file.
+pub fn load_ca_bundle(path: &str) -> Result<Vec<Certificate>> {
+    let pem = std::fs::read(path).with_context(|| format!("failed to read CA bundle {}", path))?;
+    let certs = Certificate::from_pem_bundle(&pem)
+        .with_context(|| format!("failed to parse CA bundle {}", path))?;
+    if certs.is_empty() {
+        anyhow::bail!("CA bundle {} contains no certificates", path);
+    }
+    Ok(certs)
+}
+
+/// Normalise a configured pin to `sha256/<base64>`. Accepts the bare base64
+/// digest too; anything that is not a 32-byte SHA-256 digest is rejected.
+pub fn normalize_pin(pin: &str) -> std::result::Result<String, String> {
+    let b64 = pin.trim().strip_prefix(PIN_PREFIX).unwrap_or(pin.trim());
+    match BASE64.decode(b64) {
+        Ok(digest) if digest.len() == 32 => Ok(format!("{}{}", PIN_PREFIX, b64)),
+        _ => Err(format!(
+            "invalid SPKI pin '{}' (expected sha256/<base64 SHA-256 digest>)",
+            pin
+        )),
+    }
+}
+
+/// SPKI pin (`sha256/<base64>`) of a DER-encoded certificate.
+pub fn spki_pin(cert_der: &[u8]) -> Option<String> {
+    let spki = spki_from_cert_der(cert_der)?;
+    Some(format!(
+        "{}{}",
+        PIN_PREFIX,
+        BASE64.encode(Sha256::digest(spki))
+    ))
+}
+
+/// Check the leaf certificate of `resp` against `pins`. Passes when `pins`
+/// is empty; otherwise the response must have come over TLS from a server
+/// whose public key matches one of the pins.
+pub fn verify_pins(resp: &Response, pins: &[String]) -> std::result::Result<(), IpLookupError> {
+    if pins.is_empty() {
+        return Ok(());
+    }
+    let cert = resp
+        .extensions()
+        .get::<TlsInfo>()
+        .and_then(|info| info.peer_certificate())
+        .ok_or_else(|| {
+            IpLookupError::Tls(format!(
+                "no TLS peer certificate for {} (pinning requires https)",
+                resp.url()
+            ))
+        })?;
+    let pin = spki_pin(cert)
+        .ok_or_else(|| IpLookupError::Tls("could not parse peer certificate".to_string()))?;
+    if pins.contains(&pin) {
+        Ok(())
+    } else {
+        Err(IpLookupError::Tls(format!(
+            "certificate pin mismatch for {} (server key {})",
+            resp.url().host_str().unwrap_or("?"),
+            pin
+        )))
+    }
+}
+
+/// Return the raw SubjectPublicKeyInfo element of an X.509 certificate.
+///
+/// Only the handful of DER elements in front of it are walked:
+/// Certificate -> TBSCertificate -> [version], serial, signature, issuer,
+/// validity, subject, subjectPublicKeyInfo.
+fn spki_from_cert_der(der: &[u8]) -> Option<&[u8]> {
+    let (_, cert, _) = der_element(der)?;
+    let (_, tbs, _) = der_element(cert)?;
+    let mut rest = tbs;
+    // Optional [0] EXPLICIT version
+    if rest.first() == Some(&0xA0) {
+        rest = der_element(rest)?.2;
+    }
+    // serialNumber, signature, issuer, validity, subject
+    for _ in 0..5 {
+        rest = der_element(rest)?.2;
+    }
+    let (header_len, content, _) = der_element(rest)?;
+    if rest.first() != Some(&0x30) {
+        return None;
+    }
+    Some(&rest[..header_len + content.len()])
+}
+
+/// Split one DER element off `input`: (header length, content, remainder).
+fn der_element(input: &[u8]) -> Option<(usize, &[u8], &[u8])> {
+    let first_len = *input.get(1)?;
+    let (len, header_len) = if first_len & 0x80 == 0 {
+        (usize::from(first_len), 2)
+    } else {
+        let n = usize::from(first_len & 0x7F);
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let bytes = input.get(2..2 + n)?;
+        let len = bytes
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | usize::from(*b));
+        (len, 2 + n)
+    };
+    let end = header_len.checked_add(len)?;
+    let content = input.get(header_len..end)?;
+    Some((header_len, content, &input[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pin of tests/fixtures/tls/server.crt, computed with the openssl CLI
+    // (see tests/fixtures/tls/README.md).
+    const SERVER_PIN: &str = "sha256/tJNV4o/LESY35TRGTA4nietgeSVXNR2QeFq2tiNi1JI=";
+
+    fn fixture_der() -> Vec<u8> {
+        let pem = std::fs::read_to_string("tests/fixtures/tls/server.crt").expect("fixture");
+        let b64: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+        BASE64.decode(b64).expect("base64")
+    }
+
+    #[test]
+    fn spki_pin_matches_openssl() {
+        assert_eq!(spki_pin(&fixture_der()).as_deref(), Some(SERVER_PIN));
+    }
+
+    #[test]
+    fn spki_pin_rejects_garbage() {
+        assert_eq!(spki_pin(b"\x30\x03\x02\x01"), None);
+        assert_eq!(spki_pin(&[]), None);
+    }
+
+    #[test]
+    fn normalize_pin_forms() {
+        let bare = SERVER_PIN.trim_start_matches(PIN_PREFIX);
+        assert_eq!(normalize_pin(bare).as_deref(), Ok(SERVER_PIN));
+        assert_eq!(normalize_pin(SERVER_PIN).as_deref(), Ok(SERVER_PIN));
+        assert!(normalize_pin("sha256/dG9vLXNob3J0").is_err());
+        assert!(normalize_pin("not base64!").is_err());
+    }
+}
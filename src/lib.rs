@@ -13,10 +13,10 @@
 //! ```ignore
 //! // The primary workflow is exercised in the tests; this example is for
 //! // documentation only and is not compiled as a doctest.
-//! use check_vpn::{app::perform_check, ip_api::get_isp, actions::run_action};
+//! use check_vpn::{app::perform_check, ip_api::get_ip_info, actions::run_action};
 //! // Construct an `EffectiveConfig` via merging CLI args and `Config` in real
 //! // programs. For docs we only show the call site:
-//! // perform_check(&effective_config, get_isp, run_action).unwrap();
+//! // perform_check(&effective_config, get_ip_info, run_action).unwrap();
 //! ```
 
 // Core modules using standard Rust module system
@@ -28,6 +28,7 @@ pub mod xml_io;
 
 // Application logic
 pub mod app;
+pub mod matching;
 
 // Shared helpers and modules
 pub mod actions;
@@ -46,6 +47,6 @@ pub mod timer {
 pub use actions::parse_action;
 pub use actions::Action;
 pub use config::Config;
-pub use ip_api::get_isp;
+pub use ip_api::{get_ip_info, get_isp, IpInfo};
 pub use networking::NetworkingError;
 pub use timer::{start_timer, TimerHandle};
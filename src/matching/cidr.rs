@@ -0,0 +1,128 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// An IPv4 or IPv6 network in CIDR notation, e.g. `203.0.113.0/24`.
+///
+/// A bare address (no `/len`) is accepted and treated as a single host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// Return true if `ip` lies inside this network. Addresses of the other
+    /// family never match.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = prefix_mask_u32(self.prefix_len);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = prefix_mask_u128(self.prefix_len);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_mask_u32(len: u8) -> u32 {
+    if len == 0 {
+        0
+    } else {
+        u32::MAX << (32 - u32::from(len))
+    }
+}
+
+fn prefix_mask_u128(len: u8) -> u128 {
+    if len == 0 {
+        0
+    } else {
+        u128::MAX << (128 - u32::from(len))
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, len) = match s.split_once('/') {
+            Some((a, l)) => (a, Some(l)),
+            None => (s, None),
+        };
+        let network: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("invalid IP address in CIDR '{}'", s))?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match len {
+            Some(l) => l
+                .parse::<u8>()
+                .ok()
+                .filter(|l| *l <= max)
+                .ok_or_else(|| format!("invalid prefix length in CIDR '{}'", s))?,
+            None => max,
+        };
+        Ok(Cidr {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn v4_network_contains() {
+        let c: Cidr = "203.0.113.0/24".parse().unwrap();
+        assert!(c.contains(&ip("203.0.113.7")));
+        assert!(c.contains(&ip("203.0.113.255")));
+        assert!(!c.contains(&ip("203.0.114.1")));
+        assert!(!c.contains(&ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn v6_network_contains() {
+        let c: Cidr = "2001:db8::/32".parse().unwrap();
+        assert!(c.contains(&ip("2001:db8:1::5")));
+        assert!(!c.contains(&ip("2001:db9::1")));
+    }
+
+    #[test]
+    fn bare_address_is_single_host() {
+        let c: Cidr = "198.51.100.9".parse().unwrap();
+        assert_eq!(c.to_string(), "198.51.100.9/32");
+        assert!(c.contains(&ip("198.51.100.9")));
+        assert!(!c.contains(&ip("198.51.100.10")));
+    }
+
+    #[test]
+    fn zero_prefix_matches_everything_in_family() {
+        let c: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(c.contains(&ip("8.8.8.8")));
+        assert!(!c.contains(&ip("::1")));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert!("203.0.113.0/33".parse::<Cidr>().is_err());
+        assert!("2001:db8::/129".parse::<Cidr>().is_err());
+        assert!("not-an-ip/24".parse::<Cidr>().is_err());
+        assert!("10.0.0.0/x".parse::<Cidr>().is_err());
+    }
+}
@@ -0,0 +1,430 @@
+//! Rules that decide whether a public-IP lookup means the VPN is lost.
+//!
+//! The historical rule is an exact comparison of the ISP name with
+//! `isp_to_check`. ISP names may now be given as several patterns compared
+//! according to `isp_match_mode` (see `pattern`). `MatchRules` extends that
+//! with ASN, organisation, country
+//! and public IP/CIDR conditions; the VPN is considered lost when any
+//! configured condition matches.
+//!
+//! In allowlist mode the logic is inverted: the user lists the expected VPN
+//! exits and anything else counts as VPN lost.
+
+use std::fmt;
+
+use crate::config::EffectiveConfig;
+use crate::ip_api::info::parse_asn;
+use crate::ip_api::IpInfo;
+
+pub mod cidr;
+pub mod pattern;
+
+pub use cidr::Cidr;
+pub use pattern::{Pattern, PatternMode, PATTERN_MODES};
+
+/// Names accepted for the `match_mode` config value.
+pub const MATCH_MODES: [&str; 2] = ["denylist", "allowlist"];
+
+/// How lookup results are judged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchMode {
+    /// VPN lost when the exit matches a known non-VPN connection (default).
+    #[default]
+    Denylist,
+    /// VPN lost when the exit matches none of the expected VPN exits.
+    Allowlist,
+}
+
+impl MatchMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "denylist" => Some(MatchMode::Denylist),
+            "allowlist" => Some(MatchMode::Allowlist),
+            _ => None,
+        }
+    }
+}
+
+/// Expected VPN exits used in allowlist mode.
+#[derive(Debug, Clone, Default)]
+pub struct AllowList {
+    pub isps: Vec<Pattern>,
+    pub asns: Vec<u32>,
+    pub cidrs: Vec<Cidr>,
+}
+
+impl AllowList {
+    pub fn is_empty(&self) -> bool {
+        self.isps.is_empty() && self.asns.is_empty() && self.cidrs.is_empty()
+    }
+
+    /// Some(true) if the exit matches an allowed entry, Some(false) if it
+    /// matches none, None if the lookup lacks every field the list examines.
+    fn allows(&self, info: &IpInfo) -> Option<bool> {
+        let mut judged = false;
+        if !self.isps.is_empty() {
+            if let Some(isp) = &info.isp {
+                judged = true;
+                if self.isps.iter().any(|p| p.matches(isp)) {
+                    return Some(true);
+                }
+            }
+        }
+        if !self.asns.is_empty() {
+            if let Some(asn) = info.asn {
+                judged = true;
+                if self.asns.contains(&asn) {
+                    return Some(true);
+                }
+            }
+        }
+        if !self.cidrs.is_empty() {
+            if let Some(ip) = &info.ip {
+                judged = true;
+                if self.cidrs.iter().any(|c| c.contains(ip)) {
+                    return Some(true);
+                }
+            }
+        }
+        if judged {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// Compiled form of the VPN-lost conditions from an `EffectiveConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct MatchRules {
+    pub mode: MatchMode,
+    pub allowed: AllowList,
+    pub isps: Vec<Pattern>,
+    pub orgs: Vec<String>,
+    pub asns: Vec<u32>,
+    /// Country codes or names that indicate the VPN is lost
+    pub countries: Vec<String>,
+    /// If non-empty, any country outside this list indicates the VPN is lost
+    pub expected_countries: Vec<String>,
+    pub cidrs: Vec<Cidr>,
+}
+
+/// Which condition matched, for logging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchReason {
+    Isp(String),
+    Org(String),
+    Asn(u32),
+    Country(String),
+    UnexpectedCountry(String),
+    Cidr(Cidr),
+    /// Allowlist mode: the exit matched none of the expected VPN exits
+    NotAllowed,
+}
+
+impl fmt::Display for MatchReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchReason::Isp(s) => write!(f, "ISP matches '{}'", s),
+            MatchReason::Org(s) => write!(f, "org matches '{}'", s),
+            MatchReason::Asn(n) => write!(f, "ASN matches AS{}", n),
+            MatchReason::Country(s) => write!(f, "country matches '{}'", s),
+            MatchReason::UnexpectedCountry(s) => {
+                write!(f, "country '{}' is not an expected country", s)
+            }
+            MatchReason::Cidr(c) => write!(f, "public IP is inside {}", c),
+            MatchReason::NotAllowed => write!(f, "exit is not one of the expected VPN exits"),
+        }
+    }
+}
+
+impl MatchRules {
+    /// Build the rules from a merged configuration. Returns an error naming
+    /// the first malformed ASN, CIDR or pattern (validation reports all of them).
+    pub fn from_effective(eff: &EffectiveConfig) -> Result<Self, String> {
+        let mode = MatchMode::parse(&eff.match_mode)
+            .ok_or_else(|| format!("unknown match mode '{}'", eff.match_mode))?;
+        let pattern_mode = PatternMode::parse(&eff.isp_match_mode)
+            .ok_or_else(|| format!("unknown ISP match mode '{}'", eff.isp_match_mode))?;
+        let isps = std::iter::once(&eff.isp_to_check)
+            .chain(&eff.isp_pattern)
+            .map(|s| Pattern::compile(s, pattern_mode))
+            .collect::<Result<Vec<_>, _>>()?;
+        let asns = parse_asns(&eff.asn_to_check)?;
+        let cidrs = parse_cidrs(&eff.cidr_to_check)?;
+        let allowed = AllowList {
+            isps: eff
+                .allowed_isp
+                .iter()
+                .map(|s| Pattern::compile(s, pattern_mode))
+                .collect::<Result<Vec<_>, _>>()?,
+            asns: parse_asns(&eff.allowed_asn)?,
+            cidrs: parse_cidrs(&eff.allowed_cidr)?,
+        };
+        Ok(MatchRules {
+            mode,
+            allowed,
+            isps,
+            orgs: eff.org_to_check.clone(),
+            asns,
+            countries: eff.country_to_check.clone(),
+            expected_countries: eff.expected_country.clone(),
+            cidrs,
+        })
+    }
+
+    /// Evaluate the rules against a lookup result. Returns the first matching
+    /// condition, or None if the exit looks like the VPN.
+    ///
+    /// In allowlist mode only the allowlist is consulted; a lookup that lacks
+    /// every field the allowlist examines is not judged.
+    pub fn vpn_lost_reason(&self, info: &IpInfo) -> Option<MatchReason> {
+        match self.mode {
+            MatchMode::Allowlist => match self.allowed.allows(info) {
+                Some(false) => Some(MatchReason::NotAllowed),
+                _ => None,
+            },
+            MatchMode::Denylist => self.denylist_reason(info),
+        }
+    }
+
+    fn denylist_reason(&self, info: &IpInfo) -> Option<MatchReason> {
+        if let Some(isp) = &info.isp {
+            if let Some(m) = self.isps.iter().find(|p| p.matches(isp)) {
+                return Some(MatchReason::Isp(m.as_str().to_string()));
+            }
+        }
+        if let Some(org) = &info.org {
+            if let Some(m) = self.orgs.iter().find(|p| *p == org) {
+                return Some(MatchReason::Org(m.clone()));
+            }
+        }
+        if let Some(asn) = info.asn {
+            if self.asns.contains(&asn) {
+                return Some(MatchReason::Asn(asn));
+            }
+        }
+        if let Some(m) = self.countries.iter().find(|c| country_matches(c, info)) {
+            return Some(MatchReason::Country(m.clone()));
+        }
+        if !self.expected_countries.is_empty() {
+            // Only judge when the provider reported a country at all.
+            if let Some(seen) = info.country_code.as_ref().or(info.country.as_ref()) {
+                if !self
+                    .expected_countries
+                    .iter()
+                    .any(|c| country_matches(c, info))
+                {
+                    return Some(MatchReason::UnexpectedCountry(seen.clone()));
+                }
+            }
+        }
+        if let Some(ip) = &info.ip {
+            if let Some(c) = self.cidrs.iter().find(|c| c.contains(ip)) {
+                return Some(MatchReason::Cidr(*c));
+            }
+        }
+        None
+    }
+}
+
+fn parse_asns(list: &[String]) -> Result<Vec<u32>, String> {
+    list.iter()
+        .map(|s| parse_asn(s).ok_or_else(|| format!("invalid ASN '{}'", s)))
+        .collect()
+}
+
+fn parse_cidrs(list: &[String]) -> Result<Vec<Cidr>, String> {
+    list.iter().map(|s| s.parse::<Cidr>()).collect()
+}
+
+/// Compare a configured country against the code and the name reported by
+/// the provider, ignoring case.
+fn country_matches(configured: &str, info: &IpInfo) -> bool {
+    let configured = configured.trim();
+    [&info.country_code, &info.country]
+        .into_iter()
+        .flatten()
+        .any(|seen| seen.eq_ignore_ascii_case(configured))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> IpInfo {
+        IpInfo {
+            ip: Some("203.0.113.7".parse().unwrap()),
+            isp: Some("Home ISP".into()),
+            org: Some("Home ISP Residential".into()),
+            asn: Some(64500),
+            country: Some("Sweden".into()),
+            country_code: Some("SE".into()),
+            ..Default::default()
+        }
+    }
+
+    fn rules() -> MatchRules {
+        MatchRules {
+            isps: vec![Pattern::exact("Other ISP")],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn isp_exact_match() {
+        let r = MatchRules {
+            isps: vec![Pattern::exact("Home ISP")],
+            ..Default::default()
+        };
+        assert_eq!(
+            r.vpn_lost_reason(&sample()),
+            Some(MatchReason::Isp("Home ISP".into()))
+        );
+        assert_eq!(rules().vpn_lost_reason(&sample()), None);
+    }
+
+    #[test]
+    fn asn_and_org_match() {
+        let r = MatchRules {
+            asns: vec![64500],
+            ..rules()
+        };
+        assert_eq!(r.vpn_lost_reason(&sample()), Some(MatchReason::Asn(64500)));
+
+        let r = MatchRules {
+            orgs: vec!["Home ISP Residential".into()],
+            ..rules()
+        };
+        assert!(matches!(
+            r.vpn_lost_reason(&sample()),
+            Some(MatchReason::Org(_))
+        ));
+    }
+
+    #[test]
+    fn country_and_expected_country() {
+        let r = MatchRules {
+            countries: vec!["se".into()],
+            ..rules()
+        };
+        assert!(matches!(
+            r.vpn_lost_reason(&sample()),
+            Some(MatchReason::Country(_))
+        ));
+
+        // "VPN lost if country is not SE"
+        let r = MatchRules {
+            expected_countries: vec!["SE".into()],
+            ..rules()
+        };
+        assert_eq!(r.vpn_lost_reason(&sample()), None);
+        let abroad = IpInfo {
+            country: Some("Netherlands".into()),
+            country_code: Some("NL".into()),
+            ..sample()
+        };
+        assert_eq!(
+            r.vpn_lost_reason(&abroad),
+            Some(MatchReason::UnexpectedCountry("NL".into()))
+        );
+        // Unknown country is not judged.
+        let unknown = IpInfo {
+            country: None,
+            country_code: None,
+            ..sample()
+        };
+        assert_eq!(r.vpn_lost_reason(&unknown), None);
+    }
+
+    #[test]
+    fn cidr_match() {
+        let r = MatchRules {
+            cidrs: vec!["203.0.113.0/24".parse().unwrap()],
+            ..rules()
+        };
+        assert!(matches!(
+            r.vpn_lost_reason(&sample()),
+            Some(MatchReason::Cidr(_))
+        ));
+    }
+
+    #[test]
+    fn from_effective_rejects_bad_values() {
+        let eff = EffectiveConfig {
+            asn_to_check: vec!["ASX".into()],
+            ..Default::default()
+        };
+        assert!(MatchRules::from_effective(&eff).is_err());
+
+        let eff = EffectiveConfig {
+            cidr_to_check: vec!["10.0.0.0/40".into()],
+            ..Default::default()
+        };
+        assert!(MatchRules::from_effective(&eff).is_err());
+    }
+
+    #[test]
+    fn allowlist_mode_flags_unexpected_exits() {
+        let r = MatchRules {
+            mode: MatchMode::Allowlist,
+            allowed: AllowList {
+                isps: vec![Pattern::exact("VPN Provider AB")],
+                asns: vec![42708],
+                cidrs: vec!["46.246.8.0/24".parse().unwrap()],
+            },
+            // Denylist entries are ignored in allowlist mode.
+            isps: vec![Pattern::exact("VPN Provider AB")],
+            ..Default::default()
+        };
+
+        // Home connection: matches nothing on the allowlist.
+        assert_eq!(r.vpn_lost_reason(&sample()), Some(MatchReason::NotAllowed));
+
+        // Any single allowlist hit is enough.
+        let by_asn = IpInfo {
+            asn: Some(42708),
+            ..sample()
+        };
+        assert_eq!(r.vpn_lost_reason(&by_asn), None);
+        let by_ip = IpInfo {
+            ip: Some("46.246.8.48".parse().unwrap()),
+            ..sample()
+        };
+        assert_eq!(r.vpn_lost_reason(&by_ip), None);
+        let by_isp = IpInfo {
+            isp: Some("VPN Provider AB".into()),
+            ..sample()
+        };
+        assert_eq!(r.vpn_lost_reason(&by_isp), None);
+
+        // Nothing to judge with.
+        assert_eq!(r.vpn_lost_reason(&IpInfo::default()), None);
+    }
+
+    #[test]
+    fn from_effective_compiles_isp_patterns() {
+        let eff = EffectiveConfig {
+            isp_to_check: "Hutchison*".into(),
+            isp_pattern: vec!["Three*".into()],
+            isp_match_mode: "glob".into(),
+            ..Default::default()
+        };
+        let r = MatchRules::from_effective(&eff).unwrap();
+        let three = IpInfo {
+            isp: Some("Three UK".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            r.vpn_lost_reason(&three),
+            Some(MatchReason::Isp("Three*".into()))
+        );
+
+        let bad = EffectiveConfig {
+            isp_pattern: vec!["(unclosed".into()],
+            isp_match_mode: "regex".into(),
+            ..Default::default()
+        };
+        assert!(MatchRules::from_effective(&bad).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use regex::{Regex, RegexBuilder};
+
+/// Names accepted for the `isp_match_mode` config value.
+pub const PATTERN_MODES: [&str; 5] = [
+    "exact",
+    "case-insensitive",
+    "glob",
+    "regex",
+    "normalized-whitespace",
+];
+
+/// How a configured name pattern is compared with a lookup value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PatternMode {
+    /// Byte-for-byte equality (the historical behaviour).
+    #[default]
+    Exact,
+    /// Equality ignoring case.
+    CaseInsensitive,
+    /// Shell-style wildcards: `*` matches any run of characters, `?` one
+    /// character. The whole value must match.
+    Glob,
+    /// Regular expression (regex crate syntax), searched anywhere in the
+    /// value; anchor with `^...$` for a full match.
+    Regex,
+    /// Equality after trimming and collapsing runs of whitespace.
+    NormalizedWhitespace,
+}
+
+impl PatternMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "exact" => Some(PatternMode::Exact),
+            "case-insensitive" => Some(PatternMode::CaseInsensitive),
+            "glob" => Some(PatternMode::Glob),
+            "regex" => Some(PatternMode::Regex),
+            "normalized-whitespace" => Some(PatternMode::NormalizedWhitespace),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Compiled {
+    Exact,
+    CaseInsensitive,
+    Regex(Regex),
+    NormalizedWhitespace(String),
+}
+
+/// A name pattern compiled for a specific `PatternMode`.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    source: String,
+    compiled: Compiled,
+}
+
+impl Pattern {
+    /// Compile `source` for `mode`. Fails only for invalid regexes.
+    pub fn compile(source: &str, mode: PatternMode) -> Result<Self, String> {
+        let compiled = match mode {
+            PatternMode::Exact => Compiled::Exact,
+            PatternMode::CaseInsensitive => Compiled::CaseInsensitive,
+            PatternMode::Glob => Compiled::Regex(
+                Regex::new(&glob_to_regex(source))
+                    .map_err(|e| format!("invalid glob '{}': {}", source, e))?,
+            ),
+            PatternMode::Regex => Compiled::Regex(
+                RegexBuilder::new(source)
+                    .size_limit(1 << 20)
+                    .build()
+                    .map_err(|e| format!("invalid regex '{}': {}", source, e))?,
+            ),
+            PatternMode::NormalizedWhitespace => {
+                Compiled::NormalizedWhitespace(normalize_whitespace(source))
+            }
+        };
+        Ok(Pattern {
+            source: source.to_string(),
+            compiled,
+        })
+    }
+
+    /// Shorthand for an exact pattern (cannot fail).
+    pub fn exact(source: &str) -> Self {
+        Pattern {
+            source: source.to_string(),
+            compiled: Compiled::Exact,
+        }
+    }
+
+    /// The pattern as written in the configuration.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        match &self.compiled {
+            Compiled::Exact => value == self.source,
+            Compiled::CaseInsensitive => value.to_lowercase() == self.source.to_lowercase(),
+            Compiled::Regex(re) => re.is_match(value),
+            Compiled::NormalizedWhitespace(norm) => normalize_whitespace(value) == *norm,
+        }
+    }
+}
+
+fn normalize_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Translate a glob into an anchored regex, escaping everything else.
+fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::with_capacity(glob.len() + 8);
+    out.push('^');
+    for c in glob.chars() {
+        match c {
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            other => out.push_str(&regex::escape(&other.to_string())),
+        }
+    }
+    out.push('$');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn m(mode: PatternMode, pat: &str, value: &str) -> bool {
+        Pattern::compile(pat, mode).unwrap().matches(value)
+    }
+
+    #[test]
+    fn exact_and_case_insensitive() {
+        assert!(m(
+            PatternMode::Exact,
+            "Hutchison 3G UK Ltd",
+            "Hutchison 3G UK Ltd"
+        ));
+        assert!(!m(
+            PatternMode::Exact,
+            "Hutchison 3G UK Ltd",
+            "HUTCHISON 3G UK LTD"
+        ));
+        assert!(m(
+            PatternMode::CaseInsensitive,
+            "Hutchison 3G UK Ltd",
+            "HUTCHISON 3G uk ltd"
+        ));
+    }
+
+    #[test]
+    fn glob_matches_whole_value() {
+        assert!(m(PatternMode::Glob, "Hutchison*", "Hutchison 3G UK Ltd"));
+        assert!(m(PatternMode::Glob, "*3G UK?Ltd", "Hutchison 3G UK Ltd"));
+        assert!(!m(PatternMode::Glob, "3G*", "Hutchison 3G UK Ltd"));
+        // regex metacharacters are literal in globs
+        assert!(m(PatternMode::Glob, "A.B (x)", "A.B (x)"));
+        assert!(!m(PatternMode::Glob, "A.B", "AxB"));
+    }
+
+    #[test]
+    fn regex_searches_and_rejects_invalid() {
+        assert!(m(
+            PatternMode::Regex,
+            "(?i)hutchison",
+            "Hutchison 3G UK Ltd"
+        ));
+        assert!(!m(PatternMode::Regex, "^3G", "Hutchison 3G UK Ltd"));
+        let err = Pattern::compile("Hutchison(", PatternMode::Regex).unwrap_err();
+        assert!(err.contains("invalid regex"));
+    }
+
+    #[test]
+    fn normalized_whitespace() {
+        assert!(m(
+            PatternMode::NormalizedWhitespace,
+            "Hutchison 3G UK Ltd",
+            "  Hutchison   3G\tUK Ltd "
+        ));
+        assert!(!m(
+            PatternMode::NormalizedWhitespace,
+            "Hutchison 3G UK Ltd",
+            "Hutchison 3G UK Limited"
+        ));
+    }
+
+    #[test]
+    fn parse_modes() {
+        for name in PATTERN_MODES {
+            assert!(PatternMode::parse(name).is_some(), "{}", name);
+        }
+        assert!(PatternMode::parse("fuzzy").is_none());
+    }
+}
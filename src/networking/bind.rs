@@ -0,0 +1,117 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::time::Duration;
+
+use crate::config::EffectiveConfig;
+
+/// Where outgoing probe and lookup sockets are bound: an interface
+/// (`SO_BINDTODEVICE`) and/or a source address. The default binds nothing
+/// and leaves the choice to the routing table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SocketBind {
+    pub interface: Option<String>,
+    pub source_address: Option<IpAddr>,
+}
+
+impl SocketBind {
+    /// `bind_interface` and `source_address` from the merged configuration.
+    pub fn from_effective(eff: &EffectiveConfig) -> Self {
+        SocketBind {
+            interface: eff.bind_interface.clone(),
+            source_address: eff.source_address.as_deref().and_then(|s| s.parse().ok()),
+        }
+    }
+
+    /// Whether `dst` can be reached from the source address, i.e. both are
+    /// of the same address family. Always true without a source address.
+    pub fn can_reach(&self, dst: &SocketAddr) -> bool {
+        self.source_address
+            .is_none_or(|src| src.is_ipv4() == dst.is_ipv4())
+    }
+
+    /// Open a TCP connection to `dst` from the bound interface/address.
+    pub fn connect_tcp(&self, dst: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+        let socket = self.socket(dst, Type::STREAM, Protocol::TCP)?;
+        socket.connect_timeout(&(*dst).into(), timeout)?;
+        Ok(socket.into())
+    }
+
+    /// A UDP socket for talking to `dst`; the caller still `connect`s it.
+    pub fn udp_socket(&self, dst: &SocketAddr) -> io::Result<UdpSocket> {
+        let socket = self.socket(dst, Type::DGRAM, Protocol::UDP)?;
+        if self.source_address.is_none() {
+            let any: IpAddr = if dst.is_ipv4() {
+                Ipv4Addr::UNSPECIFIED.into()
+            } else {
+                Ipv6Addr::UNSPECIFIED.into()
+            };
+            socket.bind(&SocketAddr::new(any, 0).into())?;
+        }
+        Ok(socket.into())
+    }
+
+    fn socket(&self, dst: &SocketAddr, ty: Type, protocol: Protocol) -> io::Result<Socket> {
+        if !self.can_reach(dst) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "source address {} cannot reach {}",
+                    self.source_address.unwrap(),
+                    dst
+                ),
+            ));
+        }
+        let socket = Socket::new(Domain::for_address(*dst), ty, Some(protocol))?;
+        if let Some(name) = &self.interface {
+            socket.bind_device(Some(name.as_bytes())).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("cannot bind to interface {}: {}", name, e),
+                )
+            })?;
+        }
+        if let Some(src) = self.source_address {
+            socket
+                .bind(&SocketAddr::new(src, 0).into())
+                .map_err(|e| io::Error::new(e.kind(), format!("cannot bind to {}: {}", src, e)))?;
+        }
+        Ok(socket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn source_address_must_match_family() {
+        let bind = SocketBind {
+            source_address: Some("127.0.0.1".parse().unwrap()),
+            ..Default::default()
+        };
+        let v6: SocketAddr = "[::1]:443".parse().unwrap();
+        assert!(bind.can_reach(&"192.0.2.1:443".parse().unwrap()));
+        assert!(!bind.can_reach(&v6));
+        let err = bind
+            .connect_tcp(&v6, Duration::from_millis(200))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(SocketBind::default().can_reach(&v6));
+    }
+
+    #[test]
+    fn connects_from_source_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let dst = listener.local_addr().unwrap();
+        let bind = SocketBind {
+            source_address: Some("127.0.0.2".parse().unwrap()),
+            ..Default::default()
+        };
+        let stream = bind.connect_tcp(&dst, Duration::from_secs(1)).unwrap();
+        assert_eq!(stream.local_addr().unwrap().ip().to_string(), "127.0.0.2");
+        let (_, peer) = listener.accept().unwrap();
+        assert_eq!(peer.ip().to_string(), "127.0.0.2");
+    }
+}
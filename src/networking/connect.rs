@@ -1,4 +1,4 @@
-use super::NetworkingError;
+use super::{NetworkingError, SocketBind};
 use log::trace;
 use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::time::Duration;
@@ -8,11 +8,26 @@ use std::time::Duration;
 /// connects successfully, Ok(false) if none connect, or Err on name-resolution
 /// failure.
 pub fn try_connect(addr: &str, timeout: Duration) -> Result<bool, NetworkingError> {
+    try_connect_bound(addr, timeout, &SocketBind::default())
+}
+
+/// `try_connect` from the interface and/or source address in `bind`.
+/// Resolved addresses the source address cannot reach (the other address
+/// family) are skipped.
+pub fn try_connect_bound(
+    addr: &str,
+    timeout: Duration,
+    bind: &SocketBind,
+) -> Result<bool, NetworkingError> {
     match addr.to_socket_addrs() {
         Ok(addrs) => {
             for socket in addrs {
                 trace!("Resolved {} -> {}", addr, socket);
-                if try_connect_addr(&socket, timeout) {
+                if !bind.can_reach(&socket) {
+                    trace!("skipping {}: not reachable from the source address", socket);
+                    continue;
+                }
+                if try_connect_addr(&socket, timeout, bind) {
                     return Ok(true);
                 }
             }
@@ -22,8 +37,13 @@ pub fn try_connect(addr: &str, timeout: Duration) -> Result<bool, NetworkingErro
     }
 }
 
-fn try_connect_addr(socket: &SocketAddr, timeout: Duration) -> bool {
-    match TcpStream::connect_timeout(socket, timeout) {
+fn try_connect_addr(socket: &SocketAddr, timeout: Duration, bind: &SocketBind) -> bool {
+    let res = if *bind == SocketBind::default() {
+        TcpStream::connect_timeout(socket, timeout)
+    } else {
+        bind.connect_tcp(socket, timeout)
+    };
+    match res {
         Ok(_) => true,
         Err(e) => {
             trace!("connect to {} failed: {}", socket, e);
@@ -65,6 +85,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn try_connect_bound_to_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind listener");
+        let addr = listener.local_addr().expect("local addr").to_string();
+        let timeout = Duration::from_millis(TEST_TIMEOUT_MS);
+
+        let lo = SocketBind {
+            interface: Some("lo".to_string()),
+            source_address: Some("127.0.0.1".parse().unwrap()),
+        };
+        assert!(matches!(try_connect_bound(&addr, timeout, &lo), Ok(true)));
+
+        // An IPv6 source cannot reach an IPv4 endpoint at all.
+        let v6 = SocketBind {
+            interface: None,
+            source_address: Some("::1".parse().unwrap()),
+        };
+        assert!(matches!(try_connect_bound(&addr, timeout, &v6), Ok(false)));
+    }
+
     #[test]
     fn try_connect_dns_error_returns_err() {
         // Use a syntactically-valid but (very likely) non-resolvable hostname.
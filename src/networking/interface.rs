@@ -0,0 +1,162 @@
+use anyhow::{Context, Result};
+use std::ffi::CStr;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+/// Where the kernel exposes network interfaces.
+pub const SYS_CLASS_NET: &str = "/sys/class/net";
+
+/// `IFF_UP` from `<net/if.h>`: the interface is administratively up.
+const IFF_UP: u32 = 0x1;
+
+/// State of one network interface as reported by the kernel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceState {
+    pub name: String,
+    /// RFC 2863 operational state (`up`, `down`, `unknown`, ...). Tunnel
+    /// devices such as tun and WireGuard usually report `unknown`.
+    pub operstate: String,
+    /// `IFF_*` interface flags.
+    pub flags: u32,
+    pub addresses: Vec<IpAddr>,
+}
+
+impl InterfaceState {
+    /// Administratively up and not reported down by its driver.
+    pub fn is_up(&self) -> bool {
+        self.flags & IFF_UP != 0
+            && !matches!(
+                self.operstate.as_str(),
+                "down" | "lowerlayerdown" | "notpresent"
+            )
+    }
+}
+
+/// Read `name` from `/sys/class/net` and its addresses from the kernel
+/// (`getifaddrs`, netlink-backed on Linux). `None` when it does not exist.
+pub fn read_interface(name: &str) -> Result<Option<InterfaceState>> {
+    let Some(mut state) = read_interface_from(Path::new(SYS_CLASS_NET), name)? else {
+        return Ok(None);
+    };
+    state.addresses = interface_addresses(name)
+        .with_context(|| format!("failed to list addresses of {}", name))?;
+    Ok(Some(state))
+}
+
+/// Read the operstate and flags of `name` below a `/sys/class/net`-style
+/// directory, leaving `addresses` empty.
+pub fn read_interface_from(root: &Path, name: &str) -> Result<Option<InterfaceState>> {
+    let dir = root.join(name);
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let read = |file: &str| -> Result<String> {
+        let path = dir.join(file);
+        Ok(fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?
+            .trim()
+            .to_string())
+    };
+    let flags = read("flags")?;
+    let flags = u32::from_str_radix(flags.trim_start_matches("0x"), 16)
+        .map_err(|_| anyhow::anyhow!("invalid flags '{}' for {}", flags, name))?;
+    Ok(Some(InterfaceState {
+        name: name.to_string(),
+        operstate: read("operstate")?,
+        flags,
+        addresses: Vec::new(),
+    }))
+}
+
+/// IPv4 and IPv6 addresses currently assigned to `name`.
+pub fn interface_addresses(name: &str) -> io::Result<Vec<IpAddr>> {
+    let mut head: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs fills `head` with a list we free below; every node
+    // is only read while the list is alive.
+    unsafe {
+        if libc::getifaddrs(&mut head) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut addrs = Vec::new();
+        let mut cur = head;
+        while !cur.is_null() {
+            let ifa = &*cur;
+            cur = ifa.ifa_next;
+            if ifa.ifa_addr.is_null() || CStr::from_ptr(ifa.ifa_name).to_bytes() != name.as_bytes()
+            {
+                continue;
+            }
+            match i32::from((*ifa.ifa_addr).sa_family) {
+                libc::AF_INET => {
+                    let sin = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                    addrs.push(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)).into());
+                }
+                libc::AF_INET6 => {
+                    let sin6 = &*(ifa.ifa_addr as *const libc::sockaddr_in6);
+                    addrs.push(Ipv6Addr::from(sin6.sin6_addr.s6_addr).into());
+                }
+                _ => {}
+            }
+        }
+        libc::freeifaddrs(head);
+        Ok(addrs)
+    }
+}
+
+/// Interface names the kernel accepts: 1-15 bytes, no `/`, `:` or
+/// whitespace, and not `.` or `..`.
+pub fn is_valid_interface_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() < 16
+        && name != "."
+        && name != ".."
+        && !name
+            .chars()
+            .any(|c| c == '/' || c == ':' || c.is_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_iface(root: &Path, name: &str, operstate: &str, flags: &str) {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("operstate"), format!("{}\n", operstate)).unwrap();
+        fs::write(dir.join("flags"), format!("{}\n", flags)).unwrap();
+    }
+
+    #[test]
+    fn reads_sysfs_state() {
+        let root = tempfile::tempdir().unwrap();
+        fake_iface(root.path(), "wg0", "unknown", "0x91");
+        fake_iface(root.path(), "tun0", "down", "0x1090");
+
+        let wg = read_interface_from(root.path(), "wg0").unwrap().unwrap();
+        assert_eq!(wg.flags, 0x91);
+        assert!(wg.is_up());
+        let tun = read_interface_from(root.path(), "tun0").unwrap().unwrap();
+        assert!(!tun.is_up());
+        assert!(read_interface_from(root.path(), "proton0")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn loopback_has_its_address() {
+        let lo = read_interface("lo").unwrap().expect("lo exists");
+        assert!(lo.is_up());
+        assert!(lo.addresses.contains(&IpAddr::V4(Ipv4Addr::LOCALHOST)));
+    }
+
+    #[test]
+    fn interface_names() {
+        assert!(is_valid_interface_name("wg0"));
+        assert!(is_valid_interface_name("proton0"));
+        assert!(!is_valid_interface_name(""));
+        assert!(!is_valid_interface_name("../eth0"));
+        assert!(!is_valid_interface_name("a-very-long-name0"));
+    }
+}
@@ -1,9 +1,40 @@
 use log::{debug, trace};
 use std::thread::sleep;
 use std::time::Duration;
+mod bind;
 mod connect;
 mod error;
+mod interface;
+mod netlink;
+pub mod netns;
+pub mod network_manager;
+pub mod openvpn;
+mod resolvers;
+mod route;
+pub mod systemd;
+pub mod wireguard;
+pub use bind::SocketBind;
+pub use connect::{try_connect, try_connect_bound};
 pub use error::NetworkingError;
+pub use interface::{
+    interface_addresses, is_valid_interface_name, read_interface, read_interface_from,
+    InterfaceState, SYS_CLASS_NET,
+};
+pub use resolvers::{
+    active_resolvers, parse_resolv_conf, parse_resolved_dns, resolved_dns_servers, resolved_links,
+    routed_servers, ResolvedLink, DEFAULT_RESOLV_CONF,
+};
+pub use route::{
+    lookup as lookup_route, parse_proc_ipv6_route, parse_proc_route, read_route_table, route_to,
+    RouteEntry, RouteInfo, PROC_NET_IPV6_ROUTE, PROC_NET_ROUTE,
+};
+
+/// Connect to the D-Bus system bus, where NetworkManager, systemd and
+/// systemd-resolved run.
+pub fn system_bus() -> anyhow::Result<zbus::blocking::Connection> {
+    use anyhow::Context;
+    zbus::blocking::Connection::system().context("failed to connect to system bus")
+}
 
 /// Default timeout (seconds) for connectivity checks.
 pub const DEFAULT_TIMEOUT_SECS: u64 = 2;
@@ -45,6 +76,25 @@ pub fn is_online_with_retries<S: AsRef<str>>(
     timeout_secs: u64,
     ports: &[u16],
     retries: usize,
+) -> Result<bool, NetworkingError> {
+    is_online_with_bind(
+        endpoints,
+        timeout_secs,
+        ports,
+        retries,
+        &SocketBind::default(),
+    )
+}
+
+/// Like `is_online_with_retries` but connects from the interface and/or
+/// source address in `bind`, to check the path through one interface in
+/// split-tunnel setups.
+pub fn is_online_with_bind<S: AsRef<str>>(
+    endpoints: &[S],
+    timeout_secs: u64,
+    ports: &[u16],
+    retries: usize,
+    bind: &SocketBind,
 ) -> Result<bool, NetworkingError> {
     let timeout = Duration::from_secs(timeout_secs);
     // Ensure at least one attempt is performed; keeps loop logic simple.
@@ -71,7 +121,7 @@ pub fn is_online_with_retries<S: AsRef<str>>(
                     "Attempting connect to {} (attempt {}/{})",
                     addr, attempt, attempts
                 );
-                match connect::try_connect(&addr, timeout) {
+                match connect::try_connect_bound(&addr, timeout, bind) {
                     Ok(true) => return Ok(true),
                     Ok(false) => {
                         // not reachable right now; try again if attempts remain
@@ -0,0 +1,189 @@
+//! Raw netlink sockets and message helpers, shared by the rtnetlink route
+//! lookup and the WireGuard generic netlink client.
+
+use std::io;
+use std::mem::size_of;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+/// Length of `struct nlmsghdr`.
+pub(crate) const NLMSG_HDR_LEN: usize = 16;
+/// Attribute type bits without the nested/byte-order flags.
+const NLA_TYPE_MASK: u16 = 0x3fff;
+/// How long to wait for the kernel's reply.
+const RECV_TIMEOUT_SECS: libc::time_t = 2;
+
+fn malformed() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "malformed netlink reply")
+}
+
+/// A netlink socket of one protocol (`NETLINK_ROUTE`, `NETLINK_GENERIC`).
+pub(crate) struct Socket(OwnedFd);
+
+impl Socket {
+    pub(crate) fn open(protocol: libc::c_int) -> io::Result<Self> {
+        // SAFETY: plain socket(2) call; the descriptor is owned immediately.
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                protocol,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a fresh descriptor nobody else owns.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let timeout = libc::timeval {
+            tv_sec: RECV_TIMEOUT_SECS,
+            tv_usec: 0,
+        };
+        // SAFETY: `timeout` outlives the call and its size is passed along.
+        let rc = unsafe {
+            libc::setsockopt(
+                fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const _ as *const libc::c_void,
+                size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        // Without the timeout a lost reply would block the check forever.
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Socket(fd))
+    }
+
+    pub(crate) fn send(&self, msg: &[u8]) -> io::Result<()> {
+        // SAFETY: sending from a live buffer of the given length.
+        let n = unsafe {
+            libc::send(
+                self.0.as_raw_fd(),
+                msg.as_ptr() as *const libc::c_void,
+                msg.len(),
+                0,
+            )
+        };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Receive replies until the request is answered, handing each message
+    /// (header included) to `on_message`. A netlink error reply becomes the
+    /// matching OS error.
+    pub(crate) fn recv_all(&self, mut on_message: impl FnMut(&[u8])) -> io::Result<()> {
+        let mut buf = vec![0u8; 32 * 1024];
+        loop {
+            // SAFETY: receiving into a live buffer of the given length.
+            let n = unsafe {
+                libc::recv(
+                    self.0.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                    0,
+                )
+            };
+            if n < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut msgs = &buf[..n as usize];
+            let mut multipart = false;
+            while msgs.len() >= NLMSG_HDR_LEN {
+                let len = u32::from_ne_bytes(msgs[..4].try_into().unwrap_or_default()) as usize;
+                let ty = u16::from_ne_bytes([msgs[4], msgs[5]]);
+                let flags = u16::from_ne_bytes([msgs[6], msgs[7]]);
+                if len < NLMSG_HDR_LEN || len > msgs.len() {
+                    return Err(malformed());
+                }
+                let msg = &msgs[..len];
+                msgs = &msgs[((len + 3) & !3).min(msgs.len())..];
+                match ty as i32 {
+                    libc::NLMSG_DONE => return Ok(()),
+                    libc::NLMSG_ERROR => {
+                        let code = i32::from_ne_bytes(
+                            msg.get(16..20)
+                                .and_then(|b| b.try_into().ok())
+                                .unwrap_or_default(),
+                        );
+                        if code == 0 {
+                            return Ok(());
+                        }
+                        return Err(io::Error::from_raw_os_error(-code));
+                    }
+                    _ => {
+                        multipart |= flags & libc::NLM_F_MULTI as u16 != 0;
+                        on_message(msg);
+                    }
+                }
+            }
+            if !multipart {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Build a netlink message of type `ty` (sequence number 1) around `body`.
+pub(crate) fn message(ty: u16, flags: u16, body: &[u8]) -> Vec<u8> {
+    let len = NLMSG_HDR_LEN + body.len();
+    let mut msg = Vec::with_capacity(len);
+    msg.extend_from_slice(&(len as u32).to_ne_bytes());
+    msg.extend_from_slice(&ty.to_ne_bytes());
+    msg.extend_from_slice(&flags.to_ne_bytes());
+    msg.extend_from_slice(&1u32.to_ne_bytes());
+    msg.extend_from_slice(&0u32.to_ne_bytes());
+    msg.extend_from_slice(body);
+    msg
+}
+
+/// The type of the netlink message `msg`.
+pub(crate) fn message_type(msg: &[u8]) -> Option<u16> {
+    msg.get(4..6).map(|b| u16::from_ne_bytes([b[0], b[1]]))
+}
+
+/// Append attribute `ty` with payload `data`, padded to 4 bytes.
+pub(crate) fn put_attr(buf: &mut Vec<u8>, ty: u16, data: &[u8]) {
+    buf.extend_from_slice(&((4 + data.len()) as u16).to_ne_bytes());
+    buf.extend_from_slice(&ty.to_ne_bytes());
+    buf.extend_from_slice(data);
+    buf.resize((buf.len() + 3) & !3, 0);
+}
+
+/// Iterate the netlink attributes in `buf` as (type, payload).
+pub(crate) fn attrs_of(mut buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        if buf.len() < 4 {
+            return None;
+        }
+        let len = u16::from_ne_bytes([buf[0], buf[1]]) as usize;
+        let ty = u16::from_ne_bytes([buf[2], buf[3]]) & NLA_TYPE_MASK;
+        if len < 4 || len > buf.len() {
+            return None;
+        }
+        let data = &buf[4..len];
+        buf = &buf[((len + 3) & !3).min(buf.len())..];
+        Some((ty, data))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes_round_trip_with_padding() {
+        let mut buf = Vec::new();
+        put_attr(&mut buf, 2, b"wg0\0");
+        put_attr(&mut buf, 8, &[1, 2, 3]);
+        assert_eq!(buf.len(), 8 + 8);
+        let attrs: Vec<_> = attrs_of(&buf).collect();
+        assert_eq!(attrs, [(2, &b"wg0\0"[..]), (8, &[1, 2, 3][..])]);
+
+        let msg = message(26, 1, &buf);
+        assert_eq!(msg.len(), NLMSG_HDR_LEN + buf.len());
+        assert_eq!(message_type(&msg), Some(26));
+    }
+}
@@ -0,0 +1,221 @@
+use anyhow::{Context, Result};
+use log::debug;
+use std::ffi::CString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::config::EffectiveConfig;
+
+/// Where `ip netns add` creates named network namespaces.
+pub const NETNS_RUN_DIR: &str = "/var/run/netns";
+/// Per-namespace configuration files (`resolv.conf`, ...) that `ip netns
+/// exec` bind-mounts over `/etc`.
+const NETNS_ETC_DIR: &str = "/etc/netns";
+
+/// A network namespace to run the checks in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetnsTarget {
+    /// A name below `/var/run/netns`, as used by `ip netns`.
+    Named(String),
+    /// An absolute path to a namespace file (a bind mount or `/proc/.../ns/net`).
+    Path(PathBuf),
+    /// The namespace of a running process, e.g. a container's init.
+    Pid(u32),
+}
+
+impl NetnsTarget {
+    /// `netns` (a name or absolute path) or `netns_pid` from the merged
+    /// configuration; None when neither is set.
+    pub fn from_effective(eff: &EffectiveConfig) -> Option<Self> {
+        if let Some(pid) = eff.netns_pid {
+            return Some(NetnsTarget::Pid(pid));
+        }
+        eff.netns.as_deref().map(|s| {
+            if s.starts_with('/') {
+                NetnsTarget::Path(PathBuf::from(s))
+            } else {
+                NetnsTarget::Named(s.to_string())
+            }
+        })
+    }
+
+    /// The namespace file to `setns` into.
+    pub fn path(&self) -> PathBuf {
+        match self {
+            NetnsTarget::Named(name) => Path::new(NETNS_RUN_DIR).join(name),
+            NetnsTarget::Path(path) => path.clone(),
+            NetnsTarget::Pid(pid) => PathBuf::from(format!("/proc/{}/ns/net", pid)),
+        }
+    }
+}
+
+impl fmt::Display for NetnsTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetnsTarget::Named(name) => write!(f, "'{}'", name),
+            NetnsTarget::Path(path) => write!(f, "{}", path.display()),
+            NetnsTarget::Pid(pid) => write!(f, "of PID {}", pid),
+        }
+    }
+}
+
+/// Names `ip netns` accepts: non-empty, no `/`, and not `.` or `..`.
+pub fn is_valid_netns_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/')
+}
+
+/// Run `f` on a dedicated thread that has joined `target`'s network
+/// namespace, and return its result.
+///
+/// `setns` only affects the calling thread (and threads it spawns later,
+/// such as the HTTP client's runtime), so the rest of the process stays in
+/// its own namespace. Like `ip netns exec`, the thread also gets a private
+/// mount namespace with a fresh `/sys` (so `/sys/class/net` lists the
+/// namespace's interfaces) and `/etc/netns/<name>/*` bind-mounted over
+/// `/etc`; if that is not permitted, only the network namespace changes.
+pub fn run_in<F, T>(target: &NetnsTarget, f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let target = target.clone();
+    thread::Builder::new()
+        .name("netns-worker".to_string())
+        .spawn(move || {
+            enter(&target)?;
+            f()
+        })
+        .context("failed to spawn network namespace thread")?
+        .join()
+        .map_err(|_| anyhow::anyhow!("network namespace thread panicked"))?
+}
+
+/// Move the calling thread into `target`. Only call this on a thread of
+/// its own; see `run_in`.
+fn enter(target: &NetnsTarget) -> Result<()> {
+    let path = target.path();
+    let ns = File::open(&path)
+        .with_context(|| format!("failed to open network namespace {}", path.display()))?;
+
+    // SAFETY: unshare only changes the calling thread's namespaces.
+    let private_mounts = unsafe { libc::unshare(libc::CLONE_NEWNS) } == 0
+        && mount(None, "/", None, libc::MS_REC | libc::MS_SLAVE).is_ok();
+
+    // SAFETY: `ns` is an open namespace file for the duration of the call.
+    if unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| format!("failed to enter network namespace {}", target));
+    }
+    debug!("Entered network namespace {}", target);
+
+    if !private_mounts {
+        debug!("No private mount namespace; /sys/class/net shows the original namespace");
+        return Ok(());
+    }
+    // SAFETY: the detached /sys only belongs to this thread's mount namespace.
+    unsafe {
+        libc::umount2(c"/sys".as_ptr(), libc::MNT_DETACH);
+    }
+    if let Err(e) = mount(Some("sysfs"), "/sys", Some("sysfs"), 0) {
+        debug!("Failed to mount sysfs in namespace {}: {}", target, e);
+    }
+    if let NetnsTarget::Named(name) = target {
+        bind_etc_files(name);
+    }
+    Ok(())
+}
+
+/// Bind-mount each file in `/etc/netns/<name>` over its `/etc` counterpart.
+fn bind_etc_files(name: &str) {
+    let Ok(entries) = fs::read_dir(Path::new(NETNS_ETC_DIR).join(name)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let src = entry.path();
+        let dst = Path::new("/etc").join(entry.file_name());
+        let (Some(s), Some(d)) = (src.to_str(), dst.to_str()) else {
+            continue;
+        };
+        if let Err(e) = mount(Some(s), d, None, libc::MS_BIND) {
+            debug!("Failed to bind {} over {}: {}", s, d, e);
+        }
+    }
+}
+
+fn mount(
+    source: Option<&str>,
+    target: &str,
+    fstype: Option<&str>,
+    flags: libc::c_ulong,
+) -> io::Result<()> {
+    let cstr =
+        |s: &str| CString::new(s).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e));
+    let source = source.map(cstr).transpose()?;
+    let target = cstr(target)?;
+    let fstype = fstype.map(cstr).transpose()?;
+    let ptr = |s: &Option<CString>| s.as_ref().map_or(std::ptr::null(), |s| s.as_ptr());
+    // SAFETY: every pointer is null or a NUL-terminated string that outlives the call.
+    let rc = unsafe {
+        libc::mount(
+            ptr(&source),
+            target.as_ptr(),
+            ptr(&fstype),
+            flags,
+            std::ptr::null(),
+        )
+    };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_paths() {
+        let eff = EffectiveConfig {
+            netns: Some("vpn".to_string()),
+            ..Default::default()
+        };
+        let target = NetnsTarget::from_effective(&eff).unwrap();
+        assert_eq!(target.path(), PathBuf::from("/var/run/netns/vpn"));
+
+        let eff = EffectiveConfig {
+            netns: Some("/run/docker/netns/1a2b".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            NetnsTarget::from_effective(&eff),
+            Some(NetnsTarget::Path(PathBuf::from("/run/docker/netns/1a2b")))
+        );
+
+        let eff = EffectiveConfig {
+            netns_pid: Some(4242),
+            ..Default::default()
+        };
+        let target = NetnsTarget::from_effective(&eff).unwrap();
+        assert_eq!(target.path(), PathBuf::from("/proc/4242/ns/net"));
+        assert_eq!(target.to_string(), "of PID 4242");
+
+        assert_eq!(
+            NetnsTarget::from_effective(&EffectiveConfig::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn netns_names() {
+        assert!(is_valid_netns_name("vpn"));
+        assert!(!is_valid_netns_name(""));
+        assert!(!is_valid_netns_name(".."));
+        assert!(!is_valid_netns_name("a/b"));
+    }
+}
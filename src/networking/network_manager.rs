@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fmt;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
+
+/// Well-known bus name of NetworkManager.
+pub const NM_BUS_NAME: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const NM_SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+const ACTIVE_IFACE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+
+/// `NMActiveConnectionState` of an active connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActiveState {
+    Unknown,
+    Activating,
+    Activated,
+    Deactivating,
+    Deactivated,
+}
+
+impl From<u32> for ActiveState {
+    fn from(v: u32) -> Self {
+        match v {
+            1 => ActiveState::Activating,
+            2 => ActiveState::Activated,
+            3 => ActiveState::Deactivating,
+            4 => ActiveState::Deactivated,
+            _ => ActiveState::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for ActiveState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ActiveState::Unknown => "unknown",
+            ActiveState::Activating => "activating",
+            ActiveState::Activated => "activated",
+            ActiveState::Deactivating => "deactivating",
+            ActiveState::Deactivated => "deactivated",
+        })
+    }
+}
+
+/// One entry of NetworkManager's `ActiveConnections`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveConnection {
+    /// Profile name, as shown by `nmcli connection`.
+    pub id: String,
+    pub uuid: String,
+    /// Connection type, e.g. `vpn` or `wireguard`.
+    pub kind: String,
+    pub state: ActiveState,
+}
+
+impl ActiveConnection {
+    /// Whether this connection is the profile `name` (its id or UUID).
+    pub fn is_named(&self, name: &str) -> bool {
+        self.id == name || self.uuid == name
+    }
+}
+
+/// Read every active connection from NetworkManager over `conn`.
+pub fn active_connections(conn: &Connection) -> Result<Vec<ActiveConnection>> {
+    let nm = Proxy::new(conn, NM_BUS_NAME, NM_PATH, NM_BUS_NAME)?;
+    let paths: Vec<OwnedObjectPath> = nm
+        .get_property("ActiveConnections")
+        .context("failed to read NetworkManager ActiveConnections")?;
+    paths
+        .iter()
+        .map(|path| {
+            let active = Proxy::new(conn, NM_BUS_NAME, path.as_ref(), ACTIVE_IFACE)?;
+            let state: u32 = active.get_property("State")?;
+            Ok(ActiveConnection {
+                id: active.get_property("Id")?,
+                uuid: active.get_property("Uuid")?,
+                kind: active.get_property("Type")?,
+                state: state.into(),
+            })
+        })
+        .collect::<Result<_>>()
+        .context("failed to read an active connection")
+}
+
+/// Describe why the profile `name` is not up, given the active connections.
+/// None when it is activated or still activating, so a connection that is
+/// coming up is not activated again.
+pub fn vpn_down_reason(name: &str, active: &[ActiveConnection]) -> Option<String> {
+    match active.iter().find(|c| c.is_named(name)) {
+        None => Some(format!(
+            "NetworkManager connection '{}' is not active",
+            name
+        )),
+        Some(c) if !matches!(c.state, ActiveState::Activated | ActiveState::Activating) => {
+            Some(format!(
+                "NetworkManager connection '{}' ({}) is {}",
+                c.id, c.kind, c.state
+            ))
+        }
+        Some(_) => None,
+    }
+}
+
+/// Activate the saved profile `name` (id or UUID) with `ActivateConnection`
+/// and return the path of the new active connection. NetworkManager picks
+/// the device, as `nmcli connection up` does.
+pub fn activate(conn: &Connection, name: &str) -> Result<OwnedObjectPath> {
+    let profile = find_profile(conn, name)?;
+    let nm = Proxy::new(conn, NM_BUS_NAME, NM_PATH, NM_BUS_NAME)?;
+    let root = ObjectPath::try_from("/")?;
+    let active: OwnedObjectPath = nm
+        .call("ActivateConnection", &(&profile, &root, &root))
+        .with_context(|| format!("ActivateConnection for '{}' failed", name))?;
+    Ok(active)
+}
+
+/// Path of the saved connection whose `connection.id` or `connection.uuid`
+/// is `name`.
+fn find_profile(conn: &Connection, name: &str) -> Result<OwnedObjectPath> {
+    let settings = Proxy::new(
+        conn,
+        NM_BUS_NAME,
+        NM_SETTINGS_PATH,
+        "org.freedesktop.NetworkManager.Settings",
+    )?;
+    let paths: Vec<OwnedObjectPath> = settings
+        .call("ListConnections", &())
+        .context("failed to list NetworkManager connections")?;
+    for path in paths {
+        let profile = Proxy::new(
+            conn,
+            NM_BUS_NAME,
+            path.as_ref(),
+            "org.freedesktop.NetworkManager.Settings.Connection",
+        )?;
+        let settings: HashMap<String, HashMap<String, OwnedValue>> =
+            profile.call("GetSettings", &())?;
+        let field = |key: &str| -> Option<String> {
+            let value = settings.get("connection")?.get(key)?;
+            <&str>::try_from(&**value).ok().map(str::to_string)
+        };
+        if field("id").as_deref() == Some(name) || field("uuid").as_deref() == Some(name) {
+            return Ok(path.clone());
+        }
+    }
+    anyhow::bail!("no NetworkManager connection named '{}'", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conn(id: &str, state: u32) -> ActiveConnection {
+        ActiveConnection {
+            id: id.to_string(),
+            uuid: "4b8a7c2e-0f1d-4d35-9c2a-6f0e5b1d2a77".to_string(),
+            kind: "vpn".to_string(),
+            state: state.into(),
+        }
+    }
+
+    #[test]
+    fn down_reasons() {
+        let active = [conn("Wired", 2), conn("work-vpn", 4)];
+        assert_eq!(
+            vpn_down_reason("work-vpn", &active).unwrap(),
+            "NetworkManager connection 'work-vpn' (vpn) is deactivated"
+        );
+        assert_eq!(vpn_down_reason("work-vpn", &[conn("work-vpn", 1)]), None);
+        assert!(vpn_down_reason("home-vpn", &active)
+            .unwrap()
+            .contains("is not active"));
+        assert_eq!(vpn_down_reason("Wired", &active), None);
+        assert_eq!(
+            vpn_down_reason("4b8a7c2e-0f1d-4d35-9c2a-6f0e5b1d2a77", &active[..1]),
+            None
+        );
+    }
+}
@@ -0,0 +1,392 @@
+use anyhow::{Context, Result};
+use log::debug;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Read/write timeout for management interface commands.
+pub const MANAGEMENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long OpenVPN may stay in a connecting state (`WAIT`, `AUTH`,
+/// `GET_CONFIG`, `ASSIGN_IP`, ...) before the connection counts as lost.
+pub const CONNECTING_GRACE: Duration = Duration::from_secs(120);
+
+/// Where OpenVPN's `management` directive listens: `host:port` for TCP, or
+/// an absolute path (optionally prefixed with `unix:`) for a unix socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManagementAddr {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl FromStr for ManagementAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let path = s.strip_prefix("unix:").unwrap_or(s);
+        if path.starts_with('/') {
+            return Ok(ManagementAddr::Unix(PathBuf::from(path)));
+        }
+        match s.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(ManagementAddr::Tcp(s.to_string()))
+            }
+            _ => Err(format!(
+                "'{}' is neither host:port nor an absolute unix socket path",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ManagementAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManagementAddr::Tcp(addr) => write!(f, "{}", addr),
+            ManagementAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Signals OpenVPN accepts over the management interface to reconnect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// Conditional restart: reconnect without re-reading the config.
+    Usr1,
+    /// Hard restart: re-read the config and reconnect.
+    Hup,
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Signal::Usr1 => "SIGUSR1",
+            Signal::Hup => "SIGHUP",
+        })
+    }
+}
+
+/// Current state as reported by the `state` command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenVpnState {
+    /// When OpenVPN entered this state.
+    pub since: SystemTime,
+    /// `CONNECTED`, `RECONNECTING`, `EXITING`, `WAIT`, `AUTH`, ...
+    pub name: String,
+    /// Optional detail, e.g. `SUCCESS` or the reason for a reconnect.
+    pub description: String,
+    /// Tunnel address assigned to this client.
+    pub local_ip: Option<IpAddr>,
+    pub remote: Option<SocketAddr>,
+}
+
+impl OpenVpnState {
+    pub fn is_connected(&self) -> bool {
+        self.name == "CONNECTED"
+    }
+
+    /// The tunnel is gone: OpenVPN is reconnecting or exiting, or has been
+    /// trying to connect for longer than `CONNECTING_GRACE` at `now`. A
+    /// connection that is still coming up is not lost, so a reconnect
+    /// action does not interrupt it.
+    pub fn is_lost(&self, now: SystemTime) -> bool {
+        match self.name.as_str() {
+            "CONNECTED" => false,
+            "RECONNECTING" | "EXITING" => true,
+            _ => now
+                .duration_since(self.since)
+                .is_ok_and(|age| age > CONNECTING_GRACE),
+        }
+    }
+}
+
+impl fmt::Display for OpenVpnState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.description.is_empty() {
+            write!(f, " ({})", self.description)?;
+        }
+        if let Some(remote) = self.remote {
+            write!(f, " to {}", remote)?;
+        }
+        Ok(())
+    }
+}
+
+/// Bytes moved through the tunnel since OpenVPN connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ByteCount {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+impl fmt::Display for ByteCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rx {} B, tx {} B", self.rx_bytes, self.tx_bytes)
+    }
+}
+
+/// Parse one line of `state` output: `time,state,description,local_ip,
+/// remote_ip,remote_port,...`. Trailing fields may be empty or missing.
+pub fn parse_state_line(line: &str) -> Result<OpenVpnState> {
+    let cols: Vec<&str> = line.split(',').collect();
+    if cols.len() < 2 {
+        anyhow::bail!("malformed state line '{}'", line);
+    }
+    let secs: u64 = cols[0]
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid time in state line '{}'", line))?;
+    let col = |i: usize| cols.get(i).copied().unwrap_or("");
+    let remote = match (col(4).parse::<IpAddr>(), col(5).parse::<u16>()) {
+        (Ok(ip), Ok(port)) => Some(SocketAddr::new(ip, port)),
+        _ => None,
+    };
+    Ok(OpenVpnState {
+        since: UNIX_EPOCH + Duration::from_secs(secs),
+        name: cols[1].to_string(),
+        description: col(2).to_string(),
+        local_ip: col(3).parse().ok(),
+        remote,
+    })
+}
+
+/// Parse a `>BYTECOUNT:rx,tx` notification.
+pub fn parse_bytecount(line: &str) -> Option<ByteCount> {
+    let (rx, tx) = line.strip_prefix(">BYTECOUNT:")?.split_once(',')?;
+    Some(ByteCount {
+        rx_bytes: rx.trim().parse().ok()?,
+        tx_bytes: tx.trim().parse().ok()?,
+    })
+}
+
+trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+/// A connection to OpenVPN's management interface.
+///
+/// Real-time notifications (lines starting with `>`) can arrive between any
+/// two lines of a reply; they are skipped unless a command waits for one.
+pub struct Management {
+    conn: BufReader<Box<dyn Stream>>,
+}
+
+impl Management {
+    /// Connect to `addr` and read the `>INFO:` greeting.
+    pub fn connect(addr: &ManagementAddr, timeout: Duration) -> Result<Self> {
+        let stream: Box<dyn Stream> = match addr {
+            ManagementAddr::Tcp(a) => {
+                let sa = a
+                    .to_socket_addrs()
+                    .with_context(|| format!("failed to resolve {}", a))?
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("{} resolved to no addresses", a))?;
+                let s = TcpStream::connect_timeout(&sa, timeout)
+                    .with_context(|| format!("failed to connect to {}", addr))?;
+                s.set_read_timeout(Some(timeout))?;
+                s.set_write_timeout(Some(timeout))?;
+                Box::new(s)
+            }
+            ManagementAddr::Unix(path) => {
+                let s = UnixStream::connect(path)
+                    .with_context(|| format!("failed to connect to {}", addr))?;
+                s.set_read_timeout(Some(timeout))?;
+                s.set_write_timeout(Some(timeout))?;
+                Box::new(s)
+            }
+        };
+        let mut mgmt = Management {
+            conn: BufReader::new(stream),
+        };
+        let greeting = mgmt.read_line().map_err(|e| {
+            if e.to_string().contains("ENTER PASSWORD:") {
+                anyhow::anyhow!(
+                    "management interface at {} asks for a password, which is not supported",
+                    addr
+                )
+            } else {
+                e.context(format!("no greeting from {}", addr))
+            }
+        })?;
+        if !greeting.starts_with(">INFO:") {
+            anyhow::bail!("unexpected greeting from {}: '{}'", addr, greeting);
+        }
+        debug!("OpenVPN management {}: {}", addr, greeting);
+        Ok(mgmt)
+    }
+
+    /// Current state of the connection.
+    pub fn state(&mut self) -> Result<OpenVpnState> {
+        let lines = self.multiline("state")?;
+        let last = lines
+            .last()
+            .ok_or_else(|| anyhow::anyhow!("empty reply to 'state'"))?;
+        parse_state_line(last)
+    }
+
+    /// Tunnel byte counters. OpenVPN only reports them as notifications, so
+    /// this enables them at a 1 second interval, waits for the first one and
+    /// turns them off again.
+    pub fn bytecount(&mut self) -> Result<ByteCount> {
+        self.command("bytecount 1")?;
+        let count = loop {
+            let line = self.read_line()?;
+            if let Some(count) = parse_bytecount(&line) {
+                break count;
+            }
+        };
+        self.command("bytecount 0")?;
+        Ok(count)
+    }
+
+    /// Ask OpenVPN to restart the connection.
+    pub fn signal(&mut self, signal: Signal) -> Result<()> {
+        self.command(&format!("signal {}", signal)).map(|_| ())
+    }
+
+    /// Send a command with a one-line `SUCCESS:`/`ERROR:` reply and return
+    /// the text after `SUCCESS:`.
+    fn command(&mut self, cmd: &str) -> Result<String> {
+        self.send(cmd)?;
+        loop {
+            let line = self.read_line()?;
+            if let Some(msg) = line.strip_prefix("SUCCESS:") {
+                return Ok(msg.trim().to_string());
+            }
+            if let Some(msg) = line.strip_prefix("ERROR:") {
+                anyhow::bail!("'{}' failed: {}", cmd, msg.trim());
+            }
+            if !line.starts_with('>') {
+                anyhow::bail!("unexpected reply to '{}': '{}'", cmd, line);
+            }
+        }
+    }
+
+    /// Send a command whose reply is a list of lines terminated by `END`.
+    fn multiline(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.send(cmd)?;
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_line()?;
+            if line == "END" {
+                return Ok(lines);
+            }
+            if let Some(msg) = line.strip_prefix("ERROR:") {
+                anyhow::bail!("'{}' failed: {}", cmd, msg.trim());
+            }
+            if !line.starts_with('>') {
+                lines.push(line);
+            }
+        }
+    }
+
+    fn send(&mut self, cmd: &str) -> Result<()> {
+        let stream = self.conn.get_mut();
+        stream.write_all(format!("{}\n", cmd).as_bytes())?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        let mut buf = Vec::new();
+        let res = self.conn.read_until(b'\n', &mut buf);
+        let text = String::from_utf8_lossy(&buf);
+        match res {
+            Ok(0) => anyhow::bail!("connection closed"),
+            Ok(_) => Ok(text.trim_end().to_string()),
+            // The password prompt has no newline, so show what arrived.
+            Err(e) if !buf.is_empty() => Err(anyhow::anyhow!("{} after '{}'", e, text.trim_end())),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Connect to `addr` and read the state, plus the byte counters while
+/// connected. The counters are best-effort: OpenVPN sends no notification
+/// while it is not connected, and a missing one must not hide the state.
+pub fn query(
+    addr: &ManagementAddr,
+    timeout: Duration,
+) -> Result<(OpenVpnState, Option<ByteCount>)> {
+    let mut mgmt = Management::connect(addr, timeout)?;
+    let state = mgmt.state()?;
+    if !state.is_connected() {
+        return Ok((state, None));
+    }
+    let count = mgmt
+        .bytecount()
+        .map_err(|e| debug!("No OpenVPN byte count from {}: {:#}", addr, e))
+        .ok();
+    Ok((state, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn management_addresses() {
+        assert_eq!(
+            "127.0.0.1:7505".parse(),
+            Ok(ManagementAddr::Tcp("127.0.0.1:7505".to_string()))
+        );
+        assert_eq!(
+            "unix:/run/openvpn/client.sock".parse(),
+            Ok(ManagementAddr::Unix(PathBuf::from(
+                "/run/openvpn/client.sock"
+            )))
+        );
+        assert_eq!(
+            "/run/openvpn/client.sock".parse::<ManagementAddr>(),
+            "unix:/run/openvpn/client.sock".parse()
+        );
+        assert!("localhost".parse::<ManagementAddr>().is_err());
+        assert!("run/client.sock".parse::<ManagementAddr>().is_err());
+    }
+
+    #[test]
+    fn state_lines() {
+        let s =
+            parse_state_line("1760690000,CONNECTED,SUCCESS,10.8.0.6,198.51.100.7,1194,,").unwrap();
+        assert!(s.is_connected());
+        assert_eq!(s.local_ip, Some("10.8.0.6".parse().unwrap()));
+        assert_eq!(s.to_string(), "CONNECTED (SUCCESS) to 198.51.100.7:1194");
+
+        let s = parse_state_line("1760690042,RECONNECTING,ping-restart,,,,,").unwrap();
+        assert!(!s.is_connected());
+        assert_eq!(s.remote, None);
+        assert_eq!(s.to_string(), "RECONNECTING (ping-restart)");
+
+        assert!(parse_state_line("CONNECTED").is_err());
+    }
+
+    #[test]
+    fn connecting_is_not_lost_until_the_grace_ends() {
+        let s = parse_state_line("1760690000,AUTH,,,,,,").unwrap();
+        assert!(!s.is_connected());
+        assert!(!s.is_lost(s.since + Duration::from_secs(10)));
+        assert!(s.is_lost(s.since + CONNECTING_GRACE + Duration::from_secs(1)));
+
+        let s = parse_state_line("1760690042,RECONNECTING,ping-restart,,,,,").unwrap();
+        assert!(s.is_lost(s.since));
+        let s = parse_state_line("1760690000,CONNECTED,SUCCESS,10.8.0.6,,,,").unwrap();
+        assert!(!s.is_lost(s.since + CONNECTING_GRACE * 10));
+    }
+
+    #[test]
+    fn bytecount_notifications() {
+        assert_eq!(
+            parse_bytecount(">BYTECOUNT:1843622,502340"),
+            Some(ByteCount {
+                rx_bytes: 1_843_622,
+                tx_bytes: 502_340
+            })
+        );
+        assert_eq!(parse_bytecount(">BYTECOUNT_CLI:3,1,2"), None);
+        assert_eq!(parse_bytecount(">INFO:hello"), None);
+    }
+}
@@ -0,0 +1,233 @@
+use anyhow::{Context, Result};
+use log::debug;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::OwnedObjectPath;
+
+/// Where the system resolver configuration is read from by default.
+pub const DEFAULT_RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// Stub listeners of systemd-resolved; when resolved answers over D-Bus its
+/// upstream servers are checked instead of these.
+const RESOLVED_STUBS: [Ipv4Addr; 2] = [Ipv4Addr::new(127, 0, 0, 53), Ipv4Addr::new(127, 0, 0, 54)];
+
+const RESOLVED_BUS_NAME: &str = "org.freedesktop.resolve1";
+const RESOLVED_PATH: &str = "/org/freedesktop/resolve1";
+const RESOLVED_MANAGER: &str = "org.freedesktop.resolve1.Manager";
+const RESOLVED_LINK: &str = "org.freedesktop.resolve1.Link";
+
+const AF_INET: i32 = 2;
+const AF_INET6: i32 = 10;
+
+/// Addresses of the `nameserver` lines in resolv.conf contents. IPv6
+/// zone suffixes (`%eth0`) are dropped; malformed lines are ignored like
+/// the libc resolver does.
+pub fn parse_resolv_conf(text: &str) -> Vec<IpAddr> {
+    text.lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            if words.next()? != "nameserver" {
+                return None;
+            }
+            let addr = words.next()?;
+            addr.split('%').next()?.parse().ok()
+        })
+        .collect()
+}
+
+/// Convert systemd-resolved's `DNS` property (`a(iiay)`: ifindex, address
+/// family, address bytes) into addresses, skipping unknown families.
+pub fn parse_resolved_dns(entries: &[(i32, i32, Vec<u8>)]) -> Vec<IpAddr> {
+    entries
+        .iter()
+        .filter_map(|(_ifindex, family, bytes)| parse_address(*family, bytes))
+        .collect()
+}
+
+fn parse_address(family: i32, bytes: &[u8]) -> Option<IpAddr> {
+    match family {
+        AF_INET => <[u8; 4]>::try_from(bytes)
+            .ok()
+            .map(|b| Ipv4Addr::from(b).into()),
+        AF_INET6 => <[u8; 16]>::try_from(bytes)
+            .ok()
+            .map(|b| Ipv6Addr::from(b).into()),
+        _ => None,
+    }
+}
+
+/// The DNS configuration systemd-resolved holds for one link. Index 0 is
+/// the global configuration from resolved.conf.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolvedLink {
+    pub ifindex: i32,
+    pub servers: Vec<IpAddr>,
+    /// Search and routing (`~`) domains; `.` routes every name here.
+    pub domains: Vec<String>,
+    /// Whether names matching no link's domains are sent here.
+    pub default_route: bool,
+}
+
+/// The servers that can receive ordinary queries, following resolved's
+/// routing: if any link routes `.` (e.g. a VPN with `~.`), queries go only
+/// to such links; otherwise to every link with `default_route`. Servers of
+/// links that only answer for their own domains are left out.
+pub fn routed_servers(links: &[ResolvedLink]) -> Vec<IpAddr> {
+    let routes_all = |l: &&ResolvedLink| l.domains.iter().any(|d| d == ".");
+    let used: Vec<&ResolvedLink> = if links.iter().any(|l| routes_all(&l)) {
+        links.iter().filter(routes_all).collect()
+    } else {
+        links.iter().filter(|l| l.default_route).collect()
+    };
+    let mut servers = Vec::new();
+    for ip in used.iter().flat_map(|l| &l.servers) {
+        if !servers.contains(ip) {
+            servers.push(*ip);
+        }
+    }
+    servers
+}
+
+/// Read the global and per-link DNS configuration from systemd-resolved
+/// over `conn`: servers and domains from the manager, and each link's
+/// `DefaultRoute` through `GetLink`.
+pub fn resolved_links(conn: &Connection) -> Result<Vec<ResolvedLink>> {
+    let manager = Proxy::new(conn, RESOLVED_BUS_NAME, RESOLVED_PATH, RESOLVED_MANAGER)?;
+    let dns: Vec<(i32, i32, Vec<u8>)> = manager
+        .get_property("DNS")
+        .context("failed to read systemd-resolved DNS servers")?;
+    let domains: Vec<(i32, String, bool)> = manager
+        .get_property("Domains")
+        .context("failed to read systemd-resolved domains")?;
+
+    let mut links: Vec<ResolvedLink> = Vec::new();
+    for (ifindex, family, bytes) in &dns {
+        if let Some(ip) = parse_address(*family, bytes) {
+            link_entry(&mut links, *ifindex).servers.push(ip);
+        }
+    }
+    for (ifindex, domain, _route_only) in domains {
+        link_entry(&mut links, ifindex).domains.push(domain);
+    }
+
+    for l in links.iter_mut().filter(|l| l.ifindex != 0) {
+        let path: OwnedObjectPath = manager
+            .call("GetLink", &(l.ifindex))
+            .with_context(|| format!("failed to get resolved link {}", l.ifindex))?;
+        let proxy = Proxy::new(conn, RESOLVED_BUS_NAME, path.as_ref(), RESOLVED_LINK)?;
+        // Older resolved versions lack the property; they route by default.
+        l.default_route = proxy.get_property("DefaultRoute").unwrap_or(true);
+    }
+    Ok(links)
+}
+
+fn link_entry(links: &mut Vec<ResolvedLink>, ifindex: i32) -> &mut ResolvedLink {
+    let idx = match links.iter().position(|l| l.ifindex == ifindex) {
+        Some(idx) => idx,
+        None => {
+            links.push(ResolvedLink {
+                ifindex,
+                default_route: true,
+                ..Default::default()
+            });
+            links.len() - 1
+        }
+    };
+    &mut links[idx]
+}
+
+/// The servers systemd-resolved sends ordinary queries to (see
+/// `routed_servers`), read over the system bus.
+pub fn resolved_dns_servers() -> Result<Vec<IpAddr>> {
+    let conn = super::system_bus()?;
+    Ok(routed_servers(&resolved_links(&conn)?))
+}
+
+/// The resolvers the system is using: the `nameserver` entries of
+/// `resolv_conf_path` plus, when systemd-resolved is running, the upstream
+/// servers it routes queries to (in which case its local stub addresses
+/// are left out).
+pub fn active_resolvers(resolv_conf_path: &str) -> Result<Vec<IpAddr>> {
+    let text = fs::read_to_string(resolv_conf_path)
+        .with_context(|| format!("failed to read {}", resolv_conf_path))?;
+    let mut resolvers = parse_resolv_conf(&text);
+
+    match resolved_dns_servers() {
+        Ok(upstream) => {
+            resolvers.retain(|ip| !is_resolved_stub(ip));
+            for ip in upstream {
+                if !resolvers.contains(&ip) {
+                    resolvers.push(ip);
+                }
+            }
+        }
+        Err(e) => debug!("systemd-resolved not queried: {:#}", e),
+    }
+    Ok(resolvers)
+}
+
+fn is_resolved_stub(ip: &IpAddr) -> bool {
+    matches!(ip, IpAddr::V4(v4) if RESOLVED_STUBS.contains(v4))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nameserver_lines() {
+        let text = "# generated\nsearch lan\nnameserver 10.8.0.1\nnameserver  fe80::1%eth0\n\
+                    nameserver bogus\n;nameserver 1.1.1.1\noptions edns0\n";
+        assert_eq!(
+            parse_resolv_conf(text),
+            vec![
+                "10.8.0.1".parse::<IpAddr>().unwrap(),
+                "fe80::1".parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_resolved_property() {
+        let entries = vec![
+            (3, AF_INET, vec![10, 8, 0, 1]),
+            (0, AF_INET6, Ipv6Addr::LOCALHOST.octets().to_vec()),
+            (2, 99, vec![1, 2, 3, 4]),
+            (2, AF_INET, vec![1, 2, 3]),
+        ];
+        assert_eq!(
+            parse_resolved_dns(&entries),
+            vec![
+                "10.8.0.1".parse::<IpAddr>().unwrap(),
+                IpAddr::V6(Ipv6Addr::LOCALHOST)
+            ]
+        );
+    }
+
+    fn link(ifindex: i32, server: &str, domains: &[&str], default_route: bool) -> ResolvedLink {
+        ResolvedLink {
+            ifindex,
+            servers: vec![server.parse().unwrap()],
+            domains: domains.iter().map(|d| d.to_string()).collect(),
+            default_route,
+        }
+    }
+
+    #[test]
+    fn catch_all_link_takes_every_query() {
+        let wired = link(2, "192.168.1.1", &["lan"], true);
+        let vpn = link(5, "10.8.0.1", &["."], true);
+        assert_eq!(
+            routed_servers(&[wired.clone(), vpn]),
+            vec!["10.8.0.1".parse::<IpAddr>().unwrap()]
+        );
+
+        // Without `~.`, every default-route link gets queries.
+        let split = link(5, "10.8.0.1", &["corp.example"], false);
+        assert_eq!(
+            routed_servers(&[wired, split]),
+            vec!["192.168.1.1".parse::<IpAddr>().unwrap()]
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+/// IPv4 routing table of the main table. `/proc/net` shows the main
+/// thread's network namespace, `thread-self` the calling thread's (see
+/// `netns::run_in`).
+pub const PROC_NET_ROUTE: &str = "/proc/thread-self/net/route";
+/// IPv6 routing table of the main table.
+pub const PROC_NET_IPV6_ROUTE: &str = "/proc/thread-self/net/ipv6_route";
+
+/// `RTF_UP` from `<linux/route.h>`.
+const RTF_UP: u32 = 0x1;
+
+/// Where traffic to a destination leaves the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteInfo {
+    /// Outgoing interface.
+    pub interface: String,
+    /// Next hop, or None for directly connected destinations.
+    pub gateway: Option<IpAddr>,
+}
+
+impl std::fmt::Display for RouteInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.gateway {
+            Some(gw) => write!(f, "{} via gateway {}", self.interface, gw),
+            None => write!(f, "{} (no gateway)", self.interface),
+        }
+    }
+}
+
+/// One entry of a `/proc/net/route` or `/proc/net/ipv6_route` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteEntry {
+    pub interface: String,
+    pub destination: IpAddr,
+    pub prefix_len: u8,
+    pub gateway: Option<IpAddr>,
+    pub metric: u32,
+}
+
+/// The route the kernel would use for `dst`.
+///
+/// Asks the kernel over netlink (`RTM_GETROUTE`, like `ip route get`), which
+/// honours policy routing such as wg-quick's fwmark table. Falls back to a
+/// longest-prefix match over `/proc/net/route` / `/proc/net/ipv6_route`
+/// (main table only) when netlink is unavailable.
+pub fn route_to(dst: IpAddr) -> Result<RouteInfo> {
+    match rtnetlink::route_get(dst) {
+        Ok(route) => Ok(route),
+        Err(e)
+            if matches!(
+                e.raw_os_error(),
+                Some(libc::ENETUNREACH | libc::EHOSTUNREACH)
+            ) =>
+        {
+            Err(e).with_context(|| format!("no route to {}", dst))
+        }
+        Err(e) => {
+            log::debug!("netlink route lookup failed ({}), reading /proc", e);
+            let table = if dst.is_ipv4() {
+                PROC_NET_ROUTE
+            } else {
+                PROC_NET_IPV6_ROUTE
+            };
+            let entries = read_route_table(Path::new(table))?;
+            lookup(&entries, dst).ok_or_else(|| anyhow::anyhow!("no route to {}", dst))
+        }
+    }
+}
+
+/// Read and parse a `/proc/net/route`- or `/proc/net/ipv6_route`-style file
+/// (the format is detected from the header line).
+pub fn read_route_table(path: &Path) -> Result<Vec<RouteEntry>> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    if text.starts_with("Iface") {
+        Ok(parse_proc_route(&text))
+    } else {
+        Ok(parse_proc_ipv6_route(&text))
+    }
+}
+
+/// Parse `/proc/net/route`. Addresses are little-endian hex; routes that
+/// are not up are skipped.
+pub fn parse_proc_route(text: &str) -> Vec<RouteEntry> {
+    text.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 8 {
+                return None;
+            }
+            let hex = |s: &str| u32::from_str_radix(s, 16).ok();
+            let addr = |s: &str| hex(s).map(|v| IpAddr::V4(Ipv4Addr::from(u32::from_be(v))));
+            let flags = hex(cols[3])?;
+            if flags & RTF_UP == 0 {
+                return None;
+            }
+            let gateway = addr(cols[2]).filter(|g| !g.is_unspecified());
+            let mask = u32::from_be(hex(cols[7])?);
+            Some(RouteEntry {
+                interface: cols[0].to_string(),
+                destination: addr(cols[1])?,
+                prefix_len: mask.leading_ones() as u8,
+                gateway,
+                metric: cols[6].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Parse `/proc/net/ipv6_route`: destination, prefix length, source,
+/// source prefix length, next hop, metric, refcount, use, flags, interface.
+pub fn parse_proc_ipv6_route(text: &str) -> Vec<RouteEntry> {
+    text.lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 10 {
+                return None;
+            }
+            let addr = |s: &str| u128::from_str_radix(s, 16).ok().map(Ipv6Addr::from);
+            let flags = u32::from_str_radix(cols[8], 16).ok()?;
+            // Skip routes that are down and the kernel's reject routes on lo.
+            if flags & RTF_UP == 0 || (cols[9] == "lo" && flags & 0x0200 != 0) {
+                return None;
+            }
+            let gateway = addr(cols[4]).filter(|g| !g.is_unspecified());
+            Some(RouteEntry {
+                interface: cols[9].to_string(),
+                destination: IpAddr::V6(addr(cols[0])?),
+                prefix_len: u8::from_str_radix(cols[1], 16).ok()?,
+                gateway: gateway.map(IpAddr::V6),
+                metric: u32::from_str_radix(cols[5], 16).ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Longest-prefix match over `entries`, preferring the lowest metric among
+/// equally specific routes.
+pub fn lookup(entries: &[RouteEntry], dst: IpAddr) -> Option<RouteInfo> {
+    entries
+        .iter()
+        .filter(|e| prefix_contains(e.destination, e.prefix_len, dst))
+        .max_by(|a, b| {
+            a.prefix_len
+                .cmp(&b.prefix_len)
+                .then(b.metric.cmp(&a.metric))
+        })
+        .map(|e| RouteInfo {
+            interface: e.interface.clone(),
+            gateway: e.gateway,
+        })
+}
+
+fn prefix_contains(net: IpAddr, len: u8, ip: IpAddr) -> bool {
+    match (net, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => {
+            let mask = u32::MAX
+                .checked_shl(32 - u32::from(len.min(32)))
+                .unwrap_or(0);
+            u32::from(net) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) => {
+            let mask = u128::MAX
+                .checked_shl(128 - u32::from(len.min(128)))
+                .unwrap_or(0);
+            u128::from(net) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Minimal rtnetlink client for a single `RTM_GETROUTE` request.
+mod rtnetlink {
+    use super::RouteInfo;
+    use crate::networking::netlink::{self, Socket, NLMSG_HDR_LEN};
+    use std::ffi::CStr;
+    use std::io;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    const RTM_NEWROUTE: u16 = 24;
+    const RTM_GETROUTE: u16 = 26;
+    const RTA_DST: u16 = 1;
+    const RTA_OIF: u16 = 4;
+    const RTA_GATEWAY: u16 = 5;
+    const RTMSG_LEN: usize = 12;
+
+    fn invalid() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, "malformed netlink reply")
+    }
+
+    pub(super) fn route_get(dst: IpAddr) -> io::Result<RouteInfo> {
+        let sock = Socket::open(libc::NETLINK_ROUTE)?;
+        sock.send(&build_request(dst))?;
+        let mut route = None;
+        sock.recv_all(|msg| {
+            if route.is_none() && netlink::message_type(msg) == Some(RTM_NEWROUTE) {
+                route = Some(parse_route(msg));
+            }
+        })?;
+        route.unwrap_or_else(|| Err(invalid()))
+    }
+
+    fn build_request(dst: IpAddr) -> Vec<u8> {
+        let (family, addr): (u8, Vec<u8>) = match dst {
+            IpAddr::V4(a) => (libc::AF_INET as u8, a.octets().to_vec()),
+            IpAddr::V6(a) => (libc::AF_INET6 as u8, a.octets().to_vec()),
+        };
+        // rtmsg: family, dst_len, src_len, tos, table, protocol, scope, type, flags
+        let mut body = vec![family, (addr.len() * 8) as u8, 0, 0, 0, 0, 0, 0];
+        body.extend_from_slice(&0u32.to_ne_bytes());
+        netlink::put_attr(&mut body, RTA_DST, &addr);
+        netlink::message(RTM_GETROUTE, libc::NLM_F_REQUEST as u16, &body)
+    }
+
+    fn parse_route(msg: &[u8]) -> io::Result<RouteInfo> {
+        let attrs = msg.get(NLMSG_HDR_LEN + RTMSG_LEN..).ok_or_else(invalid)?;
+        let mut oif = None;
+        let mut gateway = None;
+        for (ty, data) in netlink::attrs_of(attrs) {
+            match (ty, data.len()) {
+                (RTA_OIF, 4) => {
+                    oif = Some(u32::from_ne_bytes([data[0], data[1], data[2], data[3]]))
+                }
+                (RTA_GATEWAY, 4) => {
+                    gateway = Some(IpAddr::V4(Ipv4Addr::new(
+                        data[0], data[1], data[2], data[3],
+                    )))
+                }
+                (RTA_GATEWAY, 16) => {
+                    let octets: [u8; 16] = data.try_into().map_err(|_| invalid())?;
+                    gateway = Some(IpAddr::V6(Ipv6Addr::from(octets)))
+                }
+                _ => {}
+            }
+        }
+
+        let oif = oif.ok_or_else(invalid)?;
+        let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+        // SAFETY: `name` is IF_NAMESIZE bytes as if_indextoname requires.
+        let ptr = unsafe { libc::if_indextoname(oif, name.as_mut_ptr()) };
+        if ptr.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: on success the buffer holds a NUL-terminated name.
+        let interface = unsafe { CStr::from_ptr(name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned();
+        Ok(RouteInfo { interface, gateway })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUTE: &str =
+        "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+tun0\t00000000\t0100080A\t0003\t0\t0\t0\t00000080\t0\t0\t0\n\
+tun0\t00000080\t0100080A\t0003\t0\t0\t0\t00000080\t0\t0\t0\n\
+eth0\t0001A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n\
+wg1\t0000000A\t00000000\t0000\t0\t0\t0\t000000FF\t0\t0\t0\n";
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_ipv4_table() {
+        let entries = parse_proc_route(ROUTE);
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].gateway, Some(ip("192.168.1.1")));
+        assert_eq!(entries[1].prefix_len, 1);
+        assert_eq!(entries[3].destination, ip("192.168.1.0"));
+        assert_eq!(entries[3].gateway, None);
+    }
+
+    #[test]
+    fn openvpn_split_default_wins_over_default_route() {
+        let entries = parse_proc_route(ROUTE);
+        let route = lookup(&entries, ip("8.8.8.8")).unwrap();
+        assert_eq!(route.interface, "tun0");
+        assert_eq!(route.to_string(), "tun0 via gateway 10.8.0.1");
+        let route = lookup(&entries, ip("192.168.1.20")).unwrap();
+        assert_eq!(route.to_string(), "eth0 (no gateway)");
+        // Routes that are not up are ignored.
+        assert_ne!(lookup(&entries, ip("10.0.0.5")).unwrap().interface, "wg1");
+    }
+
+    #[test]
+    fn parses_ipv6_table() {
+        let text = "00000000000000000000000000000000 00 00000000000000000000000000000000 00 fe800000000000000000000000000001 00000400 00000001 00000000 00000003 eth0\n\
+fd000000000000000000000000000000 08 00000000000000000000000000000000 00 00000000000000000000000000000000 00000100 00000001 00000000 00000001 wg0\n\
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 00000000000000000000000000000000 ffffffff 00000001 00000000 00200200 lo\n";
+        let entries = parse_proc_ipv6_route(text);
+        assert_eq!(entries.len(), 2);
+        let route = lookup(&entries, ip("2001:db8::1")).unwrap();
+        assert_eq!(route.interface, "eth0");
+        assert_eq!(route.gateway, Some(ip("fe80::1")));
+        assert_eq!(lookup(&entries, ip("fd00::53")).unwrap().interface, "wg0");
+    }
+
+    #[test]
+    fn netlink_routes_loopback_via_lo() {
+        let route = route_to(ip("127.0.0.1")).unwrap();
+        assert_eq!(route.interface, "lo");
+    }
+}
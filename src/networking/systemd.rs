@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::OwnedObjectPath;
+
+const SYSTEMD_BUS_NAME: &str = "org.freedesktop.systemd1";
+const SYSTEMD_PATH: &str = "/org/freedesktop/systemd1";
+
+/// Load and activation state of a systemd unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitState {
+    pub unit: String,
+    /// `loaded`, `not-found`, `masked`, ...
+    pub load_state: String,
+    /// `active`, `inactive`, `failed`, `activating`, ...
+    pub active_state: String,
+    /// Unit-type specific detail, e.g. `running`, `dead` or `auto-restart`.
+    pub sub_state: String,
+}
+
+impl UnitState {
+    /// The unit does not exist, or has stopped or failed. A unit that is
+    /// (re)starting counts as up.
+    pub fn is_down(&self) -> bool {
+        self.load_state == "not-found"
+            || matches!(self.active_state.as_str(), "inactive" | "failed")
+    }
+}
+
+impl fmt::Display for UnitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.load_state == "not-found" {
+            return write!(f, "unit {} not found", self.unit);
+        }
+        write!(
+            f,
+            "unit {} is {} ({})",
+            self.unit, self.active_state, self.sub_state
+        )
+    }
+}
+
+/// Ask systemd over `conn` for the state of `unit`. `LoadUnit` is used
+/// rather than `GetUnit` so units that are not loaded (stopped and
+/// unreferenced) report `inactive` instead of an error.
+pub fn unit_state(conn: &Connection, unit: &str) -> Result<UnitState> {
+    let manager = Proxy::new(
+        conn,
+        SYSTEMD_BUS_NAME,
+        SYSTEMD_PATH,
+        "org.freedesktop.systemd1.Manager",
+    )?;
+    let path: OwnedObjectPath = manager
+        .call("LoadUnit", &(unit))
+        .with_context(|| format!("failed to load unit {}", unit))?;
+    let proxy = Proxy::new(
+        conn,
+        SYSTEMD_BUS_NAME,
+        path.as_ref(),
+        "org.freedesktop.systemd1.Unit",
+    )?;
+    let read = |name: &str| -> Result<String> {
+        proxy
+            .get_property(name)
+            .with_context(|| format!("failed to read {} of {}", name, unit))
+    };
+    Ok(UnitState {
+        unit: unit.to_string(),
+        load_state: read("LoadState")?,
+        active_state: read("ActiveState")?,
+        sub_state: read("SubState")?,
+    })
+}
+
+/// Unit names systemd accepts: non-empty, at most 255 bytes, no `/` or
+/// whitespace, and with a type suffix such as `.service`.
+pub fn is_valid_unit_name(unit: &str) -> bool {
+    !unit.is_empty()
+        && unit.len() <= 255
+        && !unit.chars().any(|c| c == '/' || c.is_whitespace())
+        && unit
+            .rsplit_once('.')
+            .is_some_and(|(name, suffix)| !name.is_empty() && !suffix.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(load: &str, active: &str, sub: &str) -> UnitState {
+        UnitState {
+            unit: "openvpn-client@work.service".to_string(),
+            load_state: load.to_string(),
+            active_state: active.to_string(),
+            sub_state: sub.to_string(),
+        }
+    }
+
+    #[test]
+    fn down_states() {
+        assert!(!state("loaded", "active", "running").is_down());
+        assert!(!state("loaded", "activating", "auto-restart").is_down());
+        let failed = state("loaded", "failed", "failed");
+        assert!(failed.is_down());
+        assert_eq!(
+            failed.to_string(),
+            "unit openvpn-client@work.service is failed (failed)"
+        );
+        assert!(state("loaded", "inactive", "dead").is_down());
+        let missing = state("not-found", "inactive", "dead");
+        assert!(missing.is_down());
+        assert_eq!(
+            missing.to_string(),
+            "unit openvpn-client@work.service not found"
+        );
+    }
+
+    #[test]
+    fn unit_names() {
+        assert!(is_valid_unit_name("wg-quick@wg0.service"));
+        assert!(is_valid_unit_name("openvpn-client@work.service"));
+        assert!(!is_valid_unit_name("openvpn"));
+        assert!(!is_valid_unit_name("../x.service"));
+        assert!(!is_valid_unit_name("my vpn.service"));
+    }
+}
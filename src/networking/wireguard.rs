@@ -0,0 +1,322 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use log::debug;
+use std::net::SocketAddr;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// WireGuard re-handshakes every 2 minutes while traffic flows; a peer
+/// whose latest handshake is older than this is considered dead.
+pub const DEFAULT_HANDSHAKE_MAX_AGE_SECS: u64 = 180;
+
+/// One peer of a WireGuard interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WgPeer {
+    /// Base64 public key, as shown by `wg`.
+    pub public_key: String,
+    pub endpoint: Option<SocketAddr>,
+    /// None when the peer never completed a handshake.
+    pub latest_handshake: Option<SystemTime>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+impl WgPeer {
+    /// True when the latest handshake is missing or older than `max_age`
+    /// at `now`.
+    pub fn is_stale(&self, now: SystemTime, max_age: Duration) -> bool {
+        match self.latest_handshake {
+            Some(t) => now.duration_since(t).unwrap_or_default() > max_age,
+            None => true,
+        }
+    }
+
+    /// Public key shortened for log messages.
+    pub fn short_key(&self) -> &str {
+        self.public_key.get(..8).unwrap_or(&self.public_key)
+    }
+}
+
+/// Read the peers of WireGuard interface `name`.
+///
+/// Uses the kernel's generic netlink API (needs CAP_NET_ADMIN) and falls
+/// back to parsing `wg show <name> dump` when that is unavailable.
+pub fn read_peers(name: &str) -> Result<Vec<WgPeer>> {
+    match genl::get_peers(name) {
+        Ok(peers) => Ok(peers),
+        Err(e) => {
+            debug!("WireGuard netlink query failed ({}), running wg", e);
+            let out = Command::new("wg")
+                .args(["show", name, "dump"])
+                .output()
+                .context("failed to run 'wg show dump'")?;
+            if !out.status.success() {
+                anyhow::bail!(
+                    "'wg show {} dump' failed: {}",
+                    name,
+                    String::from_utf8_lossy(&out.stderr).trim()
+                );
+            }
+            parse_dump(&String::from_utf8_lossy(&out.stdout))
+        }
+    }
+}
+
+/// Parse `wg show <if> dump` output. The first line describes the interface
+/// itself; every further line is one tab-separated peer: public key,
+/// preshared key, endpoint, allowed IPs, latest handshake (Unix seconds, 0
+/// for never), rx bytes, tx bytes, persistent keepalive.
+pub fn parse_dump(text: &str) -> Result<Vec<WgPeer>> {
+    text.lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(n, line)| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            if cols.len() < 8 {
+                anyhow::bail!(
+                    "peer line {}: expected 8 columns, got {}",
+                    n + 1,
+                    cols.len()
+                );
+            }
+            let num = |i: usize, what: &str| -> Result<u64> {
+                cols[i].parse().map_err(|_| {
+                    anyhow::anyhow!("peer line {}: invalid {} '{}'", n + 1, what, cols[i])
+                })
+            };
+            let handshake = num(4, "latest handshake")?;
+            Ok(WgPeer {
+                public_key: cols[0].to_string(),
+                endpoint: cols[2].parse().ok(),
+                latest_handshake: (handshake > 0)
+                    .then(|| UNIX_EPOCH + Duration::from_secs(handshake)),
+                rx_bytes: num(5, "rx bytes")?,
+                tx_bytes: num(6, "tx bytes")?,
+            })
+        })
+        .collect()
+}
+
+/// Describe why the WireGuard interface `name` looks dead at `now`: it has
+/// no peers, or a peer's latest handshake is missing or older than
+/// `max_age`. None when every peer is fresh.
+pub fn stale_reason(
+    name: &str,
+    peers: &[WgPeer],
+    now: SystemTime,
+    max_age: Duration,
+) -> Option<String> {
+    if peers.is_empty() {
+        return Some(format!("WireGuard interface {} has no peers", name));
+    }
+    let stale: Vec<String> = peers
+        .iter()
+        .filter(|p| p.is_stale(now, max_age))
+        .map(|p| {
+            let age = match p.latest_handshake {
+                Some(t) => format!(
+                    "latest handshake {}s ago",
+                    now.duration_since(t).unwrap_or_default().as_secs()
+                ),
+                None => "no handshake yet".to_string(),
+            };
+            format!(
+                "peer {} {} (rx {} B, tx {} B)",
+                p.short_key(),
+                age,
+                p.rx_bytes,
+                p.tx_bytes
+            )
+        })
+        .collect();
+    if stale.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "WireGuard interface {} is stale: {}",
+        name,
+        stale.join("; ")
+    ))
+}
+
+fn encode_key(key: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(key)
+}
+
+/// Minimal generic netlink client for `WG_CMD_GET_DEVICE`.
+mod genl {
+    use super::{encode_key, WgPeer};
+    use crate::networking::netlink::{self, attrs_of, put_attr, Socket, NLMSG_HDR_LEN};
+    use std::io;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const GENL_ID_CTRL: u16 = 0x10;
+    const CTRL_CMD_GETFAMILY: u8 = 3;
+    const CTRL_ATTR_FAMILY_ID: u16 = 1;
+    const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+    const WG_CMD_GET_DEVICE: u8 = 0;
+    const WG_GENL_VERSION: u8 = 1;
+    const WGDEVICE_A_IFNAME: u16 = 2;
+    const WGDEVICE_A_PEERS: u16 = 8;
+    const WGPEER_A_PUBLIC_KEY: u16 = 1;
+    const WGPEER_A_ENDPOINT: u16 = 4;
+    const WGPEER_A_LAST_HANDSHAKE_TIME: u16 = 6;
+    const WGPEER_A_RX_BYTES: u16 = 8;
+    const WGPEER_A_TX_BYTES: u16 = 9;
+    const GENL_HDR_LEN: usize = 4;
+
+    pub(super) fn get_peers(name: &str) -> io::Result<Vec<WgPeer>> {
+        let sock = Socket::open(libc::NETLINK_GENERIC)?;
+        let family = resolve_family(&sock)?;
+
+        let mut attrs = Vec::new();
+        put_attr(&mut attrs, WGDEVICE_A_IFNAME, &nul_terminated(name));
+        sock.send(&message(
+            family,
+            (libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16,
+            WG_CMD_GET_DEVICE,
+            WG_GENL_VERSION,
+            &attrs,
+        ))?;
+
+        let mut peers: Vec<WgPeer> = Vec::new();
+        recv_payloads(&sock, |payload| {
+            for (ty, data) in attrs_of(payload) {
+                if ty != WGDEVICE_A_PEERS {
+                    continue;
+                }
+                for (_, peer) in attrs_of(data) {
+                    let peer = parse_peer(peer);
+                    // Large peers are split across messages; keep the first part.
+                    if !peers.iter().any(|p| p.public_key == peer.public_key) {
+                        peers.push(peer);
+                    }
+                }
+            }
+        })?;
+        Ok(peers)
+    }
+
+    fn resolve_family(sock: &Socket) -> io::Result<u16> {
+        let mut attrs = Vec::new();
+        put_attr(&mut attrs, CTRL_ATTR_FAMILY_NAME, b"wireguard\0");
+        sock.send(&message(
+            GENL_ID_CTRL,
+            libc::NLM_F_REQUEST as u16,
+            CTRL_CMD_GETFAMILY,
+            1,
+            &attrs,
+        ))?;
+        let mut family = None;
+        recv_payloads(sock, |payload| {
+            for (ty, data) in attrs_of(payload) {
+                if ty == CTRL_ATTR_FAMILY_ID && data.len() >= 2 {
+                    family = Some(u16::from_ne_bytes([data[0], data[1]]));
+                }
+            }
+        })?;
+        family.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "wireguard module not loaded"))
+    }
+
+    fn parse_peer(data: &[u8]) -> WgPeer {
+        let mut peer = WgPeer {
+            public_key: String::new(),
+            endpoint: None,
+            latest_handshake: None,
+            rx_bytes: 0,
+            tx_bytes: 0,
+        };
+        let u64_of = |d: &[u8]| <[u8; 8]>::try_from(d).map(u64::from_ne_bytes).ok();
+        for (ty, d) in attrs_of(data) {
+            match ty {
+                WGPEER_A_PUBLIC_KEY => peer.public_key = encode_key(d),
+                WGPEER_A_ENDPOINT => peer.endpoint = parse_sockaddr(d),
+                WGPEER_A_LAST_HANDSHAKE_TIME if d.len() >= 16 => {
+                    let secs = i64::from_ne_bytes(d[..8].try_into().unwrap_or_default());
+                    let nanos = i64::from_ne_bytes(d[8..16].try_into().unwrap_or_default());
+                    if secs > 0 {
+                        peer.latest_handshake = Some(
+                            UNIX_EPOCH
+                                + Duration::new(secs as u64, nanos.clamp(0, 999_999_999) as u32),
+                        );
+                    }
+                }
+                WGPEER_A_RX_BYTES => peer.rx_bytes = u64_of(d).unwrap_or(0),
+                WGPEER_A_TX_BYTES => peer.tx_bytes = u64_of(d).unwrap_or(0),
+                _ => {}
+            }
+        }
+        peer
+    }
+
+    fn parse_sockaddr(d: &[u8]) -> Option<SocketAddr> {
+        let family = i32::from(u16::from_ne_bytes([*d.first()?, *d.get(1)?]));
+        let port = u16::from_be_bytes([*d.get(2)?, *d.get(3)?]);
+        let ip: IpAddr = match family {
+            libc::AF_INET => Ipv4Addr::from(<[u8; 4]>::try_from(d.get(4..8)?).ok()?).into(),
+            libc::AF_INET6 => Ipv6Addr::from(<[u8; 16]>::try_from(d.get(8..24)?).ok()?).into(),
+            _ => return None,
+        };
+        Some(SocketAddr::new(ip, port))
+    }
+
+    fn nul_terminated(s: &str) -> Vec<u8> {
+        let mut v = s.as_bytes().to_vec();
+        v.push(0);
+        v
+    }
+
+    /// A generic netlink message: the genl header, then `attrs`.
+    fn message(ty: u16, flags: u16, cmd: u8, version: u8, attrs: &[u8]) -> Vec<u8> {
+        let mut body = vec![cmd, version, 0, 0];
+        body.extend_from_slice(attrs);
+        netlink::message(ty, flags, &body)
+    }
+
+    /// Hand the attribute section of each reply to `on_payload`.
+    fn recv_payloads(sock: &Socket, mut on_payload: impl FnMut(&[u8])) -> io::Result<()> {
+        sock.recv_all(|msg| {
+            if let Some(payload) = msg.get(NLMSG_HDR_LEN + GENL_HDR_LEN..) {
+                on_payload(payload);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(handshake: Option<u64>) -> WgPeer {
+        WgPeer {
+            public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".to_string(),
+            endpoint: None,
+            latest_handshake: handshake.map(|s| UNIX_EPOCH + Duration::from_secs(s)),
+            rx_bytes: 0,
+            tx_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn staleness_uses_max_age() {
+        let now = UNIX_EPOCH + Duration::from_secs(10_000);
+        let max = Duration::from_secs(DEFAULT_HANDSHAKE_MAX_AGE_SECS);
+        assert!(!peer(Some(9_900)).is_stale(now, max));
+        assert!(peer(Some(9_000)).is_stale(now, max));
+        assert!(peer(None).is_stale(now, max));
+        assert_eq!(peer(None).short_key(), "xTIBA5rb");
+    }
+
+    #[test]
+    fn dump_rejects_short_lines() {
+        let text = "priv\tpub\t51820\toff\nkey\t(none)\t1.2.3.4:51820\n";
+        assert!(parse_dump(text).is_err());
+    }
+
+    #[test]
+    fn netlink_reports_missing_interface() {
+        assert!(genl::get_peers("cvpn-test0").is_err());
+    }
+}
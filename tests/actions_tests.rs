@@ -19,6 +19,7 @@ fn make_eff(isp_to_check: &str) -> EffectiveConfig {
         connectivity_retries: check_vpn::networking::DEFAULT_RETRIES,
         run_once: false,
         exit_on_error: false,
+        ..Default::default()
     }
 }
 
@@ -8,6 +8,7 @@ use std::thread;
 use check_vpn::app::perform_check;
 use check_vpn::cli::Args;
 use check_vpn::config::{Config, EffectiveConfig};
+use check_vpn::ip_api::IpInfo;
 
 /// Test helper to create a live TCP listener and return the port
 fn create_test_listener() -> (TcpListener, u16) {
@@ -39,6 +40,7 @@ fn perform_check_runs_action_when_isp_matches() {
         connectivity_retries: 1,
         run_once: true,
         exit_on_error: false,
+        ..Default::default()
     };
 
     let called = Arc::new(AtomicBool::new(false));
@@ -77,6 +79,7 @@ fn perform_check_does_not_run_action_when_isp_differs() {
         connectivity_retries: 1,
         run_once: true,
         exit_on_error: false,
+        ..Default::default()
     };
 
     let called = Arc::new(AtomicBool::new(false));
@@ -109,6 +112,7 @@ fn perform_check_with_args_config_runs_action_when_isp_matches() {
         verbose: 0,
             config: None,
         exit_on_error: false,
+        ..Default::default()
     };
 
     let eff = Config::default().merge_with_args(&args);
@@ -145,6 +149,7 @@ fn perform_check_with_args_config_does_not_run_action_when_isp_differs() {
         verbose: 0,
             config: None,
         exit_on_error: false,
+        ..Default::default()
     };
 
     let eff = Config::default().merge_with_args(&args);
@@ -181,6 +186,7 @@ fn perform_check_handles_get_isp_error_gracefully() {
         verbose: 0,
             config: None,
         exit_on_error: false,
+        ..Default::default()
     };
 
     let eff = Config::default().merge_with_args(&args);
@@ -204,3 +210,302 @@ fn perform_check_handles_get_isp_error_gracefully() {
         "action should not run when get_isp fails"
     );
 }
+
+#[test]
+fn perform_check_accepts_full_ip_info() {
+    let (listener, port) = create_test_listener();
+    let _h = thread::spawn(move || {
+        let _ = listener.accept();
+    });
+
+    let eff = EffectiveConfig {
+        isp_to_check: "ISP-INFO".to_string(),
+        dry_run: true,
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![port],
+        ..Default::default()
+    };
+
+    let ran = Arc::new(AtomicBool::new(false));
+    let ran_clone = ran.clone();
+    let run_action = move |_a: &check_vpn::actions::Action, _dry: bool| {
+        ran_clone.store(true, Ordering::SeqCst);
+    };
+
+    let get_info = || -> Result<IpInfo, anyhow::Error> {
+        Ok(IpInfo {
+            ip: Some("203.0.113.10".parse().unwrap()),
+            isp: Some("ISP-INFO".to_string()),
+            asn: Some(64500),
+            ..Default::default()
+        })
+    };
+
+    perform_check(&eff, get_info, run_action).expect("perform_check");
+    assert!(ran.load(Ordering::SeqCst));
+}
+
+/// Run `perform_check` against a local listener with the given lookup result
+/// and report whether the action fired.
+fn action_runs_for(eff: EffectiveConfig, info: IpInfo) -> bool {
+    let (listener, port) = create_test_listener();
+    let _h = thread::spawn(move || {
+        let _ = listener.accept();
+    });
+    let eff = EffectiveConfig {
+        dry_run: true,
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![port],
+        ..eff
+    };
+
+    let ran = Arc::new(AtomicBool::new(false));
+    let ran_clone = ran.clone();
+    let run_action = move |_a: &check_vpn::actions::Action, _dry: bool| {
+        ran_clone.store(true, Ordering::SeqCst);
+    };
+    perform_check(&eff, || Ok(info.clone()), run_action).expect("perform_check");
+    ran.load(Ordering::SeqCst)
+}
+
+#[test]
+fn perform_check_matches_asn_country_and_cidr() {
+    let info = IpInfo {
+        ip: Some("203.0.113.10".parse().unwrap()),
+        isp: Some("Renamed Home ISP".to_string()),
+        asn: Some(64500),
+        country_code: Some("GB".to_string()),
+        ..Default::default()
+    };
+
+    // Nothing configured beyond the (non-matching) default ISP.
+    assert!(!action_runs_for(EffectiveConfig::default(), info.clone()));
+
+    let by_asn = EffectiveConfig {
+        asn_to_check: vec!["AS64500".to_string()],
+        ..Default::default()
+    };
+    assert!(action_runs_for(by_asn, info.clone()));
+
+    let by_country = EffectiveConfig {
+        expected_country: vec!["SE".to_string()],
+        ..Default::default()
+    };
+    assert!(action_runs_for(by_country, info.clone()));
+
+    let by_cidr = EffectiveConfig {
+        cidr_to_check: vec!["203.0.113.0/24".to_string()],
+        ..Default::default()
+    };
+    assert!(action_runs_for(by_cidr, info.clone()));
+
+    let other_cidr = EffectiveConfig {
+        cidr_to_check: vec!["198.51.100.0/24".to_string()],
+        ..Default::default()
+    };
+    assert!(!action_runs_for(other_cidr, info));
+}
+
+#[test]
+fn perform_check_allowlist_mode_flags_unexpected_exit() {
+    let eff = EffectiveConfig {
+        match_mode: "allowlist".to_string(),
+        allowed_asn: vec!["AS42708".to_string()],
+        allowed_cidr: vec!["46.246.8.0/24".to_string()],
+        ..Default::default()
+    };
+
+    let vpn_exit = IpInfo {
+        ip: Some("46.246.8.48".parse().unwrap()),
+        isp: Some("GleSYS AB".to_string()),
+        asn: Some(42708),
+        ..Default::default()
+    };
+    assert!(!action_runs_for(eff.clone(), vpn_exit));
+
+    // A travel hotspot is not the home ISP, but it is not the VPN either.
+    let hotspot = IpInfo {
+        ip: Some("192.0.2.44".parse().unwrap()),
+        isp: Some("Hotel Wifi Inc".to_string()),
+        asn: Some(64999),
+        ..Default::default()
+    };
+    assert!(action_runs_for(eff, hotspot));
+}
+
+#[test]
+fn change_detection_runs_change_action_on_new_ip() {
+    use check_vpn::actions::Action;
+    use check_vpn::app::{perform_check_with_tracker, ChangeTracker};
+    use std::cell::RefCell;
+    use std::sync::Mutex;
+
+    let (_listener, port) = create_test_listener();
+    let eff = EffectiveConfig {
+        isp_to_check: "Home ISP".to_string(),
+        dry_run: true,
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![port],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        change_detection: "ip".to_string(),
+        change_action_type: Some("command".to_string()),
+        change_action_arg: "/usr/local/bin/vpn-hopped".to_string(),
+        ..Default::default()
+    };
+    assert!(Config::validate_effective(&eff).is_ok());
+
+    let exit = RefCell::new(("203.0.113.7", 64500));
+    let get_info = || {
+        let (ip, asn) = *exit.borrow();
+        Ok(IpInfo {
+            ip: Some(ip.parse().unwrap()),
+            asn: Some(asn),
+            isp: Some("VPN Provider".to_string()),
+            ..Default::default()
+        })
+    };
+    let actions = Arc::new(Mutex::new(Vec::new()));
+    let recorded = actions.clone();
+    let run_action = move |a: &Action, _d: bool| recorded.lock().unwrap().push(a.clone());
+
+    let mut tracker = ChangeTracker::default();
+    // Baseline, then an unchanged exit: no action.
+    for _ in 0..2 {
+        perform_check_with_tracker(&eff, &mut tracker, get_info, &run_action).unwrap();
+    }
+    assert!(actions.lock().unwrap().is_empty());
+
+    // Hop to another server of the same provider.
+    *exit.borrow_mut() = ("203.0.113.99", 64500);
+    perform_check_with_tracker(&eff, &mut tracker, get_info, &run_action).unwrap();
+    assert_eq!(
+        *actions.lock().unwrap(),
+        vec![Action::Command("/usr/local/bin/vpn-hopped".to_string())]
+    );
+    assert_eq!(tracker.last_ip(), Some("203.0.113.99".parse().unwrap()));
+
+    // With `asn` mode the same hop is ignored.
+    let eff = EffectiveConfig {
+        change_detection: "asn".to_string(),
+        ..eff
+    };
+    *exit.borrow_mut() = ("203.0.113.100", 64500);
+    perform_check_with_tracker(&eff, &mut tracker, get_info, &run_action).unwrap();
+    assert_eq!(actions.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn missing_tunnel_interface_runs_tunnel_action_before_connectivity() {
+    use check_vpn::actions::Action;
+    use check_vpn::app::CheckOutcome;
+    use std::sync::Mutex;
+
+    // No listener: the connectivity stage would report Offline if reached.
+    let eff = EffectiveConfig {
+        dry_run: true,
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![1],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        tunnel_interface: Some("cvpn-test0".to_string()),
+        tunnel_action_type: Some("restart-unit".to_string()),
+        tunnel_action_arg: "wg-quick@wg0".to_string(),
+        ..Default::default()
+    };
+    assert!(Config::validate_effective(&eff).is_ok());
+
+    let actions = Mutex::new(Vec::new());
+    let outcome = perform_check(
+        &eff,
+        || -> anyhow::Result<IpInfo> { panic!("lookup must not run") },
+        |a: &Action, _d: bool| actions.lock().unwrap().push(a.clone()),
+    )
+    .unwrap();
+    assert!(matches!(outcome, CheckOutcome::TunnelDown(ref r) if r.contains("does not exist")));
+    assert_eq!(outcome.exit_code(), None);
+    assert_eq!(
+        *actions.lock().unwrap(),
+        vec![Action::RestartUnit("wg-quick@wg0".to_string())]
+    );
+}
+
+#[test]
+fn present_tunnel_interface_continues_to_lookup() {
+    use check_vpn::app::CheckOutcome;
+
+    let (_listener, port) = create_test_listener();
+    let eff = EffectiveConfig {
+        isp_to_check: "Home ISP".to_string(),
+        dry_run: true,
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![port],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        // Loopback is always present and up.
+        tunnel_interface: Some("lo".to_string()),
+        ..Default::default()
+    };
+    let outcome = perform_check(
+        &eff,
+        || Ok("VPN Provider".to_string()),
+        |_, _| panic!("no action expected"),
+    )
+    .unwrap();
+    assert!(matches!(outcome, CheckOutcome::VpnActive));
+}
+
+#[test]
+fn route_check_reports_route_that_bypasses_the_tunnel() {
+    use check_vpn::app::CheckOutcome;
+    use check_vpn::networking::route_to;
+    use std::sync::Mutex;
+
+    let (_listener, port) = create_test_listener();
+    let base = EffectiveConfig {
+        isp_to_check: "Home ISP".to_string(),
+        dry_run: true,
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![port],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        tunnel_interface: Some("lo".to_string()),
+        route_check: true,
+        ..Default::default()
+    };
+    assert!(Config::validate_effective(&base).is_ok());
+
+    // Loopback traffic is routed via lo, so "lo" as the tunnel passes.
+    let outcome = perform_check(
+        &base,
+        || Ok("VPN Provider".to_string()),
+        |_, _| panic!("no action expected"),
+    )
+    .unwrap();
+    assert!(matches!(outcome, CheckOutcome::VpnActive));
+
+    // An off-host destination leaves via another interface, if there is a
+    // route at all on this machine.
+    let Ok(route) = route_to("192.0.2.1".parse().unwrap()) else {
+        return;
+    };
+    let eff = EffectiveConfig {
+        connectivity_endpoints: vec!["192.0.2.1:443".to_string()],
+        ..base
+    };
+    let ran = Mutex::new(0);
+    let outcome = perform_check(
+        &eff,
+        || -> anyhow::Result<IpInfo> { panic!("lookup must not run") },
+        |_, _| *ran.lock().unwrap() += 1,
+    )
+    .unwrap();
+    match outcome {
+        CheckOutcome::RouteBypass(reason) => {
+            assert!(reason.contains(&route.interface), "{}", reason);
+            assert!(reason.contains("instead of lo"), "{}", reason);
+        }
+        other => panic!("expected route bypass, got {:?}", other),
+    }
+    assert_eq!(*ran.lock().unwrap(), 1);
+}
@@ -0,0 +1,173 @@
+use anyhow::Result;
+use httpmock::Method::GET;
+use httpmock::MockServer;
+use std::fs;
+use std::net::{IpAddr, TcpListener};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use check_vpn::app::{perform_check, CheckOutcome};
+use check_vpn::config::{Config, EffectiveConfig};
+use check_vpn::ip_api::{
+    provider_from_config, AsnDatabase, AsnDbFile, AsnDbProvider, IpInfo, IpInfoProvider,
+};
+
+const MMDB_FIXTURE: &str = "tests/fixtures/asn_db/test-asn.mmdb";
+const TSV_FIXTURE: &str = "tests/fixtures/asn_db/ip2asn-test.tsv";
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+/// Reports a fixed public IP and nothing else, like the DNS/STUN providers.
+struct FixedIp(&'static str);
+
+impl IpInfoProvider for FixedIp {
+    fn name(&self) -> &str {
+        "fixed"
+    }
+
+    fn get_ip_info(&self) -> Result<IpInfo> {
+        Ok(IpInfo {
+            ip: Some(ip(self.0)),
+            ..Default::default()
+        })
+    }
+}
+
+#[test]
+fn mmdb_fixture_lookup() {
+    let db = AsnDatabase::load(Path::new(MMDB_FIXTURE)).expect("load mmdb");
+    let rec = db.lookup(ip("198.51.100.20")).expect("record");
+    assert_eq!(rec.asn, 64501);
+    assert_eq!(rec.as_name.as_deref(), Some("Home ISP Ltd"));
+    assert_eq!(rec.country_code.as_deref(), Some("GB"));
+    assert_eq!(db.lookup(ip("203.0.113.7")).unwrap().asn, 64500);
+    assert!(db.lookup(ip("192.0.2.1")).is_none());
+}
+
+#[test]
+fn corrupt_mmdb_is_rejected_at_load() {
+    let mut bytes = fs::read(MMDB_FIXTURE).unwrap();
+    // Point the first search tree record far past the data section.
+    bytes[..3].copy_from_slice(&[0xFF; 3]);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("corrupt.mmdb");
+    fs::write(&path, &bytes).unwrap();
+    let err = AsnDatabase::load(&path)
+        .err()
+        .expect("corrupt file rejected");
+    assert!(err.to_string().contains("invalid MaxMind DB"), "{:#}", err);
+}
+
+#[test]
+fn tsv_fixture_lookup() {
+    let db = AsnDatabase::load(Path::new(TSV_FIXTURE)).expect("load tsv");
+    assert_eq!(db.lookup(ip("203.0.113.7")).unwrap().asn, 64500);
+    assert_eq!(db.lookup(ip("2001:db8::42")).unwrap().asn, 64502);
+    assert!(db.lookup(ip("203.0.114.9")).is_none());
+}
+
+#[test]
+fn database_reloads_when_file_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("asn.tsv");
+    fs::write(&path, "203.0.113.0\t203.0.113.255\t64500\tNL\tOld Net\n").unwrap();
+    let provider = AsnDbProvider::new(
+        Box::new(FixedIp("203.0.113.7")),
+        Arc::new(AsnDbFile::new(&path)),
+    );
+    let info = provider.get_ip_info().unwrap();
+    assert_eq!(info.asn, Some(64500));
+    assert_eq!(info.isp.as_deref(), Some("Old Net"));
+
+    // Different size, so the change is seen even with coarse mtimes.
+    fs::write(
+        &path,
+        "203.0.113.0\t203.0.113.255\t64999\tSE\tReplacement Net\n",
+    )
+    .unwrap();
+    let info = provider.get_ip_info().unwrap();
+    assert_eq!(info.asn, Some(64999));
+    assert_eq!(info.country_code.as_deref(), Some("SE"));
+
+    // A broken update keeps the last good database.
+    fs::write(&path, "garbage").unwrap();
+    assert_eq!(provider.get_ip_info().unwrap().asn, Some(64999));
+}
+
+#[test]
+fn broken_update_is_not_reread_until_it_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("asn.tsv");
+    let good = "203.0.113.0\t203.0.113.255\t64500\tNL\tOld Net\n";
+    fs::write(&path, good).unwrap();
+    let db = AsnDbFile::new(&path);
+    let asn = |db: &AsnDbFile| db.current().unwrap().lookup(ip("203.0.113.7")).unwrap().asn;
+    assert_eq!(asn(&db), 64500);
+
+    let broken = "x".repeat(good.len());
+    fs::write(&path, &broken).unwrap();
+    let mtime = fs::metadata(&path).unwrap().modified().unwrap();
+    assert_eq!(asn(&db), 64500);
+
+    // Same size and mtime as the broken file: taken as unchanged, not read.
+    fs::write(&path, good.replace("64500", "64501")).unwrap();
+    let file = fs::File::options().write(true).open(&path).unwrap();
+    file.set_modified(mtime).unwrap();
+    assert_eq!(asn(&db), 64500);
+
+    file.set_modified(mtime + Duration::from_secs(1)).unwrap();
+    assert_eq!(asn(&db), 64501);
+}
+
+#[test]
+fn provider_from_config_enriches_before_matching() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/ip");
+        then.status(200).body(r#"{"query":"198.51.100.20"}"#);
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let eff = EffectiveConfig {
+        dry_run: true,
+        ip_provider: "custom".to_string(),
+        ip_provider_url: Some(server.url("/ip")),
+        asn_db_path: Some(MMDB_FIXTURE.to_string()),
+        asn_to_check: vec!["AS64501".to_string()],
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![listener.local_addr().unwrap().port()],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        ..Default::default()
+    };
+    assert!(Config::validate_effective(&eff).is_ok());
+
+    let provider = provider_from_config(&eff).expect("provider");
+    let info = provider.get_ip_info().expect("lookup");
+    assert_eq!(info.isp.as_deref(), Some("Home ISP Ltd"));
+
+    let called = Arc::new(Mutex::new(false));
+    let c = called.clone();
+    let outcome = perform_check(
+        &eff,
+        || provider.get_ip_info(),
+        move |_, _| {
+            *c.lock().unwrap() = true;
+        },
+    )
+    .expect("check");
+    assert!(matches!(outcome, CheckOutcome::VpnLost));
+    assert!(*called.lock().unwrap());
+}
+
+#[test]
+fn missing_database_is_a_config_error() {
+    let eff = EffectiveConfig {
+        asn_db_path: Some("/nonexistent/check_vpn/asn.mmdb".to_string()),
+        ..Default::default()
+    };
+    let err = Config::validate_effective(&eff).unwrap_err().to_string();
+    assert!(err.contains("asn_db_path"), "{}", err);
+}
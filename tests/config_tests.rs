@@ -138,6 +138,7 @@ fn missing_required_field_uses_default_on_merge() {
         exit_on_error: false,
         verbose: 0,
         config: None,
+        ..Default::default()
     };
 
     let eff = cfg.merge_with_args(&args);
@@ -171,3 +172,99 @@ fn env_var_config_override() {
     assert_eq!(cfg.interval, Some(77));
     assert_eq!(cfg.isp_to_check.unwrap(), "Env ISP");
 }
+
+#[test]
+fn match_conditions_parse_from_repeated_elements() {
+    let xml = r#"<config>
+      <asn_to_check>AS64500</asn_to_check>
+      <asn_to_check>64501</asn_to_check>
+      <expected_country>SE</expected_country>
+      <cidr_to_check>203.0.113.0/24</cidr_to_check>
+    </config>"#;
+    let mut path = std::env::temp_dir();
+    path.push(format!(
+        "check_vpn_match_rules_{}.xml",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    ));
+    let path_str = path.to_str().unwrap();
+    fs::write(path_str, xml).expect("write temp xml");
+    let cfg = Config::load_from_path(path_str).expect("load config");
+    let _ = fs::remove_file(path_str);
+
+    let eff = cfg.merge_with_args(&check_vpn::cli::Args::default());
+    assert_eq!(eff.asn_to_check, vec!["AS64500", "64501"]);
+    assert_eq!(eff.expected_country, vec!["SE"]);
+    assert_eq!(eff.cidr_to_check, vec!["203.0.113.0/24"]);
+    assert!(eff.org_to_check.is_empty());
+    assert!(Config::validate_effective(&eff).is_ok());
+}
+
+#[test]
+fn invalid_isp_regex_rejected_at_load() {
+    let xml = r#"<config>
+      <isp_to_check>^Hutchison</isp_to_check>
+      <isp_pattern>(?i)three uk</isp_pattern>
+      <isp_pattern>broken[</isp_pattern>
+      <isp_match_mode>regex</isp_match_mode>
+    </config>"#;
+    let mut path = std::env::temp_dir();
+    path.push(format!(
+        "check_vpn_isp_regex_{}.xml",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    ));
+    let path_str = path.to_str().unwrap();
+    fs::write(path_str, xml).expect("write temp xml");
+    let cfg = Config::load_from_path(path_str).expect("load config");
+    let _ = fs::remove_file(path_str);
+
+    let eff = cfg.merge_with_args(&check_vpn::cli::Args::default());
+    assert_eq!(eff.isp_pattern.len(), 2);
+    let err = Config::validate_effective(&eff).unwrap_err().to_string();
+    assert!(
+        err.contains("invalid regex 'broken['"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn lookup_settings_from_xml_and_cli() {
+    let xml = r#"<config>
+      <lookup_timeout_secs>9</lookup_timeout_secs>
+      <lookup_retries>3</lookup_retries>
+      <max_response_bytes>65536</max_response_bytes>
+      <user_agent>home-router/1.0</user_agent>
+    </config>"#;
+    let mut path = std::env::temp_dir();
+    path.push(format!(
+        "check_vpn_lookup_settings_{}.xml",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    ));
+    let path_str = path.to_str().unwrap();
+    fs::write(path_str, xml).expect("write temp xml");
+    let cfg = Config::load_from_path(path_str).expect("load config");
+    let _ = fs::remove_file(path_str);
+
+    let eff = cfg.merge_with_args(&check_vpn::cli::Args::default());
+    assert_eq!(eff.lookup_timeout_secs, 9);
+    assert_eq!(eff.lookup_retries, 3);
+    assert_eq!(eff.max_response_bytes, 65536);
+    assert_eq!(eff.user_agent, "home-router/1.0");
+
+    let args = check_vpn::cli::Args {
+        lookup_retries: Some(5),
+        ..Default::default()
+    };
+    let eff = cfg.merge_with_args(&args);
+    assert_eq!(eff.lookup_retries, 5);
+    assert!(Config::validate_effective(&eff).is_ok());
+}
@@ -0,0 +1,159 @@
+use std::cell::RefCell;
+use std::fs;
+use std::net::IpAddr;
+use std::os::unix::net::UnixStream;
+use std::thread;
+
+use check_vpn::actions::Action;
+use check_vpn::app::{check_dns_leak, DnsStatus};
+use check_vpn::config::{Config, EffectiveConfig};
+use check_vpn::networking::{active_resolvers, resolved_links, routed_servers};
+use zbus::blocking::connection;
+use zbus::interface;
+use zbus::zvariant::OwnedObjectPath;
+
+const WIRED: i32 = 2;
+const VPN: i32 = 5;
+
+/// Stand-in for systemd-resolved: a wired link with the ISP's server and
+/// a VPN link that routes every name (`~.`).
+struct MockResolved;
+
+#[interface(name = "org.freedesktop.resolve1.Manager")]
+impl MockResolved {
+    #[zbus(property, name = "DNS")]
+    fn dns(&self) -> Vec<(i32, i32, Vec<u8>)> {
+        vec![
+            (WIRED, 2, vec![192, 168, 1, 1]),
+            (VPN, 2, vec![10, 8, 0, 1]),
+        ]
+    }
+
+    #[zbus(property)]
+    fn domains(&self) -> Vec<(i32, String, bool)> {
+        vec![
+            (WIRED, "lan".to_string(), false),
+            (VPN, ".".to_string(), true),
+        ]
+    }
+
+    fn get_link(&self, ifindex: i32) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(format!("/org/freedesktop/resolve1/link/_3{}", ifindex)).unwrap()
+    }
+}
+
+struct MockLink;
+
+#[interface(name = "org.freedesktop.resolve1.Link")]
+impl MockLink {
+    #[zbus(property)]
+    fn default_route(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn resolv_conf_entries_are_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("resolv.conf");
+    fs::write(
+        &path,
+        "# written by openvpn\nnameserver 10.8.0.1\nnameserver 192.168.1.1\nsearch lan\n",
+    )
+    .unwrap();
+
+    // systemd-resolved may add its upstreams on hosts that run it.
+    let resolvers = active_resolvers(path.to_str().unwrap()).unwrap();
+    for ip in ["10.8.0.1", "192.168.1.1"] {
+        assert!(
+            resolvers.contains(&ip.parse::<IpAddr>().unwrap()),
+            "{:?}",
+            resolvers
+        );
+    }
+
+    assert!(active_resolvers(dir.path().join("missing").to_str().unwrap()).is_err());
+}
+
+#[test]
+fn dns_leak_from_xml_runs_vpn_lost_action() {
+    let dir = tempfile::tempdir().unwrap();
+    let resolv = dir.path().join("resolv.conf");
+    fs::write(&resolv, "nameserver 10.8.0.1\nnameserver 192.168.1.1\n").unwrap();
+    let xml = format!(
+        r#"<config>
+      <dns_leak_check>true</dns_leak_check>
+      <allowed_dns>10.8.0.0/24</allowed_dns>
+      <allowed_dns>fd00::53</allowed_dns>
+      <resolv_conf_path>{}</resolv_conf_path>
+      <vpn_lost_action_type>command</vpn_lost_action_type>
+      <vpn_lost_action_arg>/usr/local/bin/vpn-down</vpn_lost_action_arg>
+    </config>"#,
+        resolv.display()
+    );
+    let path = dir.path().join("check_vpn.xml");
+    fs::write(&path, xml).unwrap();
+    let cfg = Config::load_from_path(path.to_str().unwrap()).expect("load config");
+    let eff = cfg.merge_with_args(&check_vpn::cli::Args::default());
+    assert_eq!(eff.allowed_dns, vec!["10.8.0.0/24", "fd00::53"]);
+    assert!(Config::validate_effective(&eff).is_ok());
+
+    // Use only the file so the result does not depend on the host's resolved.
+    let ran = RefCell::new(Vec::new());
+    let status = check_dns_leak(
+        &eff,
+        || {
+            let text = fs::read_to_string(&eff.resolv_conf_path)?;
+            Ok(check_vpn::networking::parse_resolv_conf(&text))
+        },
+        |a, _| ran.borrow_mut().push(a.clone()),
+    )
+    .unwrap();
+    assert_eq!(
+        status,
+        DnsStatus::Leak(vec!["192.168.1.1".parse().unwrap()])
+    );
+    assert_eq!(
+        ran.into_inner(),
+        vec![Action::Command("/usr/local/bin/vpn-down".to_string())]
+    );
+}
+
+#[test]
+fn vpn_link_routing_everything_is_clean() {
+    let (server_sock, client_sock) = UnixStream::pair().unwrap();
+    let server = thread::spawn(move || {
+        connection::Builder::unix_stream(server_sock)
+            .server(zbus::Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at("/org/freedesktop/resolve1", MockResolved)
+            .unwrap()
+            .serve_at("/org/freedesktop/resolve1/link/_32", MockLink)
+            .unwrap()
+            .serve_at("/org/freedesktop/resolve1/link/_35", MockLink)
+            .unwrap()
+            .build()
+            .unwrap()
+    });
+    let conn = connection::Builder::unix_stream(client_sock)
+        .p2p()
+        .build()
+        .unwrap();
+    let _server = server.join().unwrap();
+
+    let links = resolved_links(&conn).unwrap();
+    assert_eq!(links.len(), 2);
+    let eff = EffectiveConfig {
+        dns_leak_check: true,
+        allowed_dns: vec!["10.8.0.0/24".to_string()],
+        ..Default::default()
+    };
+    let status = check_dns_leak(
+        &eff,
+        || Ok(routed_servers(&links)),
+        |_, _| panic!("no leak, no action"),
+    )
+    .unwrap();
+    assert_eq!(status, DnsStatus::Clean(vec!["10.8.0.1".parse().unwrap()]));
+}
@@ -0,0 +1,87 @@
+#!/usr/bin/env python3
+"""Write test-asn.mmdb, a tiny GeoLite2-ASN style MaxMind DB used by
+tests/asn_db_tests.rs. Run from this directory: python3 make_mmdb.py"""
+import struct
+
+NETWORKS = [
+    ("203.0.113.0", 24, {"autonomous_system_number": 64500,
+                         "autonomous_system_organization": "Example Transit"}),
+    ("198.51.100.0", 24, {"autonomous_system_number": 64501,
+                          "autonomous_system_organization": "Home ISP Ltd",
+                          "country": {"iso_code": "GB"}}),
+]
+
+
+def ctrl(type_, size):
+    extra = b""
+    if size >= 29:  # sizes 29..284 spill into one extra byte
+        extra, size = bytes([size - 29]), 29
+    if type_ <= 7:
+        return bytes([(type_ << 5) | size]) + extra
+    return bytes([size, type_ - 7]) + extra
+
+
+def enc(v):
+    if isinstance(v, dict):
+        out = ctrl(7, len(v))
+        for k, val in v.items():
+            out += enc(k) + enc(val)
+        return out
+    if isinstance(v, list):
+        return ctrl(11, len(v)) + b"".join(enc(x) for x in v)
+    if isinstance(v, str):
+        b = v.encode()
+        return ctrl(2, len(b)) + b
+    if isinstance(v, tuple):  # (type, int): uint16/uint32/uint64
+        t, n = v
+        b = n.to_bytes(8, "big").lstrip(b"\0")
+        return ctrl(t, len(b)) + b
+    if isinstance(v, int):
+        return enc((6, v))
+    raise TypeError(v)
+
+
+def main():
+    data = b""
+    nodes = [[None, None]]  # None = empty, ("n", i) = node, ("d", off) = data
+    for net, plen, rec in NETWORKS:
+        off = len(data)
+        data += enc(rec)
+        bits = int.from_bytes(bytes(int(x) for x in net.split(".")), "big")
+        node = 0
+        for depth in range(plen):
+            bit = (bits >> (31 - depth)) & 1
+            if depth == plen - 1:
+                nodes[node][bit] = ("d", off)
+            else:
+                if nodes[node][bit] is None:
+                    nodes.append([None, None])
+                    nodes[node][bit] = ("n", len(nodes) - 1)
+                node = nodes[node][bit][1]
+    count = len(nodes)
+
+    def value(r):
+        if r is None:
+            return count
+        kind, x = r
+        return x if kind == "n" else count + 16 + x
+
+    tree = b"".join(value(l).to_bytes(3, "big") + value(r).to_bytes(3, "big")
+                    for l, r in nodes)
+    meta = enc({
+        "binary_format_major_version": (5, 2),
+        "binary_format_minor_version": (5, 0),
+        "build_epoch": (9, 1700000000),
+        "database_type": "GeoLite2-ASN",
+        "description": {"en": "check_vpn test ASN database"},
+        "ip_version": (5, 4),
+        "languages": ["en"],
+        "node_count": (6, count),
+        "record_size": (5, 24),
+    })
+    with open("test-asn.mmdb", "wb") as f:
+        f.write(tree + b"\0" * 16 + data + b"\xab\xcd\xefMaxMind.com" + meta)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,152 @@
+use std::net::{IpAddr, TcpListener, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use check_vpn::app::{perform_check, CheckOutcome};
+use check_vpn::config::EffectiveConfig;
+use check_vpn::ip_api::{
+    provider_from_config, DnsProvider, DnsService, IpInfoProvider, IpLookupError,
+};
+
+#[derive(Clone, Copy)]
+enum Reply {
+    /// Answer with this A/AAAA/TXT payload for whatever type was asked.
+    Ip(&'static str),
+    /// Respond with the given rcode and no answers.
+    Rcode(u8),
+    /// Keep sending datagrams with the wrong id for a few seconds.
+    Stray,
+}
+
+/// A one-thread UDP DNS responder on 127.0.0.1 that answers every query
+/// with `reply`, echoing the id and question. Returns its address.
+fn responder(reply: Reply) -> String {
+    let socket = UdpSocket::bind("127.0.0.1:0").expect("bind udp");
+    let addr = socket.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        while let Ok((n, peer)) = socket.recv_from(&mut buf) {
+            let q = &buf[..n];
+            let qtype = u16::from_be_bytes([q[n - 4], q[n - 3]]);
+            let mut r = q.to_vec();
+            r[2] = 0x81;
+            match reply {
+                Reply::Stray => {
+                    r[0] ^= 0xFF;
+                    for _ in 0..40 {
+                        let _ = socket.send_to(&r, peer);
+                        thread::sleep(Duration::from_millis(50));
+                    }
+                    continue;
+                }
+                Reply::Rcode(code) => r[3] = 0x80 | code,
+                Reply::Ip(ip) => {
+                    r[3] = 0x80;
+                    r[7] = 1;
+                    let rdata = match (qtype, ip.parse::<IpAddr>().unwrap()) {
+                        (16, _) => [&[ip.len() as u8][..], ip.as_bytes()].concat(),
+                        (_, IpAddr::V4(v4)) => v4.octets().to_vec(),
+                        (_, IpAddr::V6(v6)) => v6.octets().to_vec(),
+                    };
+                    r.extend_from_slice(&[0xC0, 0x0C]);
+                    r.extend_from_slice(&qtype.to_be_bytes());
+                    r.extend_from_slice(&[0, 1, 0, 0, 0, 60]);
+                    r.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+                    r.extend_from_slice(&rdata);
+                }
+            }
+            let _ = socket.send_to(&r, peer);
+        }
+    });
+    addr
+}
+
+#[test]
+fn opendns_style_a_lookup() {
+    let addr = responder(Reply::Ip("203.0.113.7"));
+    let p = DnsProvider::new(DnsService::OpenDns, &addr, Duration::from_secs(2), 1);
+    let info = p.get_ip_info().expect("dns lookup");
+    assert_eq!(info.ip, Some("203.0.113.7".parse().unwrap()));
+    assert_eq!(info.isp, None);
+    assert_eq!(p.name(), "dns-opendns");
+}
+
+#[test]
+fn google_style_txt_lookup() {
+    let addr = responder(Reply::Ip("198.51.100.9"));
+    let p = DnsProvider::new(DnsService::Google, &addr, Duration::from_secs(2), 1);
+    let info = p.get_ip_info().expect("dns lookup");
+    assert_eq!(info.ip, Some("198.51.100.9".parse().unwrap()));
+}
+
+#[test]
+fn rcode_is_reported_as_parse_error() {
+    let addr = responder(Reply::Rcode(3));
+    let p = DnsProvider::new(DnsService::OpenDns, &addr, Duration::from_secs(2), 1);
+    let err = p.get_ip_info().unwrap_err();
+    match err.downcast_ref::<IpLookupError>() {
+        Some(IpLookupError::Parse(msg)) => assert!(msg.contains("rcode 3"), "{}", msg),
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
+
+#[test]
+fn stray_datagrams_do_not_extend_the_timeout() {
+    let addr = responder(Reply::Stray);
+    let p = DnsProvider::new(DnsService::OpenDns, &addr, Duration::from_millis(300), 1);
+    let start = Instant::now();
+    let err = p.get_ip_info().unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<IpLookupError>(),
+        Some(&IpLookupError::Timeout)
+    );
+    assert!(
+        start.elapsed() < Duration::from_secs(1),
+        "{:?}",
+        start.elapsed()
+    );
+}
+
+#[test]
+fn silent_resolver_times_out() {
+    // Bound but never answers.
+    let silent = UdpSocket::bind("127.0.0.1:0").expect("bind udp");
+    let addr = silent.local_addr().unwrap().to_string();
+    let p = DnsProvider::new(DnsService::OpenDns, &addr, Duration::from_millis(200), 2);
+    let err = p.get_ip_info().unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<IpLookupError>(),
+        Some(&IpLookupError::Timeout)
+    );
+}
+
+#[test]
+fn provider_from_config_dns_feeds_cidr_check() {
+    let addr = responder(Reply::Ip("203.0.113.7"));
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let eff = EffectiveConfig {
+        dry_run: true,
+        ip_provider: "dns-opendns".to_string(),
+        ip_provider_url: Some(addr),
+        cidr_to_check: vec!["203.0.113.0/24".to_string()],
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![listener.local_addr().unwrap().port()],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        ..Default::default()
+    };
+    let provider = provider_from_config(&eff).expect("dns provider");
+    let called = Arc::new(Mutex::new(false));
+    let c = called.clone();
+    let outcome = perform_check(
+        &eff,
+        || provider.get_ip_info(),
+        move |_, _| {
+            *c.lock().unwrap() = true;
+        },
+    )
+    .expect("check");
+    assert!(matches!(outcome, CheckOutcome::VpnLost));
+    assert!(*called.lock().unwrap());
+}
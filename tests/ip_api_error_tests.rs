@@ -0,0 +1,189 @@
+use httpmock::Method::GET;
+use httpmock::MockServer;
+use std::net::TcpListener;
+use std::time::Duration;
+
+use check_vpn::app::{perform_check, CheckOutcome};
+use check_vpn::config::{self, EffectiveConfig};
+use check_vpn::ip_api::{get_isp_with_client_and_url, IpLookupError};
+
+fn client(timeout: Duration) -> reqwest::blocking::Client {
+    reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .build()
+        .expect("client build")
+}
+
+fn lookup_error(server: &MockServer, timeout: Duration) -> IpLookupError {
+    let err = get_isp_with_client_and_url(&client(timeout), &server.url("/json"), 1)
+        .expect_err("lookup should fail");
+    err.downcast_ref::<IpLookupError>()
+        .cloned()
+        .unwrap_or_else(|| panic!("not an IpLookupError: {}", err))
+}
+
+fn serve(status: u16, headers: &[(&str, &str)], body: &str) -> MockServer {
+    let server = MockServer::start();
+    let headers: Vec<(String, String)> = headers
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let body = body.to_string();
+    server.mock(move |when, then| {
+        when.method(GET).path("/json");
+        let mut then = then.status(status);
+        for (k, v) in headers {
+            then = then.header(k, v);
+        }
+        then.body(body);
+    });
+    server
+}
+
+#[test]
+fn too_many_requests_is_rate_limited_with_retry_after() {
+    let server = serve(429, &[("Retry-After", "7")], "");
+    assert_eq!(
+        lookup_error(&server, Duration::from_secs(2)),
+        IpLookupError::RateLimited {
+            retry_after: Some(Duration::from_secs(7))
+        }
+    );
+}
+
+#[test]
+fn other_statuses_are_http_errors() {
+    let server = serve(403, &[], "forbidden");
+    assert_eq!(
+        lookup_error(&server, Duration::from_secs(2)),
+        IpLookupError::Http { status: 403 }
+    );
+}
+
+#[test]
+fn bad_json_is_a_parse_error() {
+    let server = serve(200, &[], "<html>captive portal</html>");
+    assert!(matches!(
+        lookup_error(&server, Duration::from_secs(2)),
+        IpLookupError::Parse(_)
+    ));
+}
+
+#[test]
+fn missing_isp_is_a_missing_field() {
+    let server = serve(200, &[], r#"{"status":"success"}"#);
+    assert_eq!(
+        lookup_error(&server, Duration::from_secs(2)),
+        IpLookupError::MissingField("isp".to_string())
+    );
+}
+
+#[test]
+fn slow_server_is_a_timeout() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/json");
+        then.status(200)
+            .delay(Duration::from_secs(2))
+            .body(r#"{"isp":"late"}"#);
+    });
+    assert_eq!(
+        lookup_error(&server, Duration::from_millis(200)),
+        IpLookupError::Timeout
+    );
+}
+
+#[test]
+fn refused_connection_is_a_transport_error() {
+    // Bind then drop to get a port nothing listens on.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let url = format!("http://127.0.0.1:{}/json", port);
+    let err = get_isp_with_client_and_url(&client(Duration::from_secs(2)), &url, 1).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<IpLookupError>(),
+        Some(IpLookupError::Transport(_))
+    ));
+}
+
+#[test]
+fn perform_check_reports_lookup_error_kind() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let eff = EffectiveConfig {
+        dry_run: true,
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![listener.local_addr().unwrap().port()],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        ..Default::default()
+    };
+
+    let outcome = perform_check(
+        &eff,
+        || -> anyhow::Result<String> { Err(IpLookupError::Http { status: 503 }.into()) },
+        |_, _| {},
+    )
+    .expect("perform_check");
+    assert!(matches!(
+        outcome,
+        CheckOutcome::LookupFailed(Some(IpLookupError::Http { status: 503 }))
+    ));
+    assert_eq!(outcome.exit_code(), Some(config::EXIT_ISP_FAILURE));
+    assert!(!outcome.is_transient());
+
+    let outcome = perform_check(
+        &eff,
+        || -> anyhow::Result<String> { Ok("Somewhere Else".to_string()) },
+        |_, _| {},
+    )
+    .expect("perform_check");
+    assert!(matches!(outcome, CheckOutcome::VpnActive));
+    assert_eq!(outcome.exit_code(), None);
+}
+
+#[test]
+fn perform_check_classifies_failover_and_quorum_failures() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let check = |strategy: &str, servers: &[MockServer]| {
+        let eff = EffectiveConfig {
+            dry_run: true,
+            connectivity_endpoints: vec!["127.0.0.1".to_string()],
+            connectivity_ports: vec![listener.local_addr().unwrap().port()],
+            connectivity_timeout_secs: 1,
+            connectivity_retries: 1,
+            lookup_retries: 1,
+            provider_strategy: strategy.to_string(),
+            ip_providers: servers
+                .iter()
+                .map(|s| format!("custom={}", s.url("/json")))
+                .collect(),
+            ..Default::default()
+        };
+        let provider = check_vpn::ip_api::provider_from_config(&eff).expect("provider");
+        perform_check(&eff, || provider.get_ip_info(), |_, _| {}).expect("perform_check")
+    };
+
+    let failing = [serve(503, &[], ""), serve(503, &[], "")];
+    let outcome = check("failover", &failing);
+    assert!(matches!(
+        outcome,
+        CheckOutcome::LookupFailed(Some(IpLookupError::Http { status: 503 }))
+    ));
+    assert_eq!(outcome.exit_code(), Some(config::EXIT_ISP_FAILURE));
+
+    let limited = [
+        serve(429, &[("Retry-After", "30")], ""),
+        serve(429, &[("Retry-After", "30")], ""),
+        serve(429, &[("Retry-After", "30")], ""),
+    ];
+    let outcome = check("quorum", &limited);
+    assert!(matches!(
+        outcome,
+        CheckOutcome::LookupFailed(Some(IpLookupError::RateLimited { .. }))
+    ));
+    assert_eq!(outcome.exit_code(), Some(config::EXIT_LOOKUP_RATE_LIMITED));
+    assert!(outcome.is_transient());
+}
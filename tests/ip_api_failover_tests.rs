@@ -0,0 +1,105 @@
+use httpmock::Method::GET;
+use httpmock::MockServer;
+
+use check_vpn::app::perform_check;
+use check_vpn::config::{self, EffectiveConfig};
+use check_vpn::ip_api::{provider_from_config, IpLookupError};
+use std::net::TcpListener;
+
+fn failover_config(servers: &[&MockServer]) -> EffectiveConfig {
+    EffectiveConfig {
+        provider_strategy: "failover".to_string(),
+        ip_providers: servers
+            .iter()
+            .map(|s| format!("custom={}", s.url("/json")))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn rate_limited_primary_falls_back_to_secondary() {
+    let primary = MockServer::start();
+    let limited = primary.mock(|when, then| {
+        when.method(GET).path("/json");
+        then.status(429);
+    });
+    let secondary = MockServer::start();
+    let ok = secondary.mock(|when, then| {
+        when.method(GET).path("/json");
+        then.status(200).body(r#"{"isp":"Fallback ISP"}"#);
+    });
+
+    let provider =
+        provider_from_config(&failover_config(&[&primary, &secondary])).expect("failover provider");
+    assert_eq!(provider.get_isp().expect("lookup"), "Fallback ISP");
+    let limited_calls = limited.calls();
+    assert!(limited_calls >= 1);
+
+    // Later checks go straight to the healthy secondary.
+    assert_eq!(provider.get_isp().expect("lookup"), "Fallback ISP");
+    assert_eq!(ok.calls(), 2);
+    assert_eq!(limited.calls(), limited_calls);
+}
+
+#[test]
+fn all_providers_failing_is_an_error() {
+    let a = MockServer::start();
+    a.mock(|when, then| {
+        when.method(GET).path("/json");
+        then.status(500);
+    });
+    let b = MockServer::start();
+    b.mock(|when, then| {
+        when.method(GET).path("/json");
+        then.status(200).body("not json");
+    });
+
+    let provider = provider_from_config(&failover_config(&[&a, &b])).expect("failover provider");
+    let err = provider.get_ip_info().unwrap_err();
+    assert!(
+        err.to_string().contains("all providers failed"),
+        "unexpected error: {}",
+        err
+    );
+    // The last provider's failure is kept as the typed error.
+    assert!(matches!(
+        err.downcast_ref::<IpLookupError>(),
+        Some(IpLookupError::Parse(_))
+    ));
+}
+
+#[test]
+fn all_providers_rate_limited_is_transient() {
+    let servers: Vec<MockServer> = (0..2)
+        .map(|_| {
+            let server = MockServer::start();
+            server.mock(|when, then| {
+                when.method(GET).path("/json");
+                then.status(429).header("Retry-After", "30");
+            });
+            server
+        })
+        .collect();
+
+    // Local listener so the connectivity stage passes without real network.
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let eff = EffectiveConfig {
+        dry_run: true,
+        exit_on_error: true,
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![listener.local_addr().unwrap().port()],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        ..failover_config(&[&servers[0], &servers[1]])
+    };
+    let provider = provider_from_config(&eff).expect("failover provider");
+    let outcome = perform_check(
+        &eff,
+        || provider.get_ip_info(),
+        |_, _| panic!("a rate-limited lookup must not run the action"),
+    )
+    .expect("perform_check");
+    assert_eq!(outcome.exit_code(), Some(config::EXIT_LOOKUP_RATE_LIMITED));
+    assert!(outcome.is_transient());
+}
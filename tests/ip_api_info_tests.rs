@@ -0,0 +1,47 @@
+use httpmock::Method::GET;
+use httpmock::MockServer;
+use std::time::Duration;
+
+#[test]
+fn get_ip_info_exposes_full_record() {
+    let server = MockServer::start();
+    let body = std::fs::read_to_string("examples/ip_api.json").expect("example json");
+    let _m = server.mock(|when, then| {
+        when.method(GET).path("/json");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(body);
+    });
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(2))
+        .build()
+        .expect("client build");
+    let info = check_vpn::ip_api::get_ip_info_with_client_and_url(&client, &server.url("/json"), 1)
+        .expect("lookup");
+
+    assert_eq!(info.ip, Some("46.246.8.48".parse().unwrap()));
+    assert_eq!(info.isp.as_deref(), Some("GleSYS AB"));
+    assert_eq!(info.org.as_deref(), Some("PRIVATE INTERNET ACCESS, Inc"));
+    assert_eq!(info.asn, Some(42708));
+    assert_eq!(info.country_code.as_deref(), Some("SE"));
+    assert_eq!(info.city.as_deref(), Some("Stockholm"));
+    assert_eq!(info.timezone.as_deref(), Some("Europe/Stockholm"));
+}
+
+#[test]
+fn get_ip_info_tolerates_missing_fields() {
+    let server = MockServer::start();
+    let _m = server.mock(|when, then| {
+        when.method(GET).path("/json");
+        then.status(200)
+            .body(r#"{"query":"not-an-ip","isp":"Only ISP"}"#);
+    });
+
+    let client = reqwest::blocking::Client::new();
+    let info = check_vpn::ip_api::get_ip_info_with_client_and_url(&client, &server.url("/json"), 1)
+        .expect("lookup");
+    assert_eq!(info.isp.as_deref(), Some("Only ISP"));
+    assert_eq!(info.ip, None);
+    assert_eq!(info.asn, None);
+}
@@ -0,0 +1,146 @@
+use httpmock::Method::GET;
+use httpmock::MockServer;
+use std::time::Duration;
+
+use check_vpn::config::EffectiveConfig;
+use check_vpn::ip_api::{provider_from_config, HttpProvider, IpInfoProvider, ProviderKind};
+
+fn client() -> reqwest::blocking::Client {
+    reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(2))
+        .build()
+        .expect("client build")
+}
+
+fn provider_for(
+    kind: ProviderKind,
+    server: &MockServer,
+    body: &str,
+    field: Option<&str>,
+) -> HttpProvider {
+    let body = body.to_string();
+    server.mock(move |when, then| {
+        when.method(GET).path("/json");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(body);
+    });
+    HttpProvider::new(kind, Some(&server.url("/json")), field, client(), 1).expect("provider")
+}
+
+#[test]
+fn ip_api_provider_reads_isp() {
+    let server = MockServer::start();
+    let p = provider_for(ProviderKind::IpApi, &server, r#"{"isp":"GleSYS AB"}"#, None);
+    assert_eq!(p.get_isp().expect("lookup"), "GleSYS AB");
+    assert_eq!(p.name(), "ip-api");
+}
+
+#[test]
+fn ipinfo_provider_reads_org_without_asn() {
+    let server = MockServer::start();
+    let body = r#"{"ip":"46.246.8.48","country":"SE","org":"AS42708 GleSYS AB"}"#;
+    let p = provider_for(ProviderKind::IpInfo, &server, body, None);
+    assert_eq!(p.get_isp().expect("lookup"), "GleSYS AB");
+}
+
+#[test]
+fn ifconfig_provider_reads_asn_org() {
+    let server = MockServer::start();
+    let body = r#"{"ip":"46.246.8.48","asn":"AS42708","asn_org":"GleSYS AB"}"#;
+    let p = provider_for(ProviderKind::IfconfigCo, &server, body, None);
+    assert_eq!(p.get_isp().expect("lookup"), "GleSYS AB");
+}
+
+#[test]
+fn custom_provider_reads_configured_field() {
+    let server = MockServer::start();
+    let body = r#"{"result":{"network":{"provider":"Custom ISP"}}}"#;
+    let p = provider_for(
+        ProviderKind::Custom,
+        &server,
+        body,
+        Some("result.network.provider"),
+    );
+    assert_eq!(p.get_isp().expect("lookup"), "Custom ISP");
+}
+
+#[test]
+fn custom_provider_missing_field_errors() {
+    let server = MockServer::start();
+    let p = provider_for(ProviderKind::Custom, &server, r#"{"other":"x"}"#, None);
+    let err = p.get_isp().unwrap_err().to_string();
+    assert!(
+        err.contains("isp field missing"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn provider_from_config_uses_configured_url() {
+    let server = MockServer::start();
+    let _m = server.mock(|when, then| {
+        when.method(GET).path("/whoami");
+        then.status(200).body(r#"{"asn_org":"Configured ISP"}"#);
+    });
+
+    let eff = EffectiveConfig {
+        ip_provider: "ifconfig.co".to_string(),
+        ip_provider_url: Some(server.url("/whoami")),
+        ..Default::default()
+    };
+    let p = provider_from_config(&eff).expect("provider from config");
+    assert_eq!(p.get_isp().expect("lookup"), "Configured ISP");
+}
+
+#[test]
+fn ipinfo_provider_maps_full_record() {
+    let server = MockServer::start();
+    let body = r#"{"ip":"46.246.8.48","city":"Stockholm","country":"SE","org":"AS42708 GleSYS AB","timezone":"Europe/Stockholm"}"#;
+    let p = provider_for(ProviderKind::IpInfo, &server, body, None);
+    let info = p.get_ip_info().expect("lookup");
+    assert_eq!(info.ip, Some("46.246.8.48".parse().unwrap()));
+    assert_eq!(info.asn, Some(42708));
+    assert_eq!(info.country_code.as_deref(), Some("SE"));
+    assert_eq!(info.timezone.as_deref(), Some("Europe/Stockholm"));
+}
+
+#[test]
+fn ifconfig_provider_maps_full_record() {
+    let server = MockServer::start();
+    let body = r#"{"ip":"2001:db8::1","country":"Sweden","country_iso":"SE","asn":"AS42708","asn_org":"GleSYS AB","time_zone":"Europe/Stockholm"}"#;
+    let p = provider_for(ProviderKind::IfconfigCo, &server, body, None);
+    let info = p.get_ip_info().expect("lookup");
+    assert_eq!(info.ip, Some("2001:db8::1".parse().unwrap()));
+    assert_eq!(info.asn, Some(42708));
+    assert_eq!(info.country.as_deref(), Some("Sweden"));
+    assert_eq!(info.country_code.as_deref(), Some("SE"));
+}
+
+#[test]
+fn provider_from_config_applies_lookup_settings() {
+    let server = MockServer::start();
+    let m = server.mock(|when, then| {
+        when.method(GET)
+            .path("/json")
+            .header("user-agent", "watchdog-test/2.0");
+        then.status(200).body(r#"{"isp":"Configured ISP","padding":"xxxxxxxxxxxxxxxx"}"#);
+    });
+
+    let mut eff = EffectiveConfig {
+        ip_provider_url: Some(server.url("/json")),
+        user_agent: "watchdog-test/2.0".to_string(),
+        lookup_timeout_secs: 2,
+        lookup_retries: 1,
+        ..Default::default()
+    };
+    let p = provider_from_config(&eff).expect("provider from config");
+    assert_eq!(p.get_isp().expect("lookup"), "Configured ISP");
+    m.assert();
+
+    eff.max_response_bytes = 16;
+    let p = provider_from_config(&eff).expect("provider from config");
+    let err = p.get_isp().unwrap_err().to_string();
+    assert!(err.contains("response too large"), "unexpected error: {}", err);
+}
@@ -0,0 +1,114 @@
+use httpmock::Method::GET;
+use httpmock::MockServer;
+
+use check_vpn::app::perform_check;
+use check_vpn::config::EffectiveConfig;
+use check_vpn::ip_api::provider_from_config;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+// Each mock server plays one lookup service; `None` makes it fail with 500.
+fn lookup_server(isp: Option<&str>) -> MockServer {
+    let server = MockServer::start();
+    let isp = isp.map(str::to_string);
+    server.mock(move |when, then| {
+        when.method(GET).path("/json");
+        match isp {
+            Some(isp) => {
+                then.status(200)
+                    .header("content-type", "application/json")
+                    .body(format!(r#"{{"isp":"{}","query":"203.0.113.7"}}"#, isp));
+            }
+            None => {
+                then.status(500);
+            }
+        }
+    });
+    server
+}
+
+fn quorum_config(servers: &[MockServer], quorum: Option<usize>) -> EffectiveConfig {
+    EffectiveConfig {
+        dry_run: true,
+        isp_to_check: "Home ISP".to_string(),
+        provider_strategy: "quorum".to_string(),
+        ip_providers: servers
+            .iter()
+            .map(|s| format!("custom={}", s.url("/json")))
+            .collect(),
+        provider_quorum: quorum,
+        ..Default::default()
+    }
+}
+
+fn action_runs(eff: &EffectiveConfig) -> bool {
+    // Local listener so the connectivity stage passes without real network.
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let eff = &EffectiveConfig {
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![listener.local_addr().unwrap().port()],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        ..eff.clone()
+    };
+    let provider = provider_from_config(eff).expect("quorum provider");
+    let called = Arc::new(Mutex::new(false));
+    let c = called.clone();
+    perform_check(
+        eff,
+        || provider.get_ip_info(),
+        move |_, _| {
+            *c.lock().unwrap() = true;
+        },
+    )
+    .expect("perform_check");
+    let ran = *called.lock().unwrap();
+    ran
+}
+
+#[test]
+fn majority_lost_triggers_action() {
+    let servers = [
+        lookup_server(Some("Home ISP")),
+        lookup_server(Some("Home ISP")),
+        lookup_server(Some("VPN Exit")),
+    ];
+    assert!(action_runs(&quorum_config(&servers, None)));
+}
+
+#[test]
+fn single_bad_lookup_is_outvoted() {
+    let servers = [
+        lookup_server(Some("Home ISP")),
+        lookup_server(Some("VPN Exit")),
+        lookup_server(Some("VPN Exit")),
+    ];
+    assert!(!action_runs(&quorum_config(&servers, None)));
+}
+
+#[test]
+fn failed_providers_do_not_reach_quorum() {
+    let servers = [
+        lookup_server(Some("Home ISP")),
+        lookup_server(None),
+        lookup_server(None),
+    ];
+    let eff = quorum_config(&servers, None);
+    let provider = provider_from_config(&eff).expect("quorum provider");
+    let err = provider.get_ip_info().unwrap_err().to_string();
+    assert!(
+        err.contains("no provider quorum"),
+        "unexpected error: {}",
+        err
+    );
+    assert!(!action_runs(&eff));
+}
+
+#[test]
+fn configured_quorum_of_one_acts_on_any_lost_vote() {
+    let servers = [
+        lookup_server(Some("VPN Exit")),
+        lookup_server(Some("Home ISP")),
+    ];
+    assert!(action_runs(&quorum_config(&servers, Some(1))));
+}
@@ -0,0 +1,126 @@
+use httpmock::Method::GET;
+use httpmock::MockServer;
+use std::cell::Cell;
+use std::net::TcpListener;
+use std::time::Duration;
+
+use check_vpn::app::{perform_check, CheckOutcome};
+use check_vpn::config::EffectiveConfig;
+use check_vpn::ip_api::{
+    provider_from_config, HttpProvider, IpInfoProvider, IpLookupError, ProviderKind,
+};
+
+fn limited_provider(server: &MockServer, per_minute: Option<u32>) -> HttpProvider {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(2))
+        .build()
+        .expect("client build");
+    HttpProvider::new(
+        ProviderKind::IpApi,
+        Some(&server.url("/json")),
+        None,
+        client,
+        1,
+    )
+    .expect("provider")
+    .with_rate_limit(per_minute)
+}
+
+fn rate_limit_error(p: &HttpProvider) -> Option<IpLookupError> {
+    p.get_isp()
+        .unwrap_err()
+        .downcast_ref::<IpLookupError>()
+        .cloned()
+}
+
+#[test]
+fn local_budget_skips_excess_lookups() {
+    let server = MockServer::start();
+    let m = server.mock(|when, then| {
+        when.method(GET).path("/json");
+        then.status(200).body(r#"{"isp":"VPN Exit"}"#);
+    });
+
+    let p = limited_provider(&server, Some(2));
+    assert!(p.get_isp().is_ok());
+    assert!(p.get_isp().is_ok());
+    assert!(matches!(
+        rate_limit_error(&p),
+        Some(IpLookupError::RateLimited { .. })
+    ));
+    assert_eq!(m.calls(), 2);
+}
+
+#[test]
+fn ip_api_headers_close_the_budget() {
+    let server = MockServer::start();
+    let m = server.mock(|when, then| {
+        when.method(GET).path("/json");
+        then.status(200)
+            .header("X-Rl", "0")
+            .header("X-Ttl", "42")
+            .body(r#"{"isp":"VPN Exit"}"#);
+    });
+
+    let p = limited_provider(&server, Some(45));
+    assert!(p.get_isp().is_ok());
+    match rate_limit_error(&p) {
+        Some(IpLookupError::RateLimited {
+            retry_after: Some(d),
+        }) => assert!(d > Duration::from_secs(40) && d <= Duration::from_secs(42)),
+        other => panic!("unexpected error: {:?}", other),
+    }
+    assert_eq!(m.calls(), 1);
+}
+
+#[test]
+fn too_many_requests_blocks_without_sleeping() {
+    let server = MockServer::start();
+    let m = server.mock(|when, then| {
+        when.method(GET).path("/json");
+        then.status(429).header("Retry-After", "30");
+    });
+
+    let p = limited_provider(&server, Some(45));
+    assert!(p.get_isp().is_err());
+    assert!(p.get_isp().is_err());
+    assert_eq!(m.calls(), 1);
+}
+
+#[test]
+fn exhausted_budget_is_unknown_not_vpn_lost() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/json");
+        then.status(200)
+            .header("X-Rl", "0")
+            .header("X-Ttl", "60")
+            .body(r#"{"isp":"VPN Exit"}"#);
+    });
+
+    // Local listener so the connectivity stage passes without real network.
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let eff = EffectiveConfig {
+        isp_to_check: "VPN Exit".to_string(),
+        dry_run: true,
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![listener.local_addr().unwrap().port()],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        ip_provider_url: Some(server.url("/json")),
+        ..Default::default()
+    };
+    let provider = provider_from_config(&eff).expect("provider");
+    // First lookup spends the last request of the window.
+    assert!(provider.get_ip_info().is_ok());
+
+    let called = Cell::new(false);
+    let outcome = perform_check(&eff, || provider.get_ip_info(), |_, _| called.set(true))
+        .expect("perform_check");
+    assert!(!called.get(), "skipped lookup must not trigger the action");
+    assert!(matches!(
+        outcome,
+        CheckOutcome::LookupFailed(Some(IpLookupError::RateLimited { .. }))
+    ));
+    assert!(outcome.is_transient());
+}
@@ -0,0 +1,159 @@
+use std::fs;
+use std::net::{IpAddr, SocketAddr, TcpListener, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use check_vpn::app::{perform_check, CheckOutcome};
+use check_vpn::config::{Config, EffectiveConfig};
+use check_vpn::ip_api::{provider_from_config, IpInfoProvider, IpLookupError, StunProvider};
+
+const MAGIC_COOKIE: [u8; 4] = [0x21, 0x12, 0xA4, 0x42];
+
+/// A local STUN responder that reports `mapped` (or, when None, the
+/// client's own source address) in an XOR-MAPPED-ADDRESS attribute.
+/// `error` makes it answer with a Binding error response instead.
+fn responder(mapped: Option<&'static str>, error: Option<u16>) -> String {
+    let socket = UdpSocket::bind("127.0.0.1:0").expect("bind udp");
+    let addr = socket.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        while let Ok((n, peer)) = socket.recv_from(&mut buf) {
+            let req = &buf[..n];
+            if n < 20 || req[..2] != [0x00, 0x01] || req[4..8] != MAGIC_COOKIE {
+                continue;
+            }
+            let mut attr = Vec::new();
+            let msg_type: u16 = match error {
+                Some(code) => {
+                    attr.extend_from_slice(&[0x00, 0x09, 0x00, 0x04]);
+                    attr.extend_from_slice(&[0, 0, (code / 100) as u8, (code % 100) as u8]);
+                    0x0111
+                }
+                None => {
+                    let ip = mapped
+                        .map(|m| m.parse::<IpAddr>().unwrap())
+                        .unwrap_or(peer.ip());
+                    let IpAddr::V4(v4) = ip else {
+                        panic!("responder only maps IPv4")
+                    };
+                    let port = peer.port() ^ 0x2112;
+                    attr.extend_from_slice(&[0x00, 0x20, 0x00, 0x08, 0x00, 0x01]);
+                    attr.extend_from_slice(&port.to_be_bytes());
+                    attr.extend(v4.octets().iter().zip(MAGIC_COOKIE).map(|(a, k)| a ^ k));
+                    0x0101
+                }
+            };
+            let mut resp = msg_type.to_be_bytes().to_vec();
+            resp.extend_from_slice(&(attr.len() as u16).to_be_bytes());
+            resp.extend_from_slice(&req[4..20]);
+            resp.extend_from_slice(&attr);
+            let _ = socket.send_to(&resp, peer);
+        }
+    });
+    addr
+}
+
+/// An address with nothing listening, so the query is refused.
+fn closed_port() -> SocketAddr {
+    UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+#[test]
+fn binding_request_returns_mapped_address() {
+    let addr = responder(None, None);
+    let p = StunProvider::new(vec![addr], Duration::from_secs(2), 1);
+    let info = p.get_ip_info().expect("stun lookup");
+    assert_eq!(info.ip, Some("127.0.0.1".parse().unwrap()));
+    assert_eq!(info.isp, None);
+    assert_eq!(p.name(), "stun");
+}
+
+#[test]
+fn falls_back_to_next_server() {
+    let good = responder(Some("203.0.113.7"), None);
+    let p = StunProvider::new(
+        vec![closed_port().to_string(), good],
+        Duration::from_millis(500),
+        1,
+    );
+    let info = p.get_ip_info().expect("stun lookup");
+    assert_eq!(info.ip, Some("203.0.113.7".parse().unwrap()));
+}
+
+#[test]
+fn error_response_and_silent_server() {
+    let addr = responder(None, Some(500));
+    let p = StunProvider::new(vec![addr], Duration::from_secs(2), 1);
+    let err = p.get_ip_info().unwrap_err();
+    match err.downcast_ref::<IpLookupError>() {
+        Some(IpLookupError::Parse(msg)) => assert!(msg.contains("error 500"), "{}", msg),
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    let silent = UdpSocket::bind("127.0.0.1:0").expect("bind udp");
+    let addr = silent.local_addr().unwrap().to_string();
+    let p = StunProvider::new(vec![addr], Duration::from_millis(200), 2);
+    let err = p.get_ip_info().unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<IpLookupError>(),
+        Some(&IpLookupError::Timeout)
+    );
+}
+
+#[test]
+fn stun_servers_from_xml_feed_cidr_check() {
+    let good = responder(Some("203.0.113.7"), None);
+    let xml = format!(
+        r#"<config>
+      <ip_provider>stun</ip_provider>
+      <stun_servers>{}</stun_servers>
+      <stun_servers>{}</stun_servers>
+      <cidr_to_check>203.0.113.0/24</cidr_to_check>
+      <lookup_timeout_secs>1</lookup_timeout_secs>
+    </config>"#,
+        closed_port(),
+        good
+    );
+    let mut path = std::env::temp_dir();
+    path.push(format!(
+        "check_vpn_stun_{}.xml",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    ));
+    let path_str = path.to_str().unwrap();
+    fs::write(path_str, xml).expect("write temp xml");
+    let cfg = Config::load_from_path(path_str).expect("load config");
+    let _ = fs::remove_file(path_str);
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let eff = EffectiveConfig {
+        dry_run: true,
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![listener.local_addr().unwrap().port()],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        ..cfg.merge_with_args(&check_vpn::cli::Args::default())
+    };
+    assert_eq!(eff.stun_servers.len(), 2);
+    assert!(Config::validate_effective(&eff).is_ok());
+
+    let provider = provider_from_config(&eff).expect("stun provider");
+    let called = Arc::new(Mutex::new(false));
+    let c = called.clone();
+    let outcome = perform_check(
+        &eff,
+        || provider.get_ip_info(),
+        move |_, _| {
+            *c.lock().unwrap() = true;
+        },
+    )
+    .expect("check");
+    assert!(matches!(outcome, CheckOutcome::VpnLost));
+    assert!(*called.lock().unwrap());
+}
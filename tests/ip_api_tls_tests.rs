@@ -0,0 +1,123 @@
+use native_tls::{Identity, TlsAcceptor};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+
+use check_vpn::app::{perform_check, CheckOutcome};
+use check_vpn::config::EffectiveConfig;
+use check_vpn::ip_api::{provider_from_config, IpLookupError};
+
+const CA_BUNDLE: &str = "tests/fixtures/tls/server.crt";
+// SPKI pin of tests/fixtures/tls/server.crt (see tests/fixtures/tls/README.md).
+const SERVER_PIN: &str = "sha256/tJNV4o/LESY35TRGTA4nietgeSVXNR2QeFq2tiNi1JI=";
+// A well-formed pin for some other key.
+const OTHER_PIN: &str = "sha256/M9xDrZ56vbXQA/wPi6UXhNh8Rl1cVKk77oSUSOygwfs=";
+
+/// Minimal HTTPS server using the self-signed fixture certificate. Every
+/// connection gets the same JSON body; failed handshakes are ignored.
+fn start_tls_server(body: &'static str) -> String {
+    let cert = std::fs::read("tests/fixtures/tls/server.crt").expect("cert");
+    let key = std::fs::read("tests/fixtures/tls/server.key").expect("key");
+    let identity = Identity::from_pkcs8(&cert, &key).expect("identity");
+    let acceptor = Arc::new(TlsAcceptor::new(identity).expect("acceptor"));
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let url = format!(
+        "https://127.0.0.1:{}/json",
+        listener.local_addr().unwrap().port()
+    );
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let Ok(mut tls) = acceptor.accept(stream) else {
+                continue;
+            };
+            let mut buf = [0u8; 4096];
+            let mut req = Vec::new();
+            while !req.windows(4).any(|w| w == b"\r\n\r\n") {
+                match tls.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => req.extend_from_slice(&buf[..n]),
+                }
+            }
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = tls.write_all(resp.as_bytes());
+            let _ = tls.shutdown();
+        }
+    });
+    url
+}
+
+fn https_config(url: &str, ca: Option<&str>, pins: &[&str]) -> EffectiveConfig {
+    EffectiveConfig {
+        ip_provider_url: Some(url.to_string()),
+        tls_ca_bundle: ca.map(str::to_string),
+        tls_spki_pin: pins.iter().map(|p| p.to_string()).collect(),
+        lookup_timeout_secs: 2,
+        ..Default::default()
+    }
+}
+
+fn lookup_error(eff: &EffectiveConfig) -> IpLookupError {
+    let provider = provider_from_config(eff).expect("provider");
+    let err = provider.get_isp().expect_err("lookup should fail");
+    err.downcast_ref::<IpLookupError>()
+        .cloned()
+        .unwrap_or_else(|| panic!("not an IpLookupError: {}", err))
+}
+
+#[test]
+fn custom_ca_bundle_and_matching_pin_succeed() {
+    let url = start_tls_server(r#"{"isp":"Pinned ISP"}"#);
+    let eff = https_config(&url, Some(CA_BUNDLE), &[OTHER_PIN, SERVER_PIN]);
+    let provider = provider_from_config(&eff).expect("provider");
+    assert_eq!(provider.get_isp().expect("lookup"), "Pinned ISP");
+}
+
+#[test]
+fn untrusted_self_signed_certificate_fails() {
+    let url = start_tls_server(r#"{"isp":"Pinned ISP"}"#);
+    assert!(matches!(
+        lookup_error(&https_config(&url, None, &[])),
+        IpLookupError::Transport(_)
+    ));
+}
+
+#[test]
+fn pin_mismatch_fails_the_lookup_not_the_vpn() {
+    let url = start_tls_server(r#"{"isp":"Forged ISP"}"#);
+    let eff = EffectiveConfig {
+        isp_to_check: "Forged ISP".to_string(),
+        dry_run: true,
+        ..https_config(&url, Some(CA_BUNDLE), &[OTHER_PIN])
+    };
+    match lookup_error(&eff) {
+        IpLookupError::Tls(msg) => assert!(msg.contains(SERVER_PIN), "{}", msg),
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let eff = EffectiveConfig {
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![listener.local_addr().unwrap().port()],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        ..eff
+    };
+    let provider = provider_from_config(&eff).expect("provider");
+    let outcome = perform_check(
+        &eff,
+        || provider.get_ip_info(),
+        |_, _| panic!("pin mismatch must not trigger the action"),
+    )
+    .expect("perform_check");
+    assert!(matches!(
+        outcome,
+        CheckOutcome::LookupFailed(Some(IpLookupError::Tls(_)))
+    ));
+}
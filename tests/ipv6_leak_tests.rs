@@ -0,0 +1,102 @@
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use check_vpn::actions::Action;
+use check_vpn::app::{check_ipv6_leak, Ipv6Status};
+use check_vpn::config::EffectiveConfig;
+use check_vpn::ip_api::{provider_for_family, provider_from_config, IpFamily};
+
+/// Serve `body` as JSON to every connection on `addr` ("127.0.0.1:0" or
+/// "[::1]:0") and return the bound port.
+fn json_server(addr: &str, body: &'static str) -> u16 {
+    let listener = TcpListener::bind(addr).expect("bind listener");
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf);
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(resp.as_bytes());
+        }
+    });
+    port
+}
+
+fn custom_eff(url: String) -> EffectiveConfig {
+    EffectiveConfig {
+        ip_provider: "custom".to_string(),
+        ip_provider_url: Some(url),
+        isp_to_check: "Home ISP".to_string(),
+        ipv6_leak_check: true,
+        ipv6_leak_action_type: Some("command".to_string()),
+        ipv6_leak_action_arg: "/usr/local/bin/block-v6".to_string(),
+        lookup_timeout_secs: 2,
+        lookup_retries: 1,
+        dry_run: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn ipv4_forced_lookup_uses_ipv4_only() {
+    let port = json_server("127.0.0.1:0", r#"{"isp":"VPN Provider"}"#);
+    let eff = custom_eff(format!("http://localhost:{}/json", port));
+
+    let v4 = provider_for_family(&eff, Some(IpFamily::V4)).unwrap();
+    assert_eq!(v4.get_isp().unwrap(), "VPN Provider");
+
+    let v4 = provider_for_family(
+        &custom_eff(format!("http://[::1]:{}/json", port)),
+        Some(IpFamily::V4),
+    )
+    .unwrap();
+    let err = v4.get_ip_info().unwrap_err().to_string();
+    assert!(err.contains("is not an IPv4 address"), "{}", err);
+}
+
+#[test]
+fn home_isp_over_ipv6_runs_leak_action() {
+    let port = json_server("[::1]:0", r#"{"isp":"Home ISP","query":"2001:db8::7"}"#);
+    let eff = custom_eff(format!("http://[::1]:{}/json", port));
+
+    // The main lookup is forced over IPv4 and cannot reach this server.
+    assert!(provider_from_config(&eff).unwrap().get_ip_info().is_err());
+
+    let v6 = provider_for_family(&eff, Some(IpFamily::V6)).unwrap();
+    let ran = RefCell::new(Vec::new());
+    let status = check_ipv6_leak(
+        &eff,
+        || v6.get_ip_info(),
+        |a, dry| ran.borrow_mut().push((a.clone(), dry)),
+    )
+    .unwrap();
+    match status {
+        Ipv6Status::Leak(info) => assert_eq!(info.ip, Some("2001:db8::7".parse().unwrap())),
+        other => panic!("expected leak, got {:?}", other),
+    }
+    assert_eq!(
+        ran.into_inner(),
+        vec![(Action::Command("/usr/local/bin/block-v6".to_string()), true)]
+    );
+}
+
+#[test]
+fn unreachable_ipv6_lookup_is_reported_unavailable() {
+    let port = json_server("127.0.0.1:0", r#"{"isp":"Home ISP"}"#);
+    let eff = custom_eff(format!("http://127.0.0.1:{}/json", port));
+
+    let v6 = provider_for_family(&eff, Some(IpFamily::V6)).unwrap();
+    let status = check_ipv6_leak(
+        &eff,
+        || v6.get_ip_info(),
+        |_, _| panic!("no action expected"),
+    )
+    .unwrap();
+    assert!(matches!(status, Ipv6Status::Unavailable(_)));
+}
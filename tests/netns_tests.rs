@@ -0,0 +1,67 @@
+use std::io;
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+
+use check_vpn::networking::netns::{run_in, NetnsTarget};
+use check_vpn::networking::{is_online_with_bind, read_interface, SocketBind, SYS_CLASS_NET};
+
+/// Park a thread in a fresh network namespace (only a down `lo`) and return
+/// its thread ID plus a sender that releases it. None when namespaces
+/// cannot be created here (no CAP_SYS_ADMIN).
+fn fresh_namespace() -> Option<(u32, mpsc::Sender<()>)> {
+    let (tid_tx, tid_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        // SAFETY: only this thread moves to the new namespace.
+        if unsafe { libc::unshare(libc::CLONE_NEWNET) } != 0 {
+            let _ = tid_tx.send(Err(io::Error::last_os_error()));
+            return;
+        }
+        let _ = tid_tx.send(Ok(unsafe { libc::gettid() } as u32));
+        let _ = done_rx.recv();
+    });
+    match tid_rx.recv().unwrap() {
+        Ok(tid) => Some((tid, done_tx)),
+        Err(e) => {
+            eprintln!("skipping: cannot create a network namespace: {}", e);
+            None
+        }
+    }
+}
+
+#[test]
+fn probes_run_inside_the_target_namespace() {
+    let Some((tid, _done)) = fresh_namespace() else {
+        return;
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let probe = move || is_online_with_bind(&["127.0.0.1"], 1, &[port], 1, &SocketBind::default());
+    assert!(probe().unwrap());
+
+    let (online, lo, links) = run_in(&NetnsTarget::Pid(tid), move || {
+        let links: Vec<_> = std::fs::read_dir(SYS_CLASS_NET)?
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        Ok((probe()?, read_interface("lo")?, links))
+    })
+    .unwrap();
+    // The listener lives in this namespace, and lo is down in the new one.
+    assert!(!online);
+    assert!(!lo.expect("lo exists in every namespace").is_up());
+    // /sys was remounted, so it lists the namespace's interfaces only.
+    assert_eq!(links, ["lo"]);
+}
+
+#[test]
+fn missing_namespace_is_an_error() {
+    let target = NetnsTarget::Named("check-vpn-does-not-exist".to_string());
+    let err = run_in(&target, || Ok(())).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("/var/run/netns/check-vpn-does-not-exist"),
+        "{:#}",
+        err
+    );
+}
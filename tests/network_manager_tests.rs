@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use check_vpn::networking::network_manager::{
+    activate, active_connections, vpn_down_reason, ActiveState,
+};
+use zbus::blocking::{connection, Connection};
+use zbus::interface;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Str};
+
+const PROFILE_PATH: &str = "/org/freedesktop/NetworkManager/Settings/7";
+const ACTIVE_PATH: &str = "/org/freedesktop/NetworkManager/ActiveConnection/3";
+
+/// Stand-in for the NetworkManager root object. Records activations.
+struct MockNm {
+    active: Vec<OwnedObjectPath>,
+    activated: Arc<Mutex<Vec<String>>>,
+}
+
+#[interface(name = "org.freedesktop.NetworkManager")]
+impl MockNm {
+    #[zbus(property)]
+    fn active_connections(&self) -> Vec<OwnedObjectPath> {
+        self.active.clone()
+    }
+
+    fn activate_connection(
+        &self,
+        connection: OwnedObjectPath,
+        _device: OwnedObjectPath,
+        _specific_object: OwnedObjectPath,
+    ) -> OwnedObjectPath {
+        self.activated.lock().unwrap().push(connection.to_string());
+        OwnedObjectPath::try_from(ACTIVE_PATH).unwrap()
+    }
+}
+
+struct MockActive {
+    state: u32,
+}
+
+#[interface(name = "org.freedesktop.NetworkManager.Connection.Active")]
+impl MockActive {
+    #[zbus(property)]
+    fn id(&self) -> String {
+        "work-vpn".to_string()
+    }
+
+    #[zbus(property)]
+    fn uuid(&self) -> String {
+        "4b8a7c2e-0f1d-4d35-9c2a-6f0e5b1d2a77".to_string()
+    }
+
+    #[zbus(property, name = "Type")]
+    fn kind(&self) -> String {
+        "vpn".to_string()
+    }
+
+    #[zbus(property)]
+    fn state(&self) -> u32 {
+        self.state
+    }
+}
+
+struct MockSettings;
+
+#[interface(name = "org.freedesktop.NetworkManager.Settings")]
+impl MockSettings {
+    fn list_connections(&self) -> Vec<OwnedObjectPath> {
+        vec![OwnedObjectPath::try_from(PROFILE_PATH).unwrap()]
+    }
+}
+
+struct MockProfile;
+
+#[interface(name = "org.freedesktop.NetworkManager.Settings.Connection")]
+impl MockProfile {
+    fn get_settings(&self) -> HashMap<String, HashMap<String, OwnedValue>> {
+        let connection = HashMap::from([
+            ("id".to_string(), OwnedValue::from(Str::from("work-vpn"))),
+            ("type".to_string(), OwnedValue::from(Str::from("vpn"))),
+        ]);
+        HashMap::from([("connection".to_string(), connection)])
+    }
+}
+
+/// Serve the mock NetworkManager over a private peer-to-peer connection and
+/// return (client, server); the server end must be kept alive. `state` is
+/// None when no VPN is active.
+fn mock_nm(state: Option<u32>, activated: Arc<Mutex<Vec<String>>>) -> (Connection, Connection) {
+    let (server_sock, client_sock) = UnixStream::pair().unwrap();
+    let server = thread::spawn(move || {
+        let nm = MockNm {
+            active: state
+                .iter()
+                .map(|_| OwnedObjectPath::try_from(ACTIVE_PATH).unwrap())
+                .collect(),
+            activated,
+        };
+        let mut builder = connection::Builder::unix_stream(server_sock)
+            .server(zbus::Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at("/org/freedesktop/NetworkManager", nm)
+            .unwrap()
+            .serve_at("/org/freedesktop/NetworkManager/Settings", MockSettings)
+            .unwrap()
+            .serve_at(PROFILE_PATH, MockProfile)
+            .unwrap();
+        if let Some(state) = state {
+            builder = builder.serve_at(ACTIVE_PATH, MockActive { state }).unwrap();
+        }
+        builder.build().unwrap()
+    });
+    let client = connection::Builder::unix_stream(client_sock)
+        .p2p()
+        .build()
+        .unwrap();
+    (client, server.join().unwrap())
+}
+
+#[test]
+fn activated_vpn_is_up() {
+    let (conn, _server) = mock_nm(Some(2), Arc::default());
+    let active = active_connections(&conn).unwrap();
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].id, "work-vpn");
+    assert_eq!(active[0].kind, "vpn");
+    assert_eq!(active[0].state, ActiveState::Activated);
+    assert_eq!(vpn_down_reason("work-vpn", &active), None);
+}
+
+#[test]
+fn activating_vpn_is_not_down() {
+    let (conn, _server) = mock_nm(Some(1), Arc::default());
+    let active = active_connections(&conn).unwrap();
+    assert_eq!(active[0].state, ActiveState::Activating);
+    assert_eq!(vpn_down_reason("work-vpn", &active), None);
+}
+
+#[test]
+fn deactivating_or_missing_vpn_is_down() {
+    let (conn, _server) = mock_nm(Some(3), Arc::default());
+    let active = active_connections(&conn).unwrap();
+    assert_eq!(
+        vpn_down_reason("work-vpn", &active).unwrap(),
+        "NetworkManager connection 'work-vpn' (vpn) is deactivating"
+    );
+
+    let (conn, _server) = mock_nm(None, Arc::default());
+    let active = active_connections(&conn).unwrap();
+    assert!(vpn_down_reason("work-vpn", &active)
+        .unwrap()
+        .contains("is not active"));
+}
+
+#[test]
+fn activate_finds_profile_by_id() {
+    let activated = Arc::new(Mutex::new(Vec::new()));
+    let (conn, _server) = mock_nm(None, activated.clone());
+    let path = activate(&conn, "work-vpn").unwrap();
+    assert_eq!(path.as_str(), ACTIVE_PATH);
+    assert_eq!(*activated.lock().unwrap(), vec![PROFILE_PATH.to_string()]);
+
+    let err = activate(&conn, "home-vpn").unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("no NetworkManager connection named 'home-vpn'"));
+}
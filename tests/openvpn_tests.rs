@@ -0,0 +1,211 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::os::unix::net::UnixListener;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use check_vpn::actions::runner::{ActionRunner, RealActionRunner};
+use check_vpn::actions::{parse_action, Action};
+use check_vpn::app::{perform_check, CheckOutcome};
+use check_vpn::config::EffectiveConfig;
+use check_vpn::ip_api::IpInfo;
+use check_vpn::networking::openvpn::{query, ByteCount, ManagementAddr, Signal};
+
+const GREETING: &str =
+    ">INFO:OpenVPN Management Interface Version 5 -- type 'help' for more info\r\n";
+
+/// Play OpenVPN on one accepted connection: send the greeting, then answer
+/// each command with the scripted reply. Returns the commands received.
+fn serve<S: Read + Write>(mut stream: S, script: &[(&str, &str)]) -> Vec<String> {
+    stream.write_all(GREETING.as_bytes()).unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut seen = Vec::new();
+    let mut line = String::new();
+    while reader.read_line(&mut line).unwrap_or(0) > 0 {
+        let cmd = line.trim_end().to_string();
+        line.clear();
+        let reply = script
+            .iter()
+            .find(|(c, _)| *c == cmd)
+            .map(|(_, r)| *r)
+            .unwrap_or("ERROR: unknown command\r\n");
+        seen.push(cmd);
+        reader.get_mut().write_all(reply.as_bytes()).unwrap();
+    }
+    seen
+}
+
+const CONNECTED: &[(&str, &str)] = &[
+    (
+        "state",
+        ">HOLD:Waiting for hold release:0\r\n1760690000,CONNECTED,SUCCESS,10.8.0.6,198.51.100.7,1194,,\r\nEND\r\n",
+    ),
+    (
+        "bytecount 1",
+        "SUCCESS: bytecount interval changed\r\n>BYTECOUNT:1843622,502340\r\n",
+    ),
+    ("bytecount 0", "SUCCESS: bytecount interval changed\r\n"),
+];
+
+#[test]
+fn state_and_bytecount_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let server = thread::spawn(move || serve(listener.accept().unwrap().0, CONNECTED));
+
+    let (state, bytes) = query(&addr.parse().unwrap(), Duration::from_secs(2)).unwrap();
+    assert!(state.is_connected());
+    assert_eq!(state.remote, Some("198.51.100.7:1194".parse().unwrap()));
+    assert_eq!(
+        bytes,
+        Some(ByteCount {
+            rx_bytes: 1_843_622,
+            tx_bytes: 502_340
+        })
+    );
+    assert_eq!(
+        server.join().unwrap(),
+        vec!["state", "bytecount 1", "bytecount 0"]
+    );
+}
+
+#[test]
+fn missing_byte_count_keeps_the_state() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        serve(
+            listener.accept().unwrap().0,
+            &[
+                (CONNECTED[0].0, CONNECTED[0].1),
+                ("bytecount 1", "SUCCESS: bytecount interval changed\r\n"),
+            ],
+        )
+    });
+
+    let (state, bytes) = query(&addr.parse().unwrap(), Duration::from_millis(300)).unwrap();
+    assert!(state.is_connected());
+    assert_eq!(bytes, None);
+}
+
+#[test]
+fn signal_action_over_unix_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("openvpn.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+    let server = thread::spawn(move || {
+        serve(
+            listener.accept().unwrap().0,
+            &[("signal SIGUSR1", "SUCCESS: signal SIGUSR1 thrown\r\n")],
+        )
+    });
+
+    let action = parse_action("openvpn-sigusr1", path.to_str().unwrap());
+    assert_eq!(
+        action,
+        Action::OpenVpnSignal {
+            management: path.to_str().unwrap().to_string(),
+            signal: Signal::Usr1
+        }
+    );
+    RealActionRunner::new().execute(&action, false).unwrap();
+    assert_eq!(server.join().unwrap(), vec!["signal SIGUSR1"]);
+}
+
+#[test]
+fn reconnecting_state_is_vpn_lost() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("openvpn.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+    thread::spawn(move || {
+        serve(
+            listener.accept().unwrap().0,
+            &[
+                // No byte count: OpenVPN only sends it while connected.
+                (
+                    "state",
+                    "1760690042,RECONNECTING,ping-restart,,,,,\r\nEND\r\n",
+                ),
+            ],
+        )
+    });
+
+    let probe = TcpListener::bind("127.0.0.1:0").unwrap();
+    let eff = EffectiveConfig {
+        openvpn_management: Some(path.to_str().unwrap().to_string()),
+        action_type: "openvpn-sighup".to_string(),
+        action_arg: path.to_str().unwrap().to_string(),
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![probe.local_addr().unwrap().port()],
+        connectivity_timeout_secs: 2,
+        connectivity_retries: 1,
+        isp_to_check: "Home ISP".to_string(),
+        dry_run: true,
+        ..Default::default()
+    };
+    let ran = Mutex::new(Vec::new());
+    let outcome = perform_check(
+        &eff,
+        || -> anyhow::Result<IpInfo> { panic!("lookup must not run") },
+        |a, _| ran.lock().unwrap().push(a.clone()),
+    )
+    .unwrap();
+    assert!(matches!(outcome, CheckOutcome::VpnLost));
+    assert_eq!(
+        ran.into_inner().unwrap(),
+        vec![Action::OpenVpnSignal {
+            management: path.to_str().unwrap().to_string(),
+            signal: Signal::Hup
+        }]
+    );
+}
+
+#[test]
+fn connecting_state_is_left_alone() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    thread::spawn(move || {
+        let state = format!("{},AUTH,,,,,,\r\nEND\r\n", now);
+        serve(listener.accept().unwrap().0, &[("state", &state)])
+    });
+
+    let probe = TcpListener::bind("127.0.0.1:0").unwrap();
+    let eff = EffectiveConfig {
+        openvpn_management: Some(addr.clone()),
+        action_type: "openvpn-sigusr1".to_string(),
+        action_arg: addr,
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![probe.local_addr().unwrap().port()],
+        connectivity_timeout_secs: 2,
+        connectivity_retries: 1,
+        isp_to_check: "Home ISP".to_string(),
+        dry_run: true,
+        ..Default::default()
+    };
+    let outcome = perform_check(
+        &eff,
+        || -> anyhow::Result<IpInfo> { Ok(IpInfo::from("VPN Exit".to_string())) },
+        |_, _| panic!("a connecting OpenVPN must not be signalled"),
+    )
+    .unwrap();
+    assert!(matches!(outcome, CheckOutcome::VpnActive));
+}
+
+#[test]
+fn password_prompt_is_reported() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr: ManagementAddr = listener.local_addr().unwrap().to_string().parse().unwrap();
+    thread::spawn(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        s.write_all(b"ENTER PASSWORD:").unwrap();
+        thread::sleep(Duration::from_secs(2));
+    });
+
+    let err = query(&addr, Duration::from_millis(300)).unwrap_err();
+    assert!(err.to_string().contains("asks for a password"), "{:#}", err);
+}
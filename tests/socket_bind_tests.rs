@@ -0,0 +1,72 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use check_vpn::config::EffectiveConfig;
+use check_vpn::ip_api::provider_from_config;
+use check_vpn::networking::{is_online_with_bind, SocketBind};
+
+/// Answer every request with the caller's address as ip-api's `query`, so
+/// tests can see which source address a lookup used.
+fn whoami_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind listener");
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf);
+            let peer = stream.peer_addr().unwrap().ip();
+            let body = format!(r#"{{"isp":"VPN Provider","query":"{}"}}"#, peer);
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(resp.as_bytes());
+        }
+    });
+    port
+}
+
+fn lookup_eff(port: u16, bind_interface: Option<&str>, source: &str) -> EffectiveConfig {
+    EffectiveConfig {
+        ip_provider: "custom".to_string(),
+        ip_provider_url: Some(format!("http://127.0.0.1:{}/json", port)),
+        bind_interface: bind_interface.map(str::to_string),
+        source_address: Some(source.to_string()),
+        lookup_timeout_secs: 2,
+        lookup_retries: 1,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn lookups_are_sent_from_the_source_address() {
+    let port = whoami_server();
+    for (iface, source) in [(None, "127.0.0.2"), (Some("lo"), "127.0.0.3")] {
+        let eff = lookup_eff(port, iface, source);
+        assert!(check_vpn::config::Config::validate_effective(&eff).is_ok());
+        let info = provider_from_config(&eff).unwrap().get_ip_info().unwrap();
+        assert_eq!(info.ip, Some(source.parse().unwrap()));
+    }
+}
+
+#[test]
+fn probes_follow_the_bound_interface() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let endpoints = ["127.0.0.1"];
+
+    let lo = SocketBind {
+        interface: Some("lo".to_string()),
+        source_address: None,
+    };
+    assert!(is_online_with_bind(&endpoints, 1, &[port], 1, &lo).unwrap());
+
+    // Nothing gets out of an interface that does not exist.
+    let missing = SocketBind {
+        interface: Some("wgnotthere0".to_string()),
+        source_address: None,
+    };
+    assert!(!is_online_with_bind(&endpoints, 1, &[port], 1, &missing).unwrap());
+}
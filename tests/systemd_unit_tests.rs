@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::os::unix::net::UnixStream;
+use std::thread;
+
+use check_vpn::networking::systemd::unit_state;
+use zbus::blocking::{connection, Connection};
+use zbus::interface;
+use zbus::zvariant::OwnedObjectPath;
+
+const UNIT_PATH: &str = "/org/freedesktop/systemd1/unit/wg_2dquick_40wg0_2eservice";
+
+/// Stand-in for the systemd manager: knows one unit.
+struct MockManager;
+
+#[interface(name = "org.freedesktop.systemd1.Manager")]
+impl MockManager {
+    fn load_unit(&self, name: &str) -> zbus::fdo::Result<OwnedObjectPath> {
+        if name == "wg-quick@wg0.service" {
+            Ok(OwnedObjectPath::try_from(UNIT_PATH).unwrap())
+        } else {
+            Err(zbus::fdo::Error::InvalidArgs(format!(
+                "Unit name {} is not valid.",
+                name
+            )))
+        }
+    }
+}
+
+struct MockUnit {
+    props: HashMap<&'static str, &'static str>,
+}
+
+#[interface(name = "org.freedesktop.systemd1.Unit")]
+impl MockUnit {
+    #[zbus(property)]
+    fn load_state(&self) -> String {
+        self.props["LoadState"].to_string()
+    }
+
+    #[zbus(property)]
+    fn active_state(&self) -> String {
+        self.props["ActiveState"].to_string()
+    }
+
+    #[zbus(property)]
+    fn sub_state(&self) -> String {
+        self.props["SubState"].to_string()
+    }
+}
+
+/// Serve the mock systemd over a private peer-to-peer connection and return
+/// (client, server); the server end must be kept alive.
+fn mock_systemd(
+    load: &'static str,
+    active: &'static str,
+    sub: &'static str,
+) -> (Connection, Connection) {
+    let (server_sock, client_sock) = UnixStream::pair().unwrap();
+    let server = thread::spawn(move || {
+        let unit = MockUnit {
+            props: HashMap::from([
+                ("LoadState", load),
+                ("ActiveState", active),
+                ("SubState", sub),
+            ]),
+        };
+        connection::Builder::unix_stream(server_sock)
+            .server(zbus::Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at("/org/freedesktop/systemd1", MockManager)
+            .unwrap()
+            .serve_at(UNIT_PATH, unit)
+            .unwrap()
+            .build()
+            .unwrap()
+    });
+    let client = connection::Builder::unix_stream(client_sock)
+        .p2p()
+        .build()
+        .unwrap();
+    (client, server.join().unwrap())
+}
+
+#[test]
+fn running_unit_is_up() {
+    let (conn, _server) = mock_systemd("loaded", "active", "running");
+    let state = unit_state(&conn, "wg-quick@wg0.service").unwrap();
+    assert!(!state.is_down());
+    assert_eq!(
+        state.to_string(),
+        "unit wg-quick@wg0.service is active (running)"
+    );
+}
+
+#[test]
+fn failed_unit_is_down() {
+    let (conn, _server) = mock_systemd("loaded", "failed", "failed");
+    let state = unit_state(&conn, "wg-quick@wg0.service").unwrap();
+    assert!(state.is_down());
+    assert_eq!(state.active_state, "failed");
+    assert_eq!(state.sub_state, "failed");
+}
+
+#[test]
+fn load_errors_are_reported() {
+    let (conn, _server) = mock_systemd("loaded", "active", "running");
+    let err = unit_state(&conn, "openvpn@work.service").unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("failed to load unit openvpn@work.service"),
+        "{:#}",
+        err
+    );
+}
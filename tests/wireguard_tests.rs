@@ -0,0 +1,102 @@
+use std::fs;
+use std::net::TcpListener;
+use std::time::{Duration, UNIX_EPOCH};
+
+use check_vpn::app::{perform_check, CheckOutcome};
+use check_vpn::config::EffectiveConfig;
+use check_vpn::ip_api::IpInfo;
+use check_vpn::networking::wireguard::{
+    parse_dump, stale_reason, WgPeer, DEFAULT_HANDSHAKE_MAX_AGE_SECS,
+};
+
+const NOW_SECS: u64 = 1_760_690_100;
+
+fn fixture(name: &str) -> Vec<WgPeer> {
+    let path = format!(
+        "{}/tests/fixtures/wireguard/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    parse_dump(&fs::read_to_string(path).expect("read fixture")).expect("parse dump")
+}
+
+fn max_age() -> Duration {
+    Duration::from_secs(DEFAULT_HANDSHAKE_MAX_AGE_SECS)
+}
+
+#[test]
+fn dump_fields_are_parsed() {
+    let peers = fixture("fresh.dump");
+    assert_eq!(peers.len(), 1);
+    let p = &peers[0];
+    assert_eq!(p.public_key, "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=");
+    assert_eq!(p.endpoint, Some("198.51.100.7:51820".parse().unwrap()));
+    assert_eq!(
+        p.latest_handshake,
+        Some(UNIX_EPOCH + Duration::from_secs(1_760_690_000))
+    );
+    assert_eq!((p.rx_bytes, p.tx_bytes), (1_843_622, 502_340));
+}
+
+#[test]
+fn fresh_handshake_is_not_stale() {
+    let now = UNIX_EPOCH + Duration::from_secs(NOW_SECS);
+    assert_eq!(
+        stale_reason("wg0", &fixture("fresh.dump"), now, max_age()),
+        None
+    );
+}
+
+#[test]
+fn old_or_missing_handshakes_are_stale() {
+    // Ten minutes later the first peer has gone quiet too.
+    let later = UNIX_EPOCH + Duration::from_secs(NOW_SECS + 500);
+    let reason = stale_reason("wg0", &fixture("fresh.dump"), later, max_age()).unwrap();
+    assert!(
+        reason.contains("peer xTIBA5rb latest handshake 600s ago"),
+        "{}",
+        reason
+    );
+
+    let now = UNIX_EPOCH + Duration::from_secs(NOW_SECS);
+    let reason = stale_reason("wg0", &fixture("stale.dump"), now, max_age()).unwrap();
+    assert!(
+        reason.contains("peer TrMvSoP4 no handshake yet"),
+        "{}",
+        reason
+    );
+    assert!(!reason.contains("xTIBA5rb"), "{}", reason);
+
+    assert!(stale_reason("wg0", &[], now, max_age())
+        .unwrap()
+        .contains("has no peers"));
+}
+
+#[test]
+fn unreadable_interface_does_not_fail_the_check() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let port = listener.local_addr().unwrap().port();
+    let eff = EffectiveConfig {
+        wireguard_interface: Some("wgnotthere0".to_string()),
+        connectivity_endpoints: vec!["127.0.0.1".to_string()],
+        connectivity_ports: vec![port],
+        connectivity_timeout_secs: 1,
+        connectivity_retries: 1,
+        isp_to_check: "Home ISP".to_string(),
+        dry_run: true,
+        ..Default::default()
+    };
+    let outcome = perform_check(
+        &eff,
+        || {
+            Ok(IpInfo {
+                isp: Some("VPN Provider".to_string()),
+                ..Default::default()
+            })
+        },
+        |_, _| panic!("no action expected"),
+    )
+    .unwrap();
+    assert!(matches!(outcome, CheckOutcome::VpnActive));
+    drop(listener);
+}